@@ -0,0 +1,114 @@
+pub mod config {
+    use crate::ppu::filters::filters::{ColorCorrection, FrameBlending};
+    use crate::ppu::scalers::scalers::{Overlay, Scaler};
+    use serde::{Deserialize, Serialize};
+    use serde_json::Value;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    //read from the working directory unless --config is given
+    pub const CONFIG_FILE: &str = "pog_boy.json";
+
+    //emulator settings, missing keys keep their default value
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    #[serde(default)]
+    pub(crate) struct Config {
+        pub(crate) color_correction: ColorCorrection,
+        pub(crate) frame_blending: FrameBlending,
+        //sgb games get their border and palettes
+        pub(crate) sgb: bool,
+        pub(crate) scaler: Scaler,
+        //factor of the nearest and xbrz scalers
+        pub(crate) scale: u32,
+        pub(crate) overlay: Overlay,
+        pub(crate) window: WindowConfig,
+        //directory of replacement tiles and where tiles missing from it are saved
+        pub(crate) hd_pack: Option<PathBuf>,
+        pub(crate) hd_dump: Option<PathBuf>,
+        //draw every sprite of a line, removing the flicker of games working around the limit
+        pub(crate) unlimited_sprites: bool,
+    }
+
+    //the size and fullscreen state are written back when the emulator is closed
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    #[serde(default)]
+    pub(crate) struct WindowConfig {
+        //starting size as a multiple of the game screen, used until a size is remembered
+        pub(crate) scale: u32,
+        pub(crate) size: Option<[u32; 2]>,
+        pub(crate) fullscreen: bool,
+        //only scale the frame by whole numbers
+        pub(crate) integer_scaling: bool,
+    }
+
+    impl Default for WindowConfig {
+        fn default() -> Self {
+            WindowConfig {
+                scale: 3,
+                size: None,
+                fullscreen: false,
+                integer_scaling: false,
+            }
+        }
+    }
+
+    impl WindowConfig {
+        pub(crate) fn initial_size(&self) -> [u32; 2] {
+            let scale = self.scale.max(1);
+            self.size.unwrap_or([160 * scale, 144 * scale])
+        }
+    }
+
+    impl Default for Config {
+        fn default() -> Self {
+            Config {
+                color_correction: ColorCorrection::default(),
+                frame_blending: FrameBlending::default(),
+                sgb: true,
+                scaler: Scaler::default(),
+                scale: 2,
+                overlay: Overlay::default(),
+                window: WindowConfig::default(),
+                hd_pack: None,
+                hd_dump: None,
+                unlimited_sprites: false,
+            }
+        }
+    }
+
+    impl Config {
+        pub(crate) fn parse(content: &str) -> Result<Config, String> {
+            serde_json::from_str(content).map_err(|error| error.to_string())
+        }
+
+        //a missing file gives the default config, a broken one is reported and ignored
+        pub(crate) fn load(path: &Path) -> Config {
+            match fs::read_to_string(path) {
+                Ok(content) => Config::parse(&content).unwrap_or_else(|error| {
+                    println!("Can't read config file {}: {}", path.display(), error);
+                    Config::default()
+                }),
+                Err(_) => Config::default(),
+            }
+        }
+
+        //only the window settings are updated, so command line overrides aren't saved
+        //and a broken file isn't overwritten
+        pub(crate) fn save_window(path: &Path, window: &WindowConfig) -> Result<(), String> {
+            let mut content = match fs::read_to_string(path) {
+                Ok(content) => {
+                    serde_json::from_str::<Value>(&content).map_err(|error| error.to_string())?
+                }
+                Err(_) => Value::Object(Default::default()),
+            };
+            let settings = content
+                .as_object_mut()
+                .ok_or_else(|| "config file isn't an object".to_string())?;
+            let window = serde_json::to_value(window).map_err(|error| error.to_string())?;
+            settings.insert("window".to_string(), window);
+            let content =
+                serde_json::to_string_pretty(&content).map_err(|error| error.to_string())?;
+            fs::write(path, content).map_err(|error| error.to_string())
+        }
+    }
+}
@@ -90,6 +90,7 @@ pub mod CPU {
             }
 
             self.MMU.interrupt_queued = false;
+            let was_past_bios = self.MMU.is_past_bios;
             //println!("Executing {} (op code 0x{:02X})", instruction, instruction.opcode);
             match self.execute(instruction) {
                 Err(instruction) => {
@@ -115,7 +116,14 @@ pub mod CPU {
                         println!("STATUS AFTER EXECUTING 0x{:04X} {}", address, self);
                     }
                     self.MMU.interrupt_queued = false;
-                    return (self.clock, clock_increment);
+                    //games detect the cgb by the value of A when the boot rom hands over
+                    if !was_past_bios && self.MMU.is_past_bios && self.MMU.cgb_mode {
+                        self.Registers.set_item("A", 0x11);
+                    }
+                    //the cpu is stopped while vram dma copies blocks
+                    let dma_cycles = std::mem::take(&mut self.MMU.hdma.stall_cycles);
+                    self.clock += dma_cycles;
+                    return (self.clock, clock_increment + dma_cycles);
                 }
             };
         }
@@ -0,0 +1,87 @@
+pub mod line_viewer {
+    use crate::debugger::debugger::Viewer;
+    use crate::ppu::palette::palette::Color;
+    use crate::ppu::ppu::{LineRegisters, PPU};
+    use image::{Rgba, RgbaImage};
+
+    const SCREEN_WIDTH: u32 = 160;
+    const SCREEN_HEIGHT: u32 = 144;
+    const STRIP_GAP: u32 = 8;
+    const STRIP_X: u32 = SCREEN_WIDTH + STRIP_GAP;
+    const COLUMN_WIDTH: u32 = 6;
+    const GAP_COLOR: Color = [40, 40, 40, 255];
+    const UNCHANGED_COLOR: Color = [70, 70, 70, 255];
+    //a color for each register of LineRegisters::NAMES, shown on the lines where it changed
+    const CHANGED_COLORS: [Color; 9] = [
+        [230, 25, 75, 255],
+        [60, 180, 75, 255],
+        [255, 225, 25, 255],
+        [0, 130, 200, 255],
+        [245, 130, 48, 255],
+        [145, 30, 180, 255],
+        [70, 240, 240, 255],
+        [240, 50, 230, 255],
+        [210, 245, 60, 255],
+    ];
+
+    //registers changed since the previous line, in the order of LineRegisters::NAMES
+    pub(crate) fn changed_registers(ppu: &PPU, line: usize) -> [bool; 9] {
+        if line == 0 {
+            return [false; 9];
+        }
+        let (previous, current) = (
+            ppu.line_registers[line - 1].values(),
+            ppu.line_registers[line].values(),
+        );
+        std::array::from_fn(|register| previous[register] != current[register])
+    }
+
+    //the last frame next to a column per register, lit on the lines where the game changed it
+    #[derive(Default)]
+    pub(crate) struct LineViewer {}
+
+    impl Viewer for LineViewer {
+        fn title(&self) -> String {
+            format!("Scanline registers ({})", LineRegisters::NAMES.join(" "))
+        }
+
+        fn render(&mut self, ppu: &PPU) -> RgbaImage {
+            let mut image = RgbaImage::from_pixel(
+                STRIP_X + LineRegisters::NAMES.len() as u32 * COLUMN_WIDTH,
+                SCREEN_HEIGHT,
+                Rgba(GAP_COLOR),
+            );
+            if ppu.image_buffer.dimensions() == (SCREEN_WIDTH, SCREEN_HEIGHT) {
+                for (x, y, pixel) in ppu.image_buffer.enumerate_pixels() {
+                    image.put_pixel(x, y, *pixel);
+                }
+            }
+            for line in 0..SCREEN_HEIGHT {
+                let changed = changed_registers(ppu, line as usize);
+                for (register, color) in CHANGED_COLORS.iter().enumerate() {
+                    let color = if changed[register] {
+                        *color
+                    } else {
+                        UNCHANGED_COLOR
+                    };
+                    //a pixel between columns keeps them apart
+                    for x in 0..COLUMN_WIDTH - 1 {
+                        image.put_pixel(
+                            STRIP_X + register as u32 * COLUMN_WIDTH + x,
+                            line,
+                            Rgba(color),
+                        );
+                    }
+                }
+            }
+            image
+        }
+
+        fn describe(&self, ppu: &PPU, _x: u32, y: u32) -> Option<String> {
+            if y >= SCREEN_HEIGHT {
+                return None;
+            }
+            Some(format!("line {}: {}", y, ppu.line_registers[y as usize]))
+        }
+    }
+}
@@ -0,0 +1,247 @@
+pub mod map_viewer {
+    use crate::debugger::debugger::Viewer;
+    use crate::debugger::tile_viewer::tile_viewer::{background_tile_index, TILE_MAPS};
+    use crate::ppu::palette::palette::{Color, PaletteLayer};
+    use crate::ppu::ppu::{LCDCFlags, TilePixelValue, PPU};
+    use image::{Rgba, RgbaImage};
+    use piston_window::Key;
+    use std::path::Path;
+
+    const MAP_TILES: u32 = 32;
+    const MAP_SIZE: u32 = MAP_TILES * 8;
+    const SCREEN_WIDTH: u32 = 160;
+    const SCREEN_HEIGHT: u32 = 144;
+    const VIEWPORT_COLOR: Color = [255, 0, 0, 255];
+    const WINDOW_COLOR: Color = [0, 96, 255, 255];
+    //a hue for each cgb palette, mixed over the tiles by the attribute overlay
+    const ATTRIBUTE_COLORS: [Color; 8] = [
+        [230, 25, 75, 255],
+        [60, 180, 75, 255],
+        [255, 225, 25, 255],
+        [0, 130, 200, 255],
+        [245, 130, 48, 255],
+        [145, 30, 180, 255],
+        [70, 240, 240, 255],
+        [240, 50, 230, 255],
+    ];
+    const ATTRIBUTE_OPACITY: f32 = 0.35;
+
+    //cgb attributes of a map entry, stored at the same address in vram bank 1
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub(crate) struct MapAttributes {
+        pub(crate) palette: u8,
+        pub(crate) bank: usize,
+        pub(crate) x_flip: bool,
+        pub(crate) y_flip: bool,
+        pub(crate) priority: bool,
+    }
+
+    impl MapAttributes {
+        fn new(value: u8) -> MapAttributes {
+            MapAttributes {
+                palette: value & 0x07,
+                bank: ((value & 0x08) >> 3) as usize,
+                x_flip: value & 0x20 != 0,
+                y_flip: value & 0x40 != 0,
+                priority: value & 0x80 != 0,
+            }
+        }
+    }
+
+    fn map_attributes(ppu: &PPU, address: usize) -> Option<MapAttributes> {
+        ppu.cgb_mode
+            .then(|| MapAttributes::new(ppu.video_ram_bank_1[address]))
+    }
+
+    //the whole 32x32 tile map (0 for 9800, 1 for 9c00) as the background would draw it
+    pub(crate) fn render_tile_map(ppu: &PPU, map: usize) -> RgbaImage {
+        let mut image = RgbaImage::new(MAP_SIZE, MAP_SIZE);
+        for entry in 0..(MAP_TILES * MAP_TILES) as usize {
+            let address = TILE_MAPS[map] + entry;
+            let tile_index = background_tile_index(ppu, ppu.video_ram[address]);
+            let attributes = map_attributes(ppu, address);
+            let tile = match attributes {
+                Some(attributes) if attributes.bank == 1 => &ppu.tile_set_bank_1[tile_index],
+                _ => &ppu.tile_set[tile_index],
+            };
+            let (tile_x, tile_y) = (entry as u32 % MAP_TILES * 8, entry as u32 / MAP_TILES * 8);
+            for y in 0..8 {
+                for x in 0..8 {
+                    let (pixel_x, pixel_y) = match attributes {
+                        Some(attributes) => (
+                            if attributes.x_flip { 7 - x } else { x },
+                            if attributes.y_flip { 7 - y } else { y },
+                        ),
+                        None => (x, y),
+                    };
+                    let color_number: TilePixelValue = tile[pixel_y][pixel_x];
+                    let color = match attributes {
+                        Some(attributes) => {
+                            ppu.get_cgb_color(color_number, attributes.palette, false)
+                        }
+                        None => ppu.get_color_from_palette(color_number, PaletteLayer::Background),
+                    };
+                    image.put_pixel(tile_x + x as u32, tile_y + y as u32, Rgba(color));
+                }
+            }
+        }
+        image
+    }
+
+    pub(crate) fn export_tile_map(ppu: &PPU, map: usize, path: &Path) -> Result<(), String> {
+        render_tile_map(ppu, map)
+            .save(path)
+            .map_err(|error| error.to_string())
+    }
+
+    //rectangle outline, wrapping around the map edges like the background does
+    fn draw_rectangle(
+        image: &mut RgbaImage,
+        (x, y): (u32, u32),
+        (width, height): (u32, u32),
+        color: Color,
+    ) {
+        for offset in 0..width {
+            image.put_pixel((x + offset) % MAP_SIZE, y % MAP_SIZE, Rgba(color));
+            image.put_pixel(
+                (x + offset) % MAP_SIZE,
+                (y + height - 1) % MAP_SIZE,
+                Rgba(color),
+            );
+        }
+        for offset in 0..height {
+            image.put_pixel(x % MAP_SIZE, (y + offset) % MAP_SIZE, Rgba(color));
+            image.put_pixel(
+                (x + width - 1) % MAP_SIZE,
+                (y + offset) % MAP_SIZE,
+                Rgba(color),
+            );
+        }
+    }
+
+    //the 9800 or 9c00 map with the scroll viewport, the visible part of the window
+    //and on cgb the palette of each entry
+    pub(crate) struct MapViewer {
+        pub(crate) map: usize,
+        pub(crate) show_attributes: bool,
+        //set by e, the map is saved on the next frame
+        export_requested: bool,
+    }
+
+    impl Default for MapViewer {
+        fn default() -> Self {
+            MapViewer {
+                map: 0,
+                show_attributes: true,
+                export_requested: false,
+            }
+        }
+    }
+
+    impl MapViewer {
+        fn export_path(&self) -> String {
+            format!("tile_map_{:X}.png", 0x8000 + TILE_MAPS[self.map])
+        }
+    }
+
+    impl Viewer for MapViewer {
+        fn title(&self) -> String {
+            format!("Tile map {:X}", 0x8000 + TILE_MAPS[self.map])
+        }
+
+        fn render(&mut self, ppu: &PPU) -> RgbaImage {
+            if self.export_requested {
+                self.export_requested = false;
+                let path = self.export_path();
+                match export_tile_map(ppu, self.map, Path::new(&path)) {
+                    Ok(()) => println!("tile map saved to {}", path),
+                    Err(error) => println!("Can't save tile map to {}: {}", path, error),
+                }
+            }
+
+            let mut image = render_tile_map(ppu, self.map);
+            if ppu.cgb_mode && self.show_attributes {
+                for (x, y, pixel) in image.enumerate_pixels_mut() {
+                    let address = TILE_MAPS[self.map] + ((y / 8) * MAP_TILES + x / 8) as usize;
+                    let tint = ATTRIBUTE_COLORS[(ppu.video_ram_bank_1[address] & 0x07) as usize];
+                    for (channel, tint) in pixel.0.iter_mut().zip(tint).take(3) {
+                        *channel = (*channel as f32 * (1.0 - ATTRIBUTE_OPACITY)
+                            + tint as f32 * ATTRIBUTE_OPACITY)
+                            .round() as u8;
+                    }
+                }
+            }
+
+            let background_map = ppu.get_lcdc_value(LCDCFlags::BG_tile_map_area) as usize;
+            if background_map == self.map {
+                draw_rectangle(
+                    &mut image,
+                    (ppu.scroll_x as u32, ppu.scroll_y as u32),
+                    (SCREEN_WIDTH, SCREEN_HEIGHT),
+                    VIEWPORT_COLOR,
+                );
+            }
+            //the window starts from the top left of its map, at wx - 7, wy on screen
+            let window_map = ppu.get_lcdc_value(LCDCFlags::Window_tile_map_area) as usize;
+            let window_visible = ppu.get_lcdc_value(LCDCFlags::Window_enable)
+                && ppu.window_x <= 166
+                && (ppu.window_y as u32) < SCREEN_HEIGHT;
+            if window_map == self.map && window_visible {
+                let screen_x = (ppu.window_x as u32).saturating_sub(7);
+                draw_rectangle(
+                    &mut image,
+                    (0, 0),
+                    (SCREEN_WIDTH - screen_x, SCREEN_HEIGHT - ppu.window_y as u32),
+                    WINDOW_COLOR,
+                );
+            }
+            image
+        }
+
+        fn describe(&self, ppu: &PPU, x: u32, y: u32) -> Option<String> {
+            if x >= MAP_SIZE || y >= MAP_SIZE {
+                return None;
+            }
+            let (column, row) = (x / 8, y / 8);
+            let address = TILE_MAPS[self.map] + (row * MAP_TILES + column) as usize;
+            let tile_number = ppu.video_ram[address];
+            let tile_index = background_tile_index(ppu, tile_number);
+            let mut description = format!(
+                "{}, {} at {:#06X}: tile {:#04X} at {:#06X}",
+                column,
+                row,
+                0x8000 + address,
+                tile_number,
+                0x8000 + tile_index * 16
+            );
+            if let Some(attributes) = map_attributes(ppu, address) {
+                description.push_str(&format!(
+                    ", bank {}, palette {}",
+                    attributes.bank, attributes.palette
+                ));
+                for (set, name) in [
+                    (attributes.x_flip, "x flip"),
+                    (attributes.y_flip, "y flip"),
+                    (attributes.priority, "priority"),
+                ] {
+                    if set {
+                        description.push_str(", ");
+                        description.push_str(name);
+                    }
+                }
+            }
+            Some(description)
+        }
+
+        //m switches map, a toggles the attribute overlay and e saves the map as png
+        fn handle_key(&mut self, key: Key) -> bool {
+            match key {
+                Key::M => self.map = (self.map + 1) % TILE_MAPS.len(),
+                Key::A => self.show_attributes = !self.show_attributes,
+                Key::E => self.export_requested = true,
+                _ => return false,
+            }
+            true
+        }
+    }
+}
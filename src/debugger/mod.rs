@@ -0,0 +1,69 @@
+pub(crate) mod line_viewer;
+pub(crate) mod map_viewer;
+pub(crate) mod oam_viewer;
+pub(crate) mod tile_viewer;
+
+pub mod debugger {
+    use crate::ppu::ppu::PPU;
+    use image::RgbaImage;
+    use piston_window::Key;
+
+    //a view of the ppu state shown next to the game in the main window
+    pub(crate) trait Viewer: Send {
+        fn title(&self) -> String;
+        //called by the emulation thread once per frame while the view is shown
+        fn render(&mut self, ppu: &PPU) -> RgbaImage;
+        //what's under the mouse, x and y are in image coordinates
+        fn describe(&self, ppu: &PPU, x: u32, y: u32) -> Option<String>;
+        //keys pressed while the view is shown, the ones it doesn't use (false) go to the game
+        fn handle_key(&mut self, _key: Key) -> bool {
+            false
+        }
+        //changes asked from the view, made by the emulation thread before rendering
+        fn update_ppu(&mut self, _ppu: &mut PPU) {}
+    }
+
+    //shared between the emulation thread, which draws the view, and the window thread
+    pub(crate) struct DebugView {
+        pub(crate) viewer: Box<dyn Viewer>,
+        pub(crate) open: bool,
+        pub(crate) cursor: Option<(u32, u32)>,
+        pub(crate) image: RgbaImage,
+        pub(crate) info: Option<String>,
+    }
+
+    impl DebugView {
+        pub(crate) fn new(viewer: Box<dyn Viewer>) -> DebugView {
+            DebugView {
+                viewer,
+                open: false,
+                cursor: None,
+                image: RgbaImage::new(1, 1),
+                info: None,
+            }
+        }
+
+        pub(crate) fn update_ppu(&mut self, ppu: &mut PPU) {
+            if self.open {
+                self.viewer.update_ppu(ppu);
+            }
+        }
+
+        pub(crate) fn update(&mut self, ppu: &PPU) {
+            if !self.open {
+                return;
+            }
+            self.image = self.viewer.render(ppu);
+            self.info = self
+                .cursor
+                .and_then(|(x, y)| self.viewer.describe(ppu, x, y));
+        }
+
+        pub(crate) fn window_title(&self) -> String {
+            match &self.info {
+                Some(info) => format!("{} - {}", self.viewer.title(), info),
+                None => self.viewer.title(),
+            }
+        }
+    }
+}
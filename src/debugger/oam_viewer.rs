@@ -0,0 +1,272 @@
+pub mod oam_viewer {
+    use crate::debugger::debugger::Viewer;
+    use crate::ppu::palette::palette::{Color, PaletteLayer};
+    use crate::ppu::ppu::{Sprite, TilePixelValue, PPU, PPU_SPRITES_NUMBER};
+    use image::{Rgba, RgbaImage};
+    use piston_window::Key;
+    use std::path::Path;
+
+    const SPRITES_PER_ROW: u32 = 8;
+    const SPRITE_ROWS: u32 = PPU_SPRITES_NUMBER as u32 / SPRITES_PER_ROW;
+    //room for an 8x16 sprite with a 4 pixel border
+    const CELL_WIDTH: u32 = 16;
+    const CELL_HEIGHT: u32 = 24;
+    const CELL_PADDING: u32 = 4;
+    const GRID_WIDTH: u32 = SPRITES_PER_ROW * CELL_WIDTH;
+    const SCREEN_GAP: u32 = 8;
+    const SCREEN_X: u32 = GRID_WIDTH + SCREEN_GAP;
+    const SCREEN_WIDTH: u32 = 160;
+    const SCREEN_HEIGHT: u32 = 144;
+    const GAP_COLOR: Color = [40, 40, 40, 255];
+    //behind the transparent pixels of the previews
+    const CELL_COLOR: Color = [80, 80, 80, 255];
+    const SELECTION_COLOR: Color = [255, 220, 0, 255];
+
+    fn sprite_color(ppu: &PPU, sprite: &Sprite, color_number: TilePixelValue) -> Color {
+        if ppu.cgb_mode {
+            ppu.get_cgb_color(color_number, sprite.cgb_palette, true)
+        } else if sprite.palette {
+            ppu.get_color_from_palette(color_number, PaletteLayer::Obj1)
+        } else {
+            ppu.get_color_from_palette(color_number, PaletteLayer::Obj0)
+        }
+    }
+
+    //draws a sprite as it appears on screen, color 0 is left untouched
+    fn draw_sprite(ppu: &PPU, sprite: &Sprite, image: &mut RgbaImage, (x, y): (u32, u32)) {
+        for row in 0..ppu.sprite_height() {
+            let pixels = ppu.sprite_row(*sprite, row);
+            for (column, color_number) in pixels.into_iter().enumerate() {
+                if color_number != TilePixelValue::Zero {
+                    image.put_pixel(
+                        x + column as u32,
+                        y + row as u32,
+                        Rgba(sprite_color(ppu, sprite, color_number)),
+                    );
+                }
+            }
+        }
+    }
+
+    //oam index, position, tile and flags of a sprite
+    pub(crate) fn sprite_info(ppu: &PPU, index: usize) -> String {
+        let sprite = &ppu.sprite_set[index];
+        let tile_number = if ppu.sprite_height() == 16 {
+            sprite.tile_number & 0xFE
+        } else {
+            sprite.tile_number
+        };
+        let mut info = format!(
+            "sprite {}: x {}, y {}, tile {:#04X} at {:#06X}",
+            index,
+            sprite.x,
+            sprite.y,
+            sprite.tile_number,
+            0x8000 + tile_number as usize * 16
+        );
+        if ppu.cgb_mode {
+            info.push_str(&format!(
+                ", bank {}, palette {}",
+                sprite.tile_bank, sprite.cgb_palette
+            ));
+        } else {
+            info.push_str(if sprite.palette { ", obp1" } else { ", obp0" });
+        }
+        for (set, name) in [
+            (sprite.x_flip, "x flip"),
+            (sprite.y_flip, "y flip"),
+            (sprite.background_priority, "behind bg"),
+            (!ppu.render_mask.is_sprite_visible(index), "hidden"),
+        ] {
+            if set {
+                info.push_str(", ");
+                info.push_str(name);
+            }
+        }
+        info
+    }
+
+    //the 40 sprites in oam order, 8 per row, with a transparent background
+    pub(crate) fn render_sprite_sheet(ppu: &PPU) -> RgbaImage {
+        let height = ppu.sprite_height() as u32;
+        let mut image = RgbaImage::new(SPRITES_PER_ROW * 8, SPRITE_ROWS * height);
+        for (index, sprite) in ppu.sprite_set.iter().enumerate() {
+            let (column, row) = (
+                index as u32 % SPRITES_PER_ROW,
+                index as u32 / SPRITES_PER_ROW,
+            );
+            draw_sprite(ppu, sprite, &mut image, (column * 8, row * height));
+        }
+        image
+    }
+
+    pub(crate) fn export_sprite_sheet(ppu: &PPU, path: &Path) -> Result<(), String> {
+        render_sprite_sheet(ppu)
+            .save(path)
+            .map_err(|error| error.to_string())
+    }
+
+    //outline of a rectangle placed inside an area of the image, only the part inside the area is drawn
+    fn draw_rectangle(
+        image: &mut RgbaImage,
+        (area_x, area_y, area_width, area_height): (u32, u32, u32, u32),
+        (x, y): (i32, i32),
+        (width, height): (i32, i32),
+        color: Color,
+    ) {
+        let mut put_pixel = |x: i32, y: i32| {
+            if x >= 0 && y >= 0 && (x as u32) < area_width && (y as u32) < area_height {
+                image.put_pixel(area_x + x as u32, area_y + y as u32, Rgba(color));
+            }
+        };
+        for offset in 0..width {
+            put_pixel(x + offset, y);
+            put_pixel(x + offset, y + height - 1);
+        }
+        for offset in 0..height {
+            put_pixel(x, y + offset);
+            put_pixel(x + width - 1, y + offset);
+        }
+    }
+
+    //previews of the 40 oam entries next to the last frame, where the selected sprite is outlined
+    #[derive(Default)]
+    pub(crate) struct OamViewer {
+        pub(crate) selected: usize,
+        //set by l, e and h, handled on the next frame
+        list_requested: bool,
+        export_requested: bool,
+        hide_requested: bool,
+    }
+
+    impl OamViewer {
+        //the oam index of the preview at x, y
+        pub(crate) fn sprite_at(&self, x: u32, y: u32) -> Option<usize> {
+            if x >= GRID_WIDTH || y >= SPRITE_ROWS * CELL_HEIGHT {
+                return None;
+            }
+            Some(((y / CELL_HEIGHT) * SPRITES_PER_ROW + x / CELL_WIDTH) as usize)
+        }
+    }
+
+    impl Viewer for OamViewer {
+        fn title(&self) -> String {
+            format!("OAM (sprite {})", self.selected)
+        }
+
+        fn render(&mut self, ppu: &PPU) -> RgbaImage {
+            if self.list_requested {
+                self.list_requested = false;
+                for index in 0..PPU_SPRITES_NUMBER {
+                    println!("{}", sprite_info(ppu, index));
+                }
+            }
+            if self.export_requested {
+                self.export_requested = false;
+                let path = "sprites.png";
+                match export_sprite_sheet(ppu, Path::new(path)) {
+                    Ok(()) => println!("sprite sheet saved to {}", path),
+                    Err(error) => println!("Can't save sprite sheet to {}: {}", path, error),
+                }
+            }
+
+            let mut image = RgbaImage::from_pixel(
+                SCREEN_X + SCREEN_WIDTH,
+                SCREEN_HEIGHT.max(SPRITE_ROWS * CELL_HEIGHT),
+                Rgba(GAP_COLOR),
+            );
+            for (index, sprite) in ppu.sprite_set.iter().enumerate() {
+                let (cell_x, cell_y) = (
+                    index as u32 % SPRITES_PER_ROW * CELL_WIDTH,
+                    index as u32 / SPRITES_PER_ROW * CELL_HEIGHT,
+                );
+                for y in 1..CELL_HEIGHT - 1 {
+                    for x in 1..CELL_WIDTH - 1 {
+                        image.put_pixel(cell_x + x, cell_y + y, Rgba(CELL_COLOR));
+                    }
+                }
+                draw_sprite(
+                    ppu,
+                    sprite,
+                    &mut image,
+                    (cell_x + CELL_PADDING, cell_y + CELL_PADDING),
+                );
+                if index == self.selected {
+                    draw_rectangle(
+                        &mut image,
+                        (cell_x, cell_y, CELL_WIDTH, CELL_HEIGHT),
+                        (0, 0),
+                        (CELL_WIDTH as i32, CELL_HEIGHT as i32),
+                        SELECTION_COLOR,
+                    );
+                }
+            }
+
+            //the sgb and scaled frames are bigger, only the game screen is used
+            if ppu.image_buffer.dimensions() == (SCREEN_WIDTH, SCREEN_HEIGHT) {
+                for (x, y, pixel) in ppu.image_buffer.enumerate_pixels() {
+                    image.put_pixel(SCREEN_X + x, y, *pixel);
+                }
+            }
+            let sprite = &ppu.sprite_set[self.selected];
+            draw_rectangle(
+                &mut image,
+                (SCREEN_X, 0, SCREEN_WIDTH, SCREEN_HEIGHT),
+                (sprite.x - 1, sprite.y - 1),
+                (10, ppu.sprite_height() + 2),
+                SELECTION_COLOR,
+            );
+            image
+        }
+
+        //hovering the screen describes the first sprite under the cursor
+        fn describe(&self, ppu: &PPU, x: u32, y: u32) -> Option<String> {
+            if let Some(index) = self.sprite_at(x, y) {
+                return Some(sprite_info(ppu, index));
+            }
+            if x < SCREEN_X || y >= SCREEN_HEIGHT {
+                return None;
+            }
+            let (screen_x, screen_y) = ((x - SCREEN_X) as i32, y as i32);
+            ppu.sprite_set
+                .iter()
+                .position(|sprite| {
+                    (sprite.x..sprite.x + 8).contains(&screen_x)
+                        && (sprite.y..sprite.y + ppu.sprite_height()).contains(&screen_y)
+                })
+                .map(|index| sprite_info(ppu, index))
+        }
+
+        fn update_ppu(&mut self, ppu: &mut PPU) {
+            if self.hide_requested {
+                self.hide_requested = false;
+                ppu.render_mask.toggle_sprite(self.selected);
+            }
+        }
+
+        //arrows select a sprite, h hides it, l prints the whole oam and e saves the sprite sheet as png
+        fn handle_key(&mut self, key: Key) -> bool {
+            let step = match key {
+                Key::Left => PPU_SPRITES_NUMBER - 1,
+                Key::Right => 1,
+                Key::Up => PPU_SPRITES_NUMBER - SPRITES_PER_ROW as usize,
+                Key::Down => SPRITES_PER_ROW as usize,
+                Key::L => {
+                    self.list_requested = true;
+                    0
+                }
+                Key::E => {
+                    self.export_requested = true;
+                    0
+                }
+                Key::H => {
+                    self.hide_requested = true;
+                    0
+                }
+                _ => return false,
+            };
+            self.selected = (self.selected + step) % PPU_SPRITES_NUMBER;
+            true
+        }
+    }
+}
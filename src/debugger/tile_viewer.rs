@@ -0,0 +1,246 @@
+pub mod tile_viewer {
+    use crate::debugger::debugger::Viewer;
+    use crate::ppu::palette::palette::{Color, PaletteLayer};
+    use crate::ppu::ppu::{
+        add_tile_to_rgba_image, LCDCFlags, TilePixelValue, PPU, PPU_TILES_NUMBER,
+    };
+    use image::{Rgba, RgbaImage};
+    use piston_window::Key;
+    use std::fmt::{Display, Formatter};
+
+    const TILES_PER_ROW: u32 = 16;
+    const TILE_ROWS: u32 = PPU_TILES_NUMBER as u32 / TILES_PER_ROW;
+    const BANK_WIDTH: u32 = TILES_PER_ROW * 8;
+    //space between the two cgb banks
+    const BANK_GAP: u32 = 8;
+    const GAP_COLOR: Color = [40, 40, 40, 255];
+    //offsets of the 9800 and 9c00 maps in vram
+    pub(crate) const TILE_MAPS: [usize; 2] = [0x1800, 0x1C00];
+
+    //colors used to draw the tiles
+    #[derive(Debug, Clone, Copy, PartialEq, Default)]
+    pub(crate) enum TilePalette {
+        //color numbers straight to the dmg palette, without bgp/obp
+        Raw,
+        #[default]
+        Background,
+        Obj0,
+        Obj1,
+        CgbBackground(u8),
+        CgbObj(u8),
+    }
+
+    impl TilePalette {
+        //cgb palettes are only offered in cgb mode
+        pub(crate) fn next(self, cgb_mode: bool) -> TilePalette {
+            match self {
+                TilePalette::Raw => TilePalette::Background,
+                TilePalette::Background => TilePalette::Obj0,
+                TilePalette::Obj0 => TilePalette::Obj1,
+                TilePalette::Obj1 if cgb_mode => TilePalette::CgbBackground(0),
+                TilePalette::Obj1 => TilePalette::Raw,
+                TilePalette::CgbBackground(7) => TilePalette::CgbObj(0),
+                TilePalette::CgbBackground(palette) => TilePalette::CgbBackground(palette + 1),
+                TilePalette::CgbObj(7) => TilePalette::Raw,
+                TilePalette::CgbObj(palette) => TilePalette::CgbObj(palette + 1),
+            }
+        }
+
+        pub(crate) fn colors(self, ppu: &PPU) -> [Color; 4] {
+            [
+                TilePixelValue::Zero,
+                TilePixelValue::One,
+                TilePixelValue::Two,
+                TilePixelValue::Three,
+            ]
+            .map(|color_number| match self {
+                TilePalette::Raw => ppu.palette.background[color_number as usize],
+                TilePalette::Background => {
+                    ppu.get_color_from_palette(color_number, PaletteLayer::Background)
+                }
+                TilePalette::Obj0 => ppu.get_color_from_palette(color_number, PaletteLayer::Obj0),
+                TilePalette::Obj1 => ppu.get_color_from_palette(color_number, PaletteLayer::Obj1),
+                TilePalette::CgbBackground(palette) => {
+                    ppu.get_cgb_color(color_number, palette, false)
+                }
+                TilePalette::CgbObj(palette) => ppu.get_cgb_color(color_number, palette, true),
+            })
+        }
+    }
+
+    impl Display for TilePalette {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            match self {
+                TilePalette::Raw => write!(f, "raw"),
+                TilePalette::Background => write!(f, "bgp"),
+                TilePalette::Obj0 => write!(f, "obp0"),
+                TilePalette::Obj1 => write!(f, "obp1"),
+                TilePalette::CgbBackground(palette) => write!(f, "bg {}", palette),
+                TilePalette::CgbObj(palette) => write!(f, "obj {}", palette),
+            }
+        }
+    }
+
+    //where a tile is stored and what is using it right now
+    #[derive(Debug, Clone, PartialEq)]
+    pub(crate) struct TileInfo {
+        pub(crate) bank: usize,
+        //0-383, from 0x8000
+        pub(crate) index: usize,
+        pub(crate) address: u16,
+        //entries of the 9800 and 9c00 maps pointing to the tile with the current lcdc addressing
+        pub(crate) map_references: [usize; 2],
+        //oam index of the sprites drawn with the tile
+        pub(crate) sprites: Vec<usize>,
+    }
+
+    impl Display for TileInfo {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            write!(
+                f,
+                "tile {} bank {} at {:#06X}, 9800 map: {}, 9C00 map: {}, sprites: ",
+                self.index, self.bank, self.address, self.map_references[0], self.map_references[1]
+            )?;
+            if self.sprites.is_empty() {
+                return write!(f, "none");
+            }
+            let sprites: Vec<String> = self
+                .sprites
+                .iter()
+                .map(|sprite| sprite.to_string())
+                .collect();
+            write!(f, "{}", sprites.join(", "))
+        }
+    }
+
+    //index (0-383) of the tile a map entry points to with the current lcdc addressing,
+    //8800 addressing puts tiles 0-127 after the ones at 0x9000
+    pub(crate) fn background_tile_index(ppu: &PPU, tile_number: u8) -> usize {
+        if ppu.get_lcdc_value(LCDCFlags::BG_tile_set_area) || tile_number >= 128 {
+            tile_number as usize
+        } else {
+            tile_number as usize + 256
+        }
+    }
+
+    pub(crate) fn tile_info(ppu: &PPU, bank: usize, index: usize) -> TileInfo {
+        let map_references = TILE_MAPS.map(|map_address| {
+            (map_address..map_address + 0x400)
+                .filter(|&address| {
+                    let tile_index = background_tile_index(ppu, ppu.video_ram[address]);
+                    let tile_bank = if ppu.cgb_mode {
+                        ((ppu.video_ram_bank_1[address] & 0x08) >> 3) as usize
+                    } else {
+                        0
+                    };
+                    tile_index == index && tile_bank == bank
+                })
+                .count()
+        });
+
+        //sprites always use 8000 addressing, tall sprites use an even/odd tile pair
+        let tall_sprites = ppu.get_lcdc_value(LCDCFlags::Obj_size);
+        let sprites = ppu
+            .sprite_set
+            .iter()
+            .enumerate()
+            .filter(|(_, sprite)| {
+                let tile_number = sprite.tile_number as usize;
+                let uses_tile = if tall_sprites {
+                    tile_number & 0xFE == index & !0x01
+                } else {
+                    tile_number == index
+                };
+                let sprite_bank = if ppu.cgb_mode { sprite.tile_bank } else { 0 };
+                uses_tile && sprite_bank == bank
+            })
+            .map(|(oam_index, _)| oam_index)
+            .collect();
+
+        TileInfo {
+            bank,
+            index,
+            address: 0x8000 + index as u16 * 16,
+            map_references,
+            sprites,
+        }
+    }
+
+    //all the tiles of each vram bank in rows of 16, banks side by side
+    #[derive(Default)]
+    pub(crate) struct TileViewer {
+        pub(crate) palette: TilePalette,
+        cgb_mode: bool,
+    }
+
+    impl TileViewer {
+        //the tile bank and index at x, y
+        pub(crate) fn tile_at(&self, x: u32, y: u32) -> Option<(usize, usize)> {
+            let bank = if x >= BANK_WIDTH + BANK_GAP && self.cgb_mode {
+                1
+            } else if x < BANK_WIDTH {
+                0
+            } else {
+                return None;
+            };
+            let bank_x = x - bank as u32 * (BANK_WIDTH + BANK_GAP);
+            if bank_x >= BANK_WIDTH || y >= TILE_ROWS * 8 {
+                return None;
+            }
+            Some((bank, ((y / 8) * TILES_PER_ROW + bank_x / 8) as usize))
+        }
+    }
+
+    impl Viewer for TileViewer {
+        fn title(&self) -> String {
+            format!("Tiles ({})", self.palette)
+        }
+
+        fn render(&mut self, ppu: &PPU) -> RgbaImage {
+            self.cgb_mode = ppu.cgb_mode;
+            if !ppu.cgb_mode {
+                //cgb palettes don't exist anymore
+                if let TilePalette::CgbBackground(_) | TilePalette::CgbObj(_) = self.palette {
+                    self.palette = TilePalette::Background;
+                }
+            }
+            let banks = if ppu.cgb_mode { 2 } else { 1 };
+            let width = banks * BANK_WIDTH + (banks - 1) * BANK_GAP;
+            let mut image = RgbaImage::from_pixel(width, TILE_ROWS * 8, Rgba(GAP_COLOR));
+            let colors = self.palette.colors(ppu);
+            for bank in 0..banks {
+                let tile_set = if bank == 0 {
+                    &ppu.tile_set
+                } else {
+                    &ppu.tile_set_bank_1
+                };
+                let bank_x = bank * (BANK_WIDTH + BANK_GAP);
+                for (index, tile) in tile_set.iter().enumerate() {
+                    let (column, row) =
+                        (index as u32 % TILES_PER_ROW, index as u32 / TILES_PER_ROW);
+                    add_tile_to_rgba_image(
+                        *tile,
+                        &mut image,
+                        (bank_x + column * 8, row * 8),
+                        &colors,
+                    );
+                }
+            }
+            image
+        }
+
+        fn describe(&self, ppu: &PPU, x: u32, y: u32) -> Option<String> {
+            self.tile_at(x, y)
+                .map(|(bank, index)| tile_info(ppu, bank, index).to_string())
+        }
+
+        //p cycles the palettes
+        fn handle_key(&mut self, key: Key) -> bool {
+            if key != Key::P {
+                return false;
+            }
+            self.palette = self.palette.next(self.cgb_mode);
+            true
+        }
+    }
+}
@@ -0,0 +1,45 @@
+pub mod display {
+    //where the frame is drawn inside the window, the rest is left black
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub(crate) struct Viewport {
+        pub(crate) x: f64,
+        pub(crate) y: f64,
+        pub(crate) scale: f64,
+    }
+
+    //frames side by side from left to right, all drawn at the same height as big as the
+    //window allows while keeping their aspect ratio. integer scaling rounds down the scale
+    //of each frame on its own, unless the window is smaller than the frame
+    pub(crate) fn fit_frames(
+        window_size: [f64; 2],
+        frame_sizes: &[[u32; 2]],
+        integer_scaling: bool,
+    ) -> Vec<Viewport> {
+        let row_aspect_ratio: f64 = frame_sizes
+            .iter()
+            .map(|[width, height]| *width as f64 / *height as f64)
+            .sum();
+        let row_height = window_size[1].min(window_size[0] / row_aspect_ratio);
+        let mut column_x = (window_size[0] - row_height * row_aspect_ratio) / 2.0;
+        frame_sizes
+            .iter()
+            .map(|[width, height]| {
+                let (frame_width, frame_height) = (*width as f64, *height as f64);
+                let scale = row_height / frame_height;
+                let scale = if integer_scaling && scale >= 1.0 {
+                    scale.floor()
+                } else {
+                    scale
+                };
+                let column_width = row_height / frame_height * frame_width;
+                let viewport = Viewport {
+                    x: (column_x + (column_width - frame_width * scale) / 2.0).floor(),
+                    y: ((window_size[1] - frame_height * scale) / 2.0).floor(),
+                    scale,
+                };
+                column_x += column_width;
+                viewport
+            })
+            .collect()
+    }
+}
@@ -1 +1,2 @@
+pub(crate) mod display;
 pub(crate) mod gamepad;
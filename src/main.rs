@@ -1,35 +1,235 @@
 extern crate core;
 
+mod config;
 mod cpu;
+mod debugger;
 mod io;
 mod memory;
 mod ppu;
+mod tools;
 
 #[cfg(test)]
 mod tests;
 
+use crate::config::config::{Config, WindowConfig, CONFIG_FILE};
 use crate::cpu::CPU::InterruptType;
 use crate::cpu::CPU::CPU;
+use crate::debugger::debugger::DebugView;
+use crate::debugger::line_viewer::line_viewer::LineViewer;
+use crate::debugger::map_viewer::map_viewer::MapViewer;
+use crate::debugger::oam_viewer::oam_viewer::OamViewer;
+use crate::debugger::tile_viewer::tile_viewer::TileViewer;
+use crate::io::display::display::fit_frames;
 use crate::memory::cartridge::cartridge::{read_cartridge, Cartridge, CartridgeInfo};
+use crate::memory::dat::dat::RomDatabase;
 use crate::memory::mmu;
 use crate::memory::mmu::mmu::MMU;
+use crate::ppu::compatibility::compatibility::compatibility_palette;
+use crate::ppu::filters::filters::{ColorCorrection, FrameBlending, FrameFilter};
+use crate::ppu::hd_pack::hd_pack::HdPack;
+use crate::ppu::palette::palette::{load_user_palettes, PaletteSelector};
+use crate::ppu::scalers::scalers::{Overlay, Scaler, Upscaler};
 use crate::ppu::ppu::{
-    dump_current_screen_tiles, dump_tile_map, tile_set_to_rgba_image, PpuMode, PPU,
+    dump_current_screen_tiles, dump_line_registers, dump_tile_map, tile_set_to_rgba_image,
+    PpuMode, PPU,
 };
+use crate::tools::fix::fix;
+use crate::tools::info::info;
 use image;
 use image::ColorType::{Rgb8, Rgba8};
 use image::RgbaImage;
-use piston_window::{image as draw_image, Button, ButtonState, Context, Event, Input, Key, PistonWindow, Texture, TextureContext, TextureSettings, WindowSettings, AdvancedWindow};
+use piston_window::{clear, image as draw_image, Button, ButtonState, Context, Event, Filter, G2dTexture, G2dTextureContext, Input, Key, Motion, PistonWindow, Texture, TextureContext, TextureSettings, Transformed, Window, WindowSettings, AdvancedWindow, ImageSize};
 use std::fs::File;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::{Receiver, Sender};
 use std::sync::{mpsc, Arc, Mutex};
 use std::time::{Duration, Instant, SystemTime};
 use std::{env, fs, thread, time};
+use winit::window::Fullscreen;
+
+struct LaunchOptions {
+    rom_name: String,
+    patch_files: Vec<PathBuf>,
+    dat_file: Option<PathBuf>,
+    palette_name: Option<String>,
+    palette_file: Option<PathBuf>,
+    config_file: Option<PathBuf>,
+    //override the config file
+    color_correction: Option<ColorCorrection>,
+    frame_blending: Option<FrameBlending>,
+    scaler: Option<Scaler>,
+    scale: Option<u32>,
+    overlay: Option<Overlay>,
+    window_scale: Option<u32>,
+    fullscreen: bool,
+    integer_scaling: bool,
+    hd_pack: Option<PathBuf>,
+    hd_dump: Option<PathBuf>,
+    unlimited_sprites: bool,
+}
+
+//pog_boy [--patch <file>]... [--dat <file>] [--palette <name>] [--palette-file <file>]
+//        [--config <file>] [--color-correction <none|cgb|agb>] [--frame-blending <none|average|ghosting>]
+//        [--scaler <none|nearest|scale2x|scale3x|hq2x|hq4x|xbrz>] [--scale <factor>] [--overlay <none|grid|scanlines>]
+//        [--window-scale <factor>] [--fullscreen] [--integer-scaling]
+//        [--hd-pack <directory>] [--hd-dump <directory>] [--unlimited-sprites] <rom-name>
+fn parse_launch_options(args: &[String]) -> LaunchOptions {
+    let mut patch_files = vec![];
+    let mut dat_file = None;
+    let mut palette_name = None;
+    let mut palette_file = None;
+    let mut config_file = None;
+    let mut color_correction = None;
+    let mut frame_blending = None;
+    let mut scaler = None;
+    let mut scale = None;
+    let mut overlay = None;
+    let mut window_scale = None;
+    let mut fullscreen = false;
+    let mut integer_scaling = false;
+    let mut hd_pack = None;
+    let mut hd_dump = None;
+    let mut unlimited_sprites = false;
+    let mut rom_name = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--patch" => patch_files.push(PathBuf::from(args.next().expect("missing patch file"))),
+            "--dat" => dat_file = Some(PathBuf::from(args.next().expect("missing dat file"))),
+            "--palette" => palette_name = Some(args.next().expect("missing palette name").clone()),
+            "--palette-file" => {
+                palette_file = Some(PathBuf::from(args.next().expect("missing palette file")))
+            }
+            "--config" => config_file = Some(PathBuf::from(args.next().expect("missing config file"))),
+            "--color-correction" => {
+                let name = args.next().expect("missing color correction");
+                color_correction = Some(
+                    ColorCorrection::from_name(name)
+                        .expect("color correction should be none, cgb or agb"),
+                )
+            }
+            "--frame-blending" => {
+                let name = args.next().expect("missing frame blending");
+                frame_blending = Some(
+                    FrameBlending::from_name(name)
+                        .expect("frame blending should be none, average or ghosting"),
+                )
+            }
+            "--scaler" => {
+                let name = args.next().expect("missing scaler");
+                scaler = Some(Scaler::from_name(name).expect(
+                    "scaler should be none, nearest, scale2x, scale3x, hq2x, hq4x or xbrz",
+                ))
+            }
+            "--scale" => {
+                let factor = args.next().expect("missing scale");
+                scale = Some(factor.parse().expect("scale should be a number"))
+            }
+            "--overlay" => {
+                let name = args.next().expect("missing overlay");
+                overlay =
+                    Some(Overlay::from_name(name).expect("overlay should be none, grid or scanlines"))
+            }
+            "--window-scale" => {
+                let factor = args.next().expect("missing window scale");
+                window_scale = Some(factor.parse().expect("window scale should be a number"))
+            }
+            "--fullscreen" => fullscreen = true,
+            "--integer-scaling" => integer_scaling = true,
+            "--hd-pack" => hd_pack = Some(PathBuf::from(args.next().expect("missing hd pack"))),
+            "--hd-dump" => {
+                hd_dump = Some(PathBuf::from(args.next().expect("missing hd dump directory")))
+            }
+            "--unlimited-sprites" => unlimited_sprites = true,
+            _ => rom_name = Some(arg.clone()),
+        }
+    }
+    LaunchOptions {
+        rom_name: rom_name.expect("missing rom name"),
+        patch_files,
+        dat_file,
+        palette_name,
+        palette_file,
+        config_file,
+        color_correction,
+        frame_blending,
+        scaler,
+        scale,
+        overlay,
+        window_scale,
+        fullscreen,
+        integer_scaling,
+        hd_pack,
+        hd_dump,
+        unlimited_sprites,
+    }
+}
+
+fn config_file(launch_options: &LaunchOptions) -> PathBuf {
+    launch_options
+        .config_file
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(CONFIG_FILE))
+}
+
+fn load_config(launch_options: &LaunchOptions) -> Config {
+    let mut config = Config::load(&config_file(launch_options));
+    if let Some(color_correction) = launch_options.color_correction {
+        config.color_correction = color_correction;
+    }
+    if let Some(frame_blending) = launch_options.frame_blending {
+        config.frame_blending = frame_blending;
+    }
+    if let Some(scaler) = launch_options.scaler {
+        config.scaler = scaler;
+    }
+    if let Some(scale) = launch_options.scale {
+        config.scale = scale;
+    }
+    if let Some(overlay) = launch_options.overlay {
+        config.overlay = overlay;
+    }
+    //an explicit scale wins over the remembered window size
+    if let Some(window_scale) = launch_options.window_scale {
+        config.window.scale = window_scale;
+        config.window.size = None;
+    }
+    config.window.fullscreen |= launch_options.fullscreen;
+    config.window.integer_scaling |= launch_options.integer_scaling;
+    if launch_options.hd_pack.is_some() {
+        config.hd_pack = launch_options.hd_pack.clone();
+    }
+    if launch_options.hd_dump.is_some() {
+        config.hd_dump = launch_options.hd_dump.clone();
+    }
+    config.unlimited_sprites |= launch_options.unlimited_sprites;
+    config
+}
+
+//enhancements changing what the game would show are listed after its name
+fn window_title(game_name: &str, unlimited_sprites: bool) -> String {
+    if unlimited_sprites {
+        format!("{} [no sprite limit]", game_name)
+    } else {
+        game_name.to_string()
+    }
+}
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    let rom_name = args.last().unwrap().clone();
+    //subcommands that don't boot the emulator
+    match args.get(1).map(String::as_str) {
+        Some("info") => return info::run(&args[2..]),
+        Some("fix") => return fix::run(&args[2..]),
+        _ => {}
+    }
+    let launch_options = parse_launch_options(&args[1..]);
+    let config = load_config(&launch_options);
+    let config_file = config_file(&launch_options);
+    //the command line overrides are kept out of the saved window settings
+    let file_window_config = Config::load(&config_file).window;
+    let launch_window_config = config.window.clone();
+    let mut window_config = config.window.clone();
 
     let (cpu_sender, window_receiver): (Sender<String>, Receiver<String>) = mpsc::channel();
     let (window_sender, cpu_receiver): (Sender<(Key, ButtonState)>, Receiver<(Key, ButtonState)>) =
@@ -37,33 +237,68 @@ fn main() {
 
     let image_buffer = Arc::new(Mutex::new(RgbaImage::new(160, 144)));
     let image_buffer_reference = image_buffer.clone();
+    let debug_views = Arc::new(Mutex::new(create_debug_views()));
+    let debug_views_reference = debug_views.clone();
 
-    let cpu_thread =
-        thread::spawn(move || run_cpu(cpu_sender, cpu_receiver, image_buffer_reference, rom_name));
+    let cpu_thread = thread::spawn(move || {
+        run_cpu(
+            cpu_sender,
+            cpu_receiver,
+            image_buffer_reference,
+            debug_views_reference,
+            launch_options,
+            config,
+        )
+    });
 
-    let mut window: PistonWindow = WindowSettings::new("Pog!", [160, 144])
-        .exit_on_esc(true)
-        .build()
-        .unwrap();
-
-    let (mut texture, mut texture_context) = {
-        let mut texture_context = TextureContext {
-            factory: window.factory.clone(),
-            encoder: window.factory.create_command_buffer().into(),
-        };
-        let image_buffer = image::ImageBuffer::new(160, 144);
-        let texture =
-            Texture::from_image(&mut texture_context, &image_buffer, &TextureSettings::new())
-                .unwrap();
-        (texture, texture_context)
-    };
+    let mut title = String::from("Pog!");
+    let mut window = build_window(&title, &window_config);
+    set_fullscreen(&mut window, window_config.fullscreen);
+    //the game frame and the open debug view, drawn side by side
+    let mut textures = vec![
+        create_texture(&mut window, &RgbaImage::new(160, 144)),
+        create_texture(&mut window, &RgbaImage::new(1, 1)),
+    ];
+    let mut open_view: Option<usize> = None;
+    let mut shown_title = title.clone();
 
     while let Some(event) = window.next() {
         match event {
             Event::Input(input, _) => match input {
                 Input::Button(ButtonArgs) => match ButtonArgs.button {
+                    Button::Keyboard(Key::F11) => {
+                        if ButtonArgs.state == ButtonState::Press {
+                            window_config.fullscreen = !window_config.fullscreen;
+                            set_fullscreen(&mut window, window_config.fullscreen);
+                        }
+                    }
+                    //a function key shows its view next to the game, or hides it when already shown
+                    Button::Keyboard(key) if DEBUG_VIEW_KEYS.contains(&key) => {
+                        if ButtonArgs.state == ButtonState::Press {
+                            let index =
+                                DEBUG_VIEW_KEYS.iter().position(|&view_key| view_key == key).unwrap();
+                            let mut debug_views = debug_views.lock().unwrap();
+                            let shown_view = open_view.take();
+                            if let Some(shown_index) = shown_view {
+                                debug_views[shown_index].open = false;
+                                debug_views[shown_index].cursor = None;
+                            }
+                            if shown_view != Some(index) {
+                                debug_views[index].open = true;
+                                open_view = Some(index);
+                            }
+                        }
+                    }
+                    //the shown view gets the presses of the keys it uses, the game gets the rest
+                    //and every release, so no button stays held
                     Button::Keyboard(key) => {
-                        window_sender.send((key, ButtonArgs.state)).unwrap();
+                        let used_by_view = ButtonArgs.state == ButtonState::Press
+                            && open_view.is_some_and(|index| {
+                                debug_views.lock().unwrap()[index].viewer.handle_key(key)
+                            });
+                        if !used_by_view {
+                            window_sender.send((key, ButtonArgs.state)).unwrap();
+                        }
                     }
                     _ => {}
                 },
@@ -73,62 +308,266 @@ fn main() {
                         .unwrap();
                 },
                 Input::Resize(resize_args) => {
-                    //println!("{} - {}", resize_args.window_size[0], resize_args.window_size[1])
+                    //the fullscreen size isn't the one to restore
+                    if !window_config.fullscreen {
+                        let [width, height] = resize_args.window_size;
+                        window_config.size = Some([width as u32, height as u32]);
+                    }
+                }
+                //the cursor is converted to the coordinates of the view image
+                Input::Move(Motion::MouseCursor([x, y])) => {
+                    if let Some(index) = open_view {
+                        let frame_size = image_buffer.lock().unwrap().dimensions();
+                        let mut debug_views = debug_views.lock().unwrap();
+                        let debug_view = &mut debug_views[index];
+                        let (width, height) = debug_view.image.dimensions();
+                        let size = window.size();
+                        let viewport = fit_frames(
+                            [size.width, size.height],
+                            &[[frame_size.0, frame_size.1], [width, height]],
+                            window_config.integer_scaling,
+                        )[1];
+                        let image_x = (x - viewport.x) / viewport.scale;
+                        let image_y = (y - viewport.y) / viewport.scale;
+                        debug_view.cursor = if image_x >= 0.0
+                            && image_y >= 0.0
+                            && image_x < width as f64
+                            && image_y < height as f64
+                        {
+                            Some((image_x as u32, image_y as u32))
+                        } else {
+                            None
+                        };
+                    }
+                }
+                Input::Cursor(false) => {
+                    if let Some(index) = open_view {
+                        debug_views.lock().unwrap()[index].cursor = None;
+                    }
                 }
                 _ => {}
             },
             Event::Loop(_) => {
                 let received = window_receiver.try_recv();
                 if received.is_ok() {
-                    window.set_title(received.unwrap());
+                    title = received.unwrap();
                 }
-                window.draw_2d(&event, |c: Context, g, device| {
-                    texture
-                        .update(&mut texture_context, &*image_buffer.lock().unwrap())
-                        .unwrap();
-                    draw_image(&texture, c.transform, g);
-                    texture_context.encoder.flush(device);
-                });
+                let frame = image_buffer.lock().unwrap().clone();
+                let mut frames = vec![frame];
+                //the view describes what's under the cursor after the game title
+                let mut window_title = title.clone();
+                if let Some(index) = open_view {
+                    let debug_views = debug_views.lock().unwrap();
+                    frames.push(debug_views[index].image.clone());
+                    window_title = format!("{} - {}", title, debug_views[index].window_title());
+                }
+                if window_title != shown_title {
+                    window.set_title(window_title.clone());
+                    shown_title = window_title;
+                }
+                let integer_scaling = window_config.integer_scaling;
+                draw_frames(&mut window, &event, &mut textures, &frames, integer_scaling);
             }
             _ => {}
         }
     }
 
+    //only the size and fullscreen state changed while running are saved over the file values
+    let mut saved_window_config = file_window_config;
+    if window_config.initial_size() != launch_window_config.initial_size() {
+        saved_window_config.size = window_config.size;
+    }
+    if window_config.fullscreen != launch_window_config.fullscreen {
+        saved_window_config.fullscreen = window_config.fullscreen;
+    }
+    if let Err(error) = Config::save_window(&config_file, &saved_window_config) {
+        println!("Can't save window settings to {}: {}", config_file.display(), error);
+    }
     let _ = cpu_thread.join();
 }
 
+fn build_window(title: &str, window_config: &WindowConfig) -> PistonWindow {
+    WindowSettings::new(title, window_config.initial_size())
+        .exit_on_esc(true)
+        .resizable(true)
+        .build()
+        .unwrap()
+}
+
+//a new window would need a second event loop, so the existing one changes mode
+fn set_fullscreen(window: &mut PistonWindow, fullscreen: bool) {
+    let mode = if fullscreen {
+        Some(Fullscreen::Borderless(None))
+    } else {
+        None
+    };
+    window.window.window.set_fullscreen(mode);
+}
+
+//pixels stay sharp when the texture is scaled up
+fn create_texture(window: &mut PistonWindow, frame: &RgbaImage) -> (G2dTexture, G2dTextureContext) {
+    let mut texture_context = TextureContext {
+        factory: window.factory.clone(),
+        encoder: window.factory.create_command_buffer().into(),
+    };
+    let texture_settings = TextureSettings::new().filter(Filter::Nearest);
+    let texture = Texture::from_image(&mut texture_context, frame, &texture_settings).unwrap();
+    (texture, texture_context)
+}
+
+//draws the frames scaled to the window next to each other, sgb borders and the upscalers
+//change the size of the game frame
+fn draw_frames(
+    window: &mut PistonWindow,
+    event: &Event,
+    textures: &mut [(G2dTexture, G2dTextureContext)],
+    frames: &[RgbaImage],
+    integer_scaling: bool,
+) {
+    for ((texture, texture_context), frame) in textures.iter_mut().zip(frames) {
+        if frame.dimensions() != texture.get_size() {
+            (*texture, *texture_context) = create_texture(window, frame);
+        }
+    }
+    window.draw_2d(event, |c: Context, g, device| {
+        let frame_sizes: Vec<[u32; 2]> =
+            frames.iter().map(|frame| [frame.width(), frame.height()]).collect();
+        let viewports = fit_frames(c.get_view_size(), &frame_sizes, integer_scaling);
+        clear([0.0, 0.0, 0.0, 1.0], g);
+        for (((texture, texture_context), frame), viewport) in
+            textures.iter_mut().zip(frames).zip(viewports)
+        {
+            texture.update(texture_context, frame).unwrap();
+            draw_image(
+                texture,
+                c.transform
+                    .trans(viewport.x, viewport.y)
+                    .scale(viewport.scale, viewport.scale),
+                g,
+            );
+            texture_context.encoder.flush(device);
+        }
+    });
+}
+
+//keys showing and hiding the debugger views, in the order of create_debug_views
+const DEBUG_VIEW_KEYS: [Key; 4] = [Key::F1, Key::F2, Key::F3, Key::F4];
+
+fn create_debug_views() -> Vec<DebugView> {
+    vec![
+        DebugView::new(Box::new(TileViewer::default())),
+        DebugView::new(Box::new(MapViewer::default())),
+        DebugView::new(Box::new(OamViewer::default())),
+        DebugView::new(Box::new(LineViewer::default())),
+    ]
+}
+
 fn run_cpu(
     cpu_sender: Sender<String>,
     cpu_receiver: Receiver<(Key, ButtonState)>,
     image_buffer_reference: Arc<Mutex<RgbaImage>>,
-    rom_name: String,
+    debug_views: Arc<Mutex<Vec<DebugView>>>,
+    launch_options: LaunchOptions,
+    config: Config,
 ) {
-    let cartridge: Cartridge = read_cartridge(&rom_name);
-    if let cartridge_info = cartridge.cartridge_info.unwrap() {
-        cpu_sender.send(cartridge_info.game_title().to_owned()).expect("Can't read cartridge title");
+    //a broken dat file shouldn't stop the game from booting
+    let rom_database = launch_options.dat_file.as_ref().and_then(|dat_file| {
+        match RomDatabase::load(dat_file) {
+            Ok(rom_database) => {
+                println!("loaded {} roms from {}", rom_database.len(), dat_file.display());
+                Some(rom_database)
+            }
+            Err(error) => {
+                println!("Can't read dat file {}: {}", dat_file.display(), error);
+                None
+            }
+        }
+    });
+    let cartridge: Cartridge = read_cartridge(
+        &launch_options.rom_name,
+        &launch_options.patch_files,
+        rom_database.as_ref(),
+    );
+    let game_name = cartridge.game_name();
+    cpu_sender
+        .send(window_title(&game_name, config.unlimited_sprites))
+        .expect("Can't read cartridge title");
+
+    let user_palettes = match &launch_options.palette_file {
+        Some(palette_file) => load_user_palettes(palette_file).unwrap_or_else(|error| {
+            println!("Can't read palette file {}: {}", palette_file.display(), error);
+            vec![]
+        }),
+        None => vec![],
+    };
+    let mut palette_selector = PaletteSelector::new(user_palettes);
+    match (&launch_options.palette_name, &cartridge.cartridge_info) {
+        (Some(palette_name), _) => {
+            if !palette_selector.select(palette_name) {
+                println!("unknown palette {}", palette_name);
+            }
+        }
+        //dmg only games get colorized like the cgb boot rom would do
+        (None, Some(cartridge_info)) if !cartridge_info.supports_cgb() => {
+            palette_selector.insert(compatibility_palette(cartridge_info));
+        }
+        _ => {}
     }
 
     let mut ppu: PPU = PPU::new();
+    ppu.palette = palette_selector.current().clone();
     let mmu: MMU = MMU::new(Some(cartridge), &mut ppu);
     let mut cpu: CPU = CPU::new(mmu);
+    if !config.sgb {
+        cpu.MMU.sgb = None;
+    }
+    cpu.MMU.PPU.unlimited_sprites = config.unlimited_sprites;
+    let mut frame_filter = FrameFilter::new(config.color_correction, config.frame_blending);
+    let upscaler = Upscaler::new(config.scaler, config.scale, config.overlay);
+    //sgb games keep their border and palettes instead
+    let mut hd_pack = None;
+    if (config.hd_pack.is_some() || config.hd_dump.is_some()) && cpu.MMU.sgb.is_none() {
+        match HdPack::load(config.hd_pack.as_deref(), config.hd_dump.as_deref()) {
+            Ok(pack) => {
+                println!("loaded {} hd tiles at {}x", pack.len(), pack.scale);
+                pack.attach(&mut cpu.MMU.PPU);
+                hd_pack = Some(pack);
+            }
+            Err(error) => println!("Can't load hd pack: {}", error),
+        }
+    }
 
     //cpu.MMU.disassemble(0x300, 0x400, 0x359);
     let mut cycles_delta = 0;
     let cycles_per_frame = 69905;
     let mut time_ref = Instant::now();
+    //the last cheat toggled with the number keys
+    let mut selected_cheat = None;
 
     'main: loop {
         let (clock, mut clock_delta) = cpu.step();
         clock_delta += cpu.check_interrupts();
         let (current_ppu_mode, should_rise_vblank_interrupt, should_rise_stat_interrupt) =
             cpu.MMU.PPU.step(clock_delta);
+        cpu.MMU.step_hdma();
         cpu.increment_timer(clock_delta as i32);
 
         cycles_delta += clock_delta;
 
         if cycles_delta >= cycles_per_frame {
             let mut image_buffer = image_buffer_reference.lock().unwrap();
-            (*image_buffer) = cpu.MMU.PPU.image_buffer.clone();
+            //sgb games are drawn with their border around the screen, hd packs at their scale
+            let frame = match (cpu.MMU.sgb.as_mut(), hd_pack.as_mut()) {
+                (Some(sgb), _) => frame_filter.apply(&sgb.compose_frame(&cpu.MMU.PPU)),
+                (None, Some(hd_pack)) => frame_filter.apply(&hd_pack.compose(&mut cpu.MMU.PPU)),
+                (None, None) => frame_filter.apply(&cpu.MMU.PPU.image_buffer),
+            };
+            (*image_buffer) = upscaler.apply(&frame);
+            for debug_view in debug_views.lock().unwrap().iter_mut() {
+                debug_view.update_ppu(&mut cpu.MMU.PPU);
+                debug_view.update(&cpu.MMU.PPU);
+            }
+            cpu.MMU.apply_cheats();
 
             let elapsed = Instant::now().duration_since(time_ref);
             time_ref = Instant::now();
@@ -160,14 +599,62 @@ fn run_cpu(
                             //toggle cpu logging
                             cpu.logging = !cpu.logging;
                         }
-                        Key::T => {
-                            //toggle tileset area
-                            if cpu.MMU.read_byte(0xFF40) == 0x91 {
-                                cpu.MMU.write_byte(0xFF40, 0x81);
-                            } else {
-                                cpu.MMU.write_byte(0xFF40, 0x91);
+                        Key::B | Key::W | Key::O => {
+                            //hide or show a layer, lcdc is left alone
+                            let render_mask = &mut cpu.MMU.PPU.render_mask;
+                            let (name, visible) = match key {
+                                Key::B => ("background", &mut render_mask.background),
+                                Key::W => ("window", &mut render_mask.window),
+                                _ => ("sprites", &mut render_mask.sprites),
+                            };
+                            *visible = !*visible;
+                            println!("{} {}", name, if *visible { "shown" } else { "hidden" });
+                        }
+                        Key::U => {
+                            //lift or restore the 10 sprites per line limit
+                            let unlimited_sprites = !cpu.MMU.PPU.unlimited_sprites;
+                            cpu.MMU.PPU.unlimited_sprites = unlimited_sprites;
+                            cpu_sender
+                                .send(window_title(&game_name, unlimited_sprites))
+                                .expect("Can't update window title");
+                        }
+                        Key::C => {
+                            //list cheats, numbered like the keys toggling them
+                            let cheats = cpu.MMU.cartridge.cheats.cheats();
+                            for (index, cheat) in cheats.iter().enumerate() {
+                                println!("{}: {}", index + 1, cheat);
+                            }
+                        }
+                        Key::D1
+                        | Key::D2
+                        | Key::D3
+                        | Key::D4
+                        | Key::D5
+                        | Key::D6
+                        | Key::D7
+                        | Key::D8
+                        | Key::D9 => {
+                            //toggle a cheat, which becomes the one delete removes
+                            let index = key as usize - Key::D1 as usize;
+                            if index < cpu.MMU.cartridge.cheats.cheats().len() {
+                                let enabled = cpu.MMU.cartridge.cheats.toggle(index);
+                                let state = if enabled { "enabled" } else { "disabled" };
+                                println!("cheat {} {}", index + 1, state);
+                                selected_cheat = Some(index);
                             }
                         }
+                        Key::Delete => {
+                            //remove the selected cheat
+                            let cheats = &mut cpu.MMU.cartridge.cheats;
+                            if let Some(cheat) = selected_cheat.take().and_then(|index| cheats.remove(index)) {
+                                println!("removed cheat {}", cheat.code);
+                            }
+                        }
+                        Key::P => {
+                            //cycle palettes
+                            cpu.MMU.PPU.palette = palette_selector.next().clone();
+                            println!("palette {}", cpu.MMU.PPU.palette.name);
+                        }
                         Key::D => {
                             println!("{}", cpu);
 
@@ -180,7 +667,7 @@ fn run_cpu(
 
                             //dump current tileset
                             let tile_set_dump: RgbaImage =
-                                tile_set_to_rgba_image(cpu.MMU.PPU.tile_set);
+                                tile_set_to_rgba_image(cpu.MMU.PPU.tile_set, &cpu.MMU.PPU.palette.background);
                             image::save_buffer(
                                 &Path::new("last_tile_set.png"),
                                 &*tile_set_dump.into_vec(),
@@ -199,6 +686,10 @@ fn run_cpu(
                             let second_tile_map = dump_tile_map(cpu.MMU.PPU.video_ram, 0x1C00);
                             fs::write("tm2.txt", second_tile_map).expect("Unable to write file");
 
+                            let line_registers = dump_line_registers(&cpu.MMU.PPU);
+                            fs::write("line_registers.txt", line_registers)
+                                .expect("Unable to write file");
+
                             let current_screen_tiles =
                                 format!("{:?}", dump_current_screen_tiles(cpu.MMU.PPU));
                             fs::write("current_screen_tiles.txt", current_screen_tiles)
@@ -1,16 +1,28 @@
 pub mod cartridge {
+    use crate::memory::cheats::cheats::CheatEngine;
+    use crate::memory::dat::dat::{DatEntry, RomDatabase};
+    use crate::memory::licensee::licensee::{
+        CARTRIDGE_TYPES, NEW_LICENSEE_CODES, OLD_LICENSEE_CODES,
+    };
     use crate::memory::mbc::mbc;
     use crate::memory::mbc::mbc::MbcType;
+    use crate::memory::patch::patch::{apply_patch, PATCH_EXTENSIONS};
+    use sha1::{Digest, Sha1};
     use serde::{Deserialize, Serialize};
     use std::fmt;
     use std::fmt::Formatter;
+    use std::fs;
     use std::fs::File;
     use std::io::Read;
+    use std::path::{Path, PathBuf};
 
     #[derive(Clone, Debug)]
     pub struct Cartridge {
         pub cartridge_info: Option<CartridgeInfo>,
         pub mbc: MbcType,
+        pub cheats: CheatEngine,
+        //set when the rom was found in the dat file given with --dat
+        pub dat_entry: Option<DatEntry>,
     }
 
     #[derive(Serialize, Deserialize, Debug, Clone, Copy)]
@@ -18,7 +30,7 @@ pub mod cartridge {
         entry_point: [u8; 4],
         nintendo_logo: [u16; 24],
         title: [u8; 15],
-        //unused
+        //0x80 or 0xC0 turn on cgb mode
         cgb_flag: u8,
         //licensee code ASCII, considered if old_license_code is 0x33
         new_licensee_code: [u8; 2],
@@ -78,15 +90,227 @@ pub mod cartridge {
     }
 
     impl CartridgeInfo {
+        //parse the header (0x100-0x14F) of the given rom, None if the rom is too small to contain it
+        pub fn from_rom(rom_buffer: &[u8]) -> Option<CartridgeInfo> {
+            if rom_buffer.len() <= HEX_HEADER_END_ADDRESS {
+                return None;
+            }
+            let rom_header: &[u8] =
+                &rom_buffer[HEX_HEADER_START_ADDRESS..HEX_HEADER_END_ADDRESS + 1];
+            bincode::deserialize(rom_header).ok()
+        }
+
         pub fn game_title(&self) -> &str {
             match std::str::from_utf8(&self.title) {
-                Ok(value) => value,
+                //unused title bytes are padded with zeroes
+                Ok(value) => value.trim_end_matches('\0'),
                 Err(error) => {
                     println!("Can't read rom name from header! :( {}", error);
                     "BAD HEADER"
                 }
             }
         }
+
+        pub fn cartridge_type(&self) -> u8 {
+            self.cartridge_type
+        }
+
+        pub fn mapper_name(&self) -> &'static str {
+            CARTRIDGE_TYPES
+                .get(&self.cartridge_type)
+                .copied()
+                .unwrap_or("UNKNOWN")
+        }
+
+        //rom size in bytes, None for unknown size codes
+        pub fn rom_size_bytes(&self) -> Option<usize> {
+            match self.rom_size {
+                0x00..=0x08 => Some((32 * 1024) << self.rom_size),
+                //only listed in unofficial docs, never seen on a real cartridge
+                0x52 => Some(72 * 0x4000),
+                0x53 => Some(80 * 0x4000),
+                0x54 => Some(96 * 0x4000),
+                _ => None,
+            }
+        }
+
+        //external ram size in bytes, None for unknown size codes
+        pub fn ram_size_bytes(&self) -> Option<usize> {
+            match self.ram_size {
+                0x00 => Some(0),
+                0x01 => Some(2 * 1024),
+                0x02 => Some(8 * 1024),
+                0x03 => Some(32 * 1024),
+                0x04 => Some(128 * 1024),
+                0x05 => Some(64 * 1024),
+                _ => None,
+            }
+        }
+
+        //0x80 means the game works on both dmg and cgb, 0xC0 means cgb only
+        pub fn supports_cgb(&self) -> bool {
+            self.cgb_flag & 0x80 != 0
+        }
+
+        pub fn is_cgb_only(&self) -> bool {
+            self.cgb_flag & 0xC0 == 0xC0
+        }
+
+        //sgb functions are ignored if the old licensee code isn't 0x33
+        pub fn supports_sgb(&self) -> bool {
+            self.sgb_flag == 0x03 && self.old_licensee_code == 0x33
+        }
+
+        pub fn licensee(&self) -> &'static str {
+            if self.old_licensee_code == 0x33 {
+                let new_licensee_code = String::from_utf8_lossy(&self.new_licensee_code);
+                return NEW_LICENSEE_CODES
+                    .get(&*new_licensee_code)
+                    .copied()
+                    .unwrap_or("Unknown");
+            }
+            OLD_LICENSEE_CODES
+                .get(&self.old_licensee_code)
+                .copied()
+                .unwrap_or("Unknown")
+        }
+
+        pub fn is_nintendo_licensee(&self) -> bool {
+            self.old_licensee_code == 0x01
+                || (self.old_licensee_code == 0x33 && self.new_licensee_code == *b"01")
+        }
+
+        //sum of the 16 bytes from 0x134 to 0x143, the cgb flag byte is still part of old titles
+        pub fn title_checksum(&self) -> u8 {
+            self.title
+                .iter()
+                .fold(self.cgb_flag, |checksum, byte| checksum.wrapping_add(*byte))
+        }
+
+        pub fn title_byte(&self, index: usize) -> u8 {
+            self.title[index]
+        }
+
+        pub fn header_checksum(&self) -> u8 {
+            self.header_checksum
+        }
+
+        //checksum of the header bytes from 0x134 to 0x14C, checked by the bios before booting
+        pub fn compute_header_checksum(&self) -> u8 {
+            let rom_header = bincode::serialize(self).expect("Can't serialize cartridge header");
+            rom_header[HEADER_CHECKSUM_START_ADDRESS - HEX_HEADER_START_ADDRESS
+                ..=HEADER_CHECKSUM_END_ADDRESS - HEX_HEADER_START_ADDRESS]
+                .iter()
+                .fold(0_u8, |checksum, byte| {
+                    checksum.wrapping_sub(*byte).wrapping_sub(1)
+                })
+        }
+
+        pub fn is_header_checksum_valid(&self) -> bool {
+            self.header_checksum == self.compute_header_checksum()
+        }
+
+        //the global checksum is stored big endian, while bincode reads it as little endian
+        pub fn global_checksum(&self) -> u16 {
+            u16::from_be_bytes(self.global_checksum.to_le_bytes())
+        }
+
+        pub fn is_nintendo_logo_valid(&self) -> bool {
+            self.nintendo_logo == CartridgeInfo::nintendo_logo_words()
+        }
+
+        //the logo is stored as u16 words to keep serde happy, bincode writes them back as the original bytes
+        fn nintendo_logo_words() -> [u16; 24] {
+            let mut nintendo_logo = [0; 24];
+            for (index, word) in nintendo_logo.iter_mut().enumerate() {
+                *word = u16::from_le_bytes([NINTENDO_LOGO[index * 2], NINTENDO_LOGO[index * 2 + 1]]);
+            }
+            nintendo_logo
+        }
+
+        pub fn fix_nintendo_logo(&mut self) {
+            self.nintendo_logo = CartridgeInfo::nintendo_logo_words();
+        }
+
+        //titles longer than 15 characters are truncated, shorter ones padded with zeroes
+        pub fn set_title(&mut self, title: &str) {
+            self.title = [0; 15];
+            for (index, byte) in title.bytes().take(self.title.len()).enumerate() {
+                self.title[index] = byte;
+            }
+        }
+
+        pub fn set_cgb_flag(&mut self, cgb_flag: u8) {
+            self.cgb_flag = cgb_flag;
+        }
+
+        pub fn set_sgb_flag(&mut self, sgb_flag: u8) {
+            self.sgb_flag = sgb_flag;
+        }
+
+        pub fn set_cartridge_type(&mut self, cartridge_type: u8) {
+            self.cartridge_type = cartridge_type;
+        }
+
+        pub fn set_rom_size(&mut self, rom_size: u8) {
+            self.rom_size = rom_size;
+        }
+
+        pub fn set_ram_size(&mut self, ram_size: u8) {
+            self.ram_size = ram_size;
+        }
+
+        pub fn set_old_licensee_code(&mut self, old_licensee_code: u8) {
+            self.old_licensee_code = old_licensee_code;
+        }
+
+        pub fn set_new_licensee_code(&mut self, new_licensee_code: [u8; 2]) {
+            self.new_licensee_code = new_licensee_code;
+        }
+
+        pub fn fix_header_checksum(&mut self) {
+            self.header_checksum = self.compute_header_checksum();
+        }
+
+        //must be called after writing the header, since the global checksum covers it too
+        pub fn fix_global_checksum(&mut self, rom_buffer: &[u8]) {
+            let global_checksum = compute_global_checksum(rom_buffer);
+            self.global_checksum = u16::from_le_bytes(global_checksum.to_be_bytes());
+        }
+
+        //write the header back into the rom using the same layout it was read with
+        pub fn write_to_rom(&self, rom_buffer: &mut [u8]) {
+            let rom_header = bincode::serialize(self).expect("Can't serialize cartridge header");
+            rom_buffer[HEX_HEADER_START_ADDRESS..HEX_HEADER_END_ADDRESS + 1]
+                .copy_from_slice(&rom_header);
+        }
+    }
+
+    //rom size code for a rom of the given length, None if the length isn't a valid rom size
+    pub fn rom_size_code(rom_length: usize) -> Option<u8> {
+        (0x00..=0x08).find(|rom_size| (32 * 1024) << rom_size == rom_length)
+    }
+
+    //sum of every rom byte except the two global checksum ones
+    pub fn compute_global_checksum(rom_buffer: &[u8]) -> u16 {
+        rom_buffer
+            .iter()
+            .enumerate()
+            .filter(|(address, _)| *address != 0x14E && *address != 0x14F)
+            .fold(0_u16, |checksum, (_, byte)| {
+                checksum.wrapping_add(*byte as u16)
+            })
+    }
+
+    pub fn rom_crc32(rom_buffer: &[u8]) -> u32 {
+        crc32fast::hash(rom_buffer)
+    }
+
+    pub fn rom_sha1(rom_buffer: &[u8]) -> String {
+        Sha1::digest(rom_buffer)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
     }
 
     impl Cartridge {
@@ -95,21 +319,88 @@ pub mod cartridge {
         }
 
         pub fn get_item(&self, address: usize) -> u8 {
-            self.mbc.read(address)
+            let value = self.mbc.read(address);
+            if address < 0x8000 {
+                return self.cheats.patch_rom_read(address, value);
+            }
+            value
+        }
+
+        //canonical dat name when the rom was identified, header title otherwise
+        pub fn game_name(&self) -> String {
+            match (&self.dat_entry, &self.cartridge_info) {
+                (Some(dat_entry), _) => dat_entry.name.clone(),
+                (None, Some(cartridge_info)) => cartridge_info.game_title().to_owned(),
+                (None, None) => String::new(),
+            }
         }
     }
 
     const HEX_HEADER_START_ADDRESS: usize = 0x100;
     const HEX_HEADER_END_ADDRESS: usize = 0x14F;
+    pub const NINTENDO_LOGO: [u8; 48] = [
+        0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00,
+        0x0D, 0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD,
+        0xD9, 0x99, 0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB,
+        0xB9, 0x33, 0x3E,
+    ];
+    const HEADER_CHECKSUM_START_ADDRESS: usize = 0x134;
+    const HEADER_CHECKSUM_END_ADDRESS: usize = 0x14C;
 
-    pub fn read_cartridge(file_name: &str) -> Cartridge {
-        let mut rom = File::open(format!("./src/roms/{}", file_name)).expect("rom not found");
+    //a patch named like the rom (game.ips or game.gb.ips) is picked up automatically. the ones
+    //next to a rom are alternatives, not a chain, so only the first found is used, in the order
+    //of PATCH_EXTENSIONS and with game.ips before game.gb.ips
+    pub fn find_patch_file(rom_path: &Path) -> Option<PathBuf> {
+        PATCH_EXTENSIONS
+            .iter()
+            .flat_map(|extension| {
+                let mut patch_file = rom_path.as_os_str().to_owned();
+                patch_file.push(format!(".{}", extension));
+                [rom_path.with_extension(extension), PathBuf::from(patch_file)]
+            })
+            .find(|patch_file| patch_file.is_file())
+    }
+
+    //patches are applied in the given order, if none is given the one next to the rom is used.
+    //the dat is searched for the unpatched rom, so translations and hacks keep the name,
+    //region and revision of the game they were made from
+    pub fn load_rom(
+        rom_path: &Path,
+        patch_files: &[PathBuf],
+        rom_database: Option<&RomDatabase>,
+    ) -> (Vec<u8>, Option<DatEntry>) {
+        let mut rom = File::open(rom_path).expect("rom not found");
 
         let mut rom_buffer = Vec::new();
         rom.read_to_end(&mut rom_buffer).expect("Can't read ROM");
+        let dat_entry = rom_database.and_then(|rom_database| rom_database.identify(&rom_buffer));
+
+        let patch_files = if patch_files.is_empty() {
+            find_patch_file(rom_path).into_iter().collect()
+        } else {
+            patch_files.to_vec()
+        };
+        for patch_file in patch_files.iter() {
+            let patch = fs::read(patch_file)
+                .unwrap_or_else(|error| panic!("Can't read patch {}: {}", patch_file.display(), error));
+            rom_buffer = apply_patch(&rom_buffer, &patch).unwrap_or_else(|error| {
+                panic!("Can't apply patch {}: {}", patch_file.display(), error)
+            });
+            println!("applied patch {}", patch_file.display());
+        }
+        (rom_buffer, dat_entry)
+    }
+
+    pub fn read_cartridge(
+        file_name: &str,
+        patch_files: &[PathBuf],
+        rom_database: Option<&RomDatabase>,
+    ) -> Cartridge {
+        let rom_path = PathBuf::from(format!("./src/roms/{}", file_name));
+        let (rom_buffer, dat_entry) = load_rom(&rom_path, patch_files, rom_database);
 
-        let rom_header: &[u8] = &rom_buffer[HEX_HEADER_START_ADDRESS..HEX_HEADER_END_ADDRESS + 1];
-        let cartridge_info: CartridgeInfo = bincode::deserialize(rom_header).unwrap();
+        let cartridge_info: CartridgeInfo =
+            CartridgeInfo::from_rom(&rom_buffer).expect("Can't read cartridge header");
         println!(
             "cartridge type {:#01x} - size {} - name {}",
             cartridge_info.cartridge_type,
@@ -117,9 +408,22 @@ pub mod cartridge {
             cartridge_info.game_title(),
         );
 
+        match &dat_entry {
+            Some(dat_entry) => println!(
+                "identified as {} - region {} - revision {}",
+                dat_entry.name,
+                dat_entry.region.as_deref().unwrap_or("unknown"),
+                dat_entry.revision.as_deref().unwrap_or("none"),
+            ),
+            None if rom_database.is_some() => println!("rom not found in the dat file"),
+            None => {}
+        }
+
         Cartridge {
             cartridge_info: Some(cartridge_info),
             mbc: MbcType::new(cartridge_info.cartridge_type, rom_buffer),
+            cheats: CheatEngine::load(&rom_path.with_extension("cht")),
+            dat_entry,
         }
     }
 }
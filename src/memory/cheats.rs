@@ -0,0 +1,263 @@
+pub mod cheats {
+    use std::fmt;
+    use std::fmt::Formatter;
+    use std::fs;
+    use std::path::Path;
+
+    #[derive(Debug, PartialEq)]
+    pub enum CheatError {
+        InvalidLength,
+        InvalidCharacter(char),
+        InvalidAddress(u16),
+    }
+
+    impl fmt::Display for CheatError {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            match self {
+                CheatError::InvalidLength => write!(
+                    f,
+                    "codes should be ABC-DEF(-GHI) for Game Genie or 8 digits for GameShark"
+                ),
+                CheatError::InvalidCharacter(character) => {
+                    write!(f, "{} isn't an hex digit", character)
+                }
+                CheatError::InvalidAddress(address) => {
+                    write!(f, "0x{:04X} can't be patched by this kind of code", address)
+                }
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum CheatCode {
+        //patches a rom read, only if the original value matches compare (when given)
+        GameGenie {
+            address: u16,
+            value: u8,
+            compare: Option<u8>,
+        },
+        //writes ram every frame, bank is None when the code should write to whatever is mapped
+        GameShark {
+            address: u16,
+            value: u8,
+            bank: Option<u8>,
+        },
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Cheat {
+        pub code: String,
+        pub description: String,
+        pub enabled: bool,
+        pub cheat_code: CheatCode,
+    }
+
+    //how the cheat list is printed, disabled cheats are marked with !
+    impl fmt::Display for Cheat {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            let enabled = if self.enabled { " " } else { "!" };
+            write!(f, "{}{} {}", enabled, self.code, self.description)
+        }
+    }
+
+    #[derive(Debug, Clone, Default)]
+    pub struct CheatEngine {
+        cheats: Vec<Cheat>,
+    }
+
+    fn parse_hex_digits(code: &str) -> Result<Vec<u8>, CheatError> {
+        code.chars()
+            .filter(|character| *character != '-')
+            .map(|character| {
+                character
+                    .to_digit(16)
+                    .map(|digit| digit as u8)
+                    .ok_or(CheatError::InvalidCharacter(character))
+            })
+            .collect()
+    }
+
+    //ABC-DEF-GHI: AB is the new value, FCDE the address (F xored with 0xF)
+    //GI is the compare value, rotated left by two and xored with 0xBA, H is ignored
+    pub fn decode_game_genie(code: &str) -> Result<CheatCode, CheatError> {
+        let digits = parse_hex_digits(code)?;
+        if digits.len() != 6 && digits.len() != 9 {
+            return Err(CheatError::InvalidLength);
+        }
+
+        let value = (digits[0] << 4) | digits[1];
+        let address = (((digits[5] ^ 0xF) as u16) << 12)
+            | ((digits[2] as u16) << 8)
+            | ((digits[3] as u16) << 4)
+            | digits[4] as u16;
+        if address > 0x7FFF {
+            return Err(CheatError::InvalidAddress(address));
+        }
+
+        let compare = if digits.len() == 9 {
+            let encoded_compare = (digits[6] << 4) | digits[8];
+            Some(encoded_compare.rotate_right(2) ^ 0xBA)
+        } else {
+            None
+        };
+
+        Ok(CheatCode::GameGenie {
+            address,
+            value,
+            compare,
+        })
+    }
+
+    //TTVVLLHH: TT is the code type, VV the value and HHLL the address
+    //type 01 writes to the mapped ram, 8X to external ram bank X and 9X to work ram bank X
+    pub fn decode_gameshark(code: &str) -> Result<CheatCode, CheatError> {
+        let digits = parse_hex_digits(code)?;
+        if digits.len() != 8 {
+            return Err(CheatError::InvalidLength);
+        }
+
+        let code_type = (digits[0] << 4) | digits[1];
+        let value = (digits[2] << 4) | digits[3];
+        let address = ((digits[6] as u16) << 12)
+            | ((digits[7] as u16) << 8)
+            | ((digits[4] as u16) << 4)
+            | digits[5] as u16;
+
+        let bank = match code_type & 0xF0 {
+            0x80 | 0x90 => Some(code_type & 0x0F),
+            _ => None,
+        };
+        let valid_address = match code_type & 0xF0 {
+            0x80 => (0xA000..=0xBFFF).contains(&address),
+            0x90 => (0xD000..=0xDFFF).contains(&address),
+            _ => (0xA000..=0xFFFF).contains(&address),
+        };
+        if !valid_address {
+            return Err(CheatError::InvalidAddress(address));
+        }
+
+        Ok(CheatCode::GameShark {
+            address,
+            value,
+            bank,
+        })
+    }
+
+    //GameShark codes are plain 8 digits, everything else is treated as Game Genie
+    pub fn decode_cheat(code: &str) -> Result<CheatCode, CheatError> {
+        if !code.contains('-') && code.len() == 8 {
+            decode_gameshark(code)
+        } else {
+            decode_game_genie(code)
+        }
+    }
+
+    impl CheatEngine {
+        //cheat files have a code per line, followed by an optional description
+        //lines starting with # are comments, codes starting with ! are disabled
+        pub fn load(path: &Path) -> CheatEngine {
+            let mut cheat_engine = CheatEngine::default();
+            let content = match fs::read_to_string(path) {
+                Ok(content) => content,
+                Err(_) => return cheat_engine,
+            };
+
+            for (line_number, line) in content.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let (code, description) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+                let (code, enabled) = match code.strip_prefix('!') {
+                    Some(code) => (code, false),
+                    None => (code, true),
+                };
+                match cheat_engine.add(code, description.trim()) {
+                    Ok(index) => cheat_engine.set_enabled(index, enabled),
+                    Err(error) => println!(
+                        "{}:{} skipping cheat {}: {}",
+                        path.display(),
+                        line_number + 1,
+                        code,
+                        error
+                    ),
+                }
+            }
+            println!("loaded {} cheats from {}", cheat_engine.cheats.len(), path.display());
+            cheat_engine
+        }
+
+        //returns the index of the new cheat, which starts enabled
+        pub fn add(&mut self, code: &str, description: &str) -> Result<usize, CheatError> {
+            let cheat_code = decode_cheat(code)?;
+            self.cheats.push(Cheat {
+                code: code.to_uppercase(),
+                description: description.to_owned(),
+                enabled: true,
+                cheat_code,
+            });
+            Ok(self.cheats.len() - 1)
+        }
+
+        pub fn remove(&mut self, index: usize) -> Option<Cheat> {
+            if index < self.cheats.len() {
+                return Some(self.cheats.remove(index));
+            }
+            None
+        }
+
+        pub fn cheats(&self) -> &[Cheat] {
+            &self.cheats
+        }
+
+        pub fn set_enabled(&mut self, index: usize, enabled: bool) {
+            if let Some(cheat) = self.cheats.get_mut(index) {
+                cheat.enabled = enabled;
+            }
+        }
+
+        //returns the new state of the cheat
+        pub fn toggle(&mut self, index: usize) -> bool {
+            match self.cheats.get_mut(index) {
+                Some(cheat) => {
+                    cheat.enabled = !cheat.enabled;
+                    cheat.enabled
+                }
+                None => false,
+            }
+        }
+
+        //given a value read from rom, apply the first matching Game Genie code
+        pub fn patch_rom_read(&self, address: usize, value: u8) -> u8 {
+            for cheat in self.cheats.iter().filter(|cheat| cheat.enabled) {
+                if let CheatCode::GameGenie {
+                    address: cheat_address,
+                    value: cheat_value,
+                    compare,
+                } = cheat.cheat_code
+                {
+                    if cheat_address as usize == address && compare.is_none_or(|compare| compare == value) {
+                        return cheat_value;
+                    }
+                }
+            }
+            value
+        }
+
+        //enabled GameShark codes as (address, value, bank)
+        pub fn ram_writes(&self) -> Vec<(u16, u8, Option<u8>)> {
+            self.cheats
+                .iter()
+                .filter(|cheat| cheat.enabled)
+                .filter_map(|cheat| match cheat.cheat_code {
+                    CheatCode::GameShark {
+                        address,
+                        value,
+                        bank,
+                    } => Some((address, value, bank)),
+                    _ => None,
+                })
+                .collect()
+        }
+    }
+}
@@ -0,0 +1,121 @@
+pub mod dat {
+    use crate::memory::cartridge::cartridge::{rom_crc32, rom_sha1};
+    use std::collections::HashMap;
+    use std::fs;
+    use std::path::Path;
+
+    const REGIONS: [&str; 22] = [
+        "World", "USA", "Europe", "Japan", "Asia", "Australia", "Brazil", "Canada", "China",
+        "France", "Germany", "Hong Kong", "Italy", "Korea", "Netherlands", "Spain", "Sweden",
+        "Taiwan", "United Kingdom", "Russia", "Scandinavia", "Unknown",
+    ];
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct DatEntry {
+        pub name: String,
+        pub region: Option<String>,
+        pub revision: Option<String>,
+    }
+
+    #[derive(Debug, Clone)]
+    struct DatRom {
+        size: Option<usize>,
+        sha1: Option<String>,
+        entry: DatEntry,
+    }
+
+    //No-Intro/Redump style xml dat, roms are indexed by crc32
+    #[derive(Debug, Default)]
+    pub struct RomDatabase {
+        roms: HashMap<u32, Vec<DatRom>>,
+    }
+
+    impl DatEntry {
+        //No-Intro names look like "Title (Region) (Language) (Rev 1)"
+        pub fn from_name(name: &str) -> DatEntry {
+            let tags: Vec<&str> = name
+                .split('(')
+                .skip(1)
+                .filter_map(|tag| tag.split_once(')').map(|(tag, _)| tag))
+                .collect();
+
+            let region = tags
+                .iter()
+                .find(|tag| tag.split(", ").all(|region| REGIONS.contains(&region)))
+                .map(|tag| tag.to_string());
+            let revision = tags.iter().find_map(|tag| {
+                if let Some(revision) = tag.strip_prefix("Rev ") {
+                    return Some(revision.to_owned());
+                }
+                match tag.strip_prefix('v') {
+                    Some(version) if version.starts_with(|c: char| c.is_ascii_digit()) => {
+                        Some(tag.to_string())
+                    }
+                    _ => None,
+                }
+            });
+
+            DatEntry {
+                name: name.to_owned(),
+                region,
+                revision,
+            }
+        }
+    }
+
+    impl RomDatabase {
+        pub fn load(path: &Path) -> Result<RomDatabase, String> {
+            let content = fs::read_to_string(path).map_err(|error| error.to_string())?;
+            RomDatabase::parse(&content)
+        }
+
+        pub fn parse(content: &str) -> Result<RomDatabase, String> {
+            let document = roxmltree::Document::parse(content).map_err(|error| error.to_string())?;
+            let mut rom_database = RomDatabase::default();
+
+            //mame style dats use machine instead of game
+            let games = document
+                .root_element()
+                .children()
+                .filter(|node| node.has_tag_name("game") || node.has_tag_name("machine"));
+            for game in games {
+                let name = match game.attribute("name") {
+                    Some(name) => name,
+                    None => continue,
+                };
+                for rom in game.children().filter(|node| node.has_tag_name("rom")) {
+                    let crc = match rom
+                        .attribute("crc")
+                        .and_then(|crc| u32::from_str_radix(crc, 16).ok())
+                    {
+                        Some(crc) => crc,
+                        None => continue,
+                    };
+                    rom_database.roms.entry(crc).or_default().push(DatRom {
+                        size: rom.attribute("size").and_then(|size| size.parse().ok()),
+                        sha1: rom.attribute("sha1").map(|sha1| sha1.to_lowercase()),
+                        entry: DatEntry::from_name(name),
+                    });
+                }
+            }
+            Ok(rom_database)
+        }
+
+        pub fn len(&self) -> usize {
+            self.roms.values().map(|roms| roms.len()).sum()
+        }
+
+        //crc32 narrows the candidates down, size and sha1 (when the dat has them) confirm the match
+        pub fn identify(&self, rom_buffer: &[u8]) -> Option<DatEntry> {
+            let candidates = self.roms.get(&rom_crc32(rom_buffer))?;
+            let sha1 = rom_sha1(rom_buffer);
+            candidates
+                .iter()
+                .find(|rom| {
+                    rom.size.is_none_or(|size| size == rom_buffer.len())
+                        && rom.sha1.as_ref().is_none_or(|rom_sha1| *rom_sha1 == sha1)
+                })
+                .map(|rom| rom.entry.clone())
+        }
+    }
+}
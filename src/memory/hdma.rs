@@ -0,0 +1,93 @@
+pub mod hdma {
+    use crate::memory::mmu::mmu::MMU;
+
+    const BLOCK_SIZE: u16 = 0x10;
+    //the cpu is stopped for 8 m-cycles (32 dots) for each block in single speed
+    const BLOCK_CYCLES: u32 = 32;
+
+    //cgb vram dma, copies blocks of 16 bytes from rom/ram to vram
+    #[derive(Debug, Clone, Default)]
+    pub struct Hdma {
+        source: u16,
+        destination: u16,
+        //blocks left, kept after a cancelled hblank dma for the status readback
+        remaining_blocks: u8,
+        //set while a hblank dma waits for the next hblank
+        hblank_active: bool,
+        //cycles the cpu has to wait for the blocks copied so far
+        pub stall_cycles: u32,
+    }
+
+    impl MMU<'_> {
+        pub fn read_hdma(&self, address: usize) -> u8 {
+            match address {
+                //bit 7 is clear while a hblank dma is running, set once done or cancelled
+                0xFF55 if self.hdma.hblank_active => self.hdma.remaining_blocks - 1,
+                0xFF55 if self.hdma.remaining_blocks == 0 => 0xFF,
+                0xFF55 => 0x80 | (self.hdma.remaining_blocks - 1),
+                //source and destination are write only
+                _ => 0xFF,
+            }
+        }
+
+        pub fn write_hdma(&mut self, address: usize, value: u8) {
+            match address {
+                0xFF51 => self.hdma.source = (self.hdma.source & 0x00FF) | ((value as u16) << 8),
+                0xFF52 => self.hdma.source = (self.hdma.source & 0xFF00) | (value & 0xF0) as u16,
+                //the destination is always in vram
+                0xFF53 => {
+                    self.hdma.destination =
+                        (self.hdma.destination & 0x00FF) | (((value & 0x1F) as u16) << 8)
+                }
+                0xFF54 => {
+                    self.hdma.destination =
+                        (self.hdma.destination & 0xFF00) | (value & 0xF0) as u16
+                }
+                0xFF55 => {
+                    let blocks = (value & 0x7F) + 1;
+                    if self.hdma.hblank_active && value & 0x80 == 0 {
+                        //writing bit 7 clear stops a running hblank dma
+                        self.hdma.hblank_active = false;
+                    } else if value & 0x80 == 0 {
+                        //general purpose dma copies everything at once
+                        self.hdma.remaining_blocks = blocks;
+                        while self.hdma.remaining_blocks > 0 {
+                            self.transfer_hdma_block();
+                        }
+                    } else {
+                        self.hdma.remaining_blocks = blocks;
+                        self.hdma.hblank_active = true;
+                        //with the lcd off there are no hblanks, a block is copied right away
+                        if !self.PPU.is_lcd_enabled() {
+                            self.transfer_hdma_block();
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        //called after the ppu has been stepped, copies a block when a hblank starts
+        pub fn step_hdma(&mut self) {
+            if self.PPU.take_hblank_started() && self.hdma.hblank_active {
+                self.transfer_hdma_block();
+            }
+        }
+
+        fn transfer_hdma_block(&mut self) {
+            for offset in 0..BLOCK_SIZE {
+                let value = self.read_byte(self.hdma.source.wrapping_add(offset) as i32);
+                //the ppu isn't reading vram during hblank, so the blocking doesn't apply
+                let destination = 0x8000 | ((self.hdma.destination + offset) & 0x1FFF);
+                self.PPU.write_byte(destination as usize, value);
+            }
+            self.hdma.source = self.hdma.source.wrapping_add(BLOCK_SIZE);
+            self.hdma.destination = (self.hdma.destination + BLOCK_SIZE) & 0x1FF0;
+            self.hdma.remaining_blocks -= 1;
+            self.hdma.stall_cycles += BLOCK_CYCLES;
+            if self.hdma.remaining_blocks == 0 {
+                self.hdma.hblank_active = false;
+            }
+        }
+    }
+}
@@ -376,6 +376,20 @@ pub mod mbc {
             }
         }
 
+        //write straight into the given external ram bank, ignoring the selected one and the ram enable
+        pub fn write_ram_bank(&mut self, bank: usize, address: usize, value: u8) {
+            let (ram, offset) = match self {
+                MbcType::None(mbc) => (&mut mbc.ram, address - 0x8000),
+                MbcType::Mbc1(mbc) => (&mut mbc.ram, bank * 0x2000 + address - 0xA000),
+                MbcType::Mbc2(mbc) => (&mut mbc.ram, address - 0xA000),
+                MbcType::Mbc3(mbc) => (&mut mbc.ram, bank * 0x2000 + address - 0xA000),
+                MbcType::Mbc5(mbc) => (&mut mbc.ram, bank * 0x2000 + address - 0xA000),
+            };
+            if let Some(ram_value) = ram.get_mut(offset) {
+                *ram_value = value;
+            }
+        }
+
         pub fn new(mbc_code: u8, rom: Vec<u8>) -> Self {
             match mbc_code {
                 0x00 => MbcType::None(MbcNone::new(rom)),
@@ -1,10 +1,12 @@
 pub mod mmu {
     use crate::io::gamepad;
     use crate::memory::cartridge::cartridge::Cartridge;
+    use crate::memory::hdma::hdma::Hdma;
     use crate::memory::op_codes_parser::op_codes_parser::{
         get_instructions_from_json, Instruction, Operand,
     };
     use crate::ppu::ppu::PPU;
+    use crate::ppu::sgb::sgb::Sgb;
     use serde_json::Value;
     use std::collections::HashMap;
     use std::fmt::Debug;
@@ -19,12 +21,19 @@ pub mod mmu {
         pub PPU: &'a mut PPU,
         pub gamepad: gamepad::gamepad::gamepad,
         pub external_ram: [u8; 0x2000],
-        pub work_ram: [u8; 0x2000],
+        //bank 0 followed by the 7 switchable cgb banks, dmg only uses bank 1
+        pub work_ram: [u8; 0x8000],
+        pub work_ram_bank: usize, //svbk
         pub io_registers: [u8; 0x100],
         pub high_ram: [u8; 0x80],
         pub non_io_internal_ram0: [u8; 0x60],
         pub non_io_internal_ram1: [u8; 0x34],
         pub is_past_bios: bool,
+        //cgb registers are only mapped when the cartridge supports cgb
+        pub cgb_mode: bool,
+        pub hdma: Hdma,
+        //set when a dmg game asks for sgb functions
+        pub(crate) sgb: Option<Sgb>,
 
         pub interrupt_master_enabled: bool, //ime
         pub interrupt_enabled: u8,          //ie
@@ -53,6 +62,16 @@ pub mod mmu {
             let prefixed_op_codes: HashMap<u8, Instruction> =
                 get_instructions_from_json(&json_op_codes, "cbprefixed");
 
+            let Cartridge = Cartridge.expect("Empty cartridge");
+            let cgb_mode = Cartridge
+                .cartridge_info
+                .map_or(false, |cartridge_info| cartridge_info.supports_cgb());
+            PPU.cgb_mode = cgb_mode;
+            let sgb = Cartridge
+                .cartridge_info
+                .filter(|cartridge_info| !cgb_mode && cartridge_info.supports_sgb())
+                .map(|_| Sgb::new(&PPU.palette.background));
+
             MMU {
                 bios: [
                     0x31, 0xFE, 0xFF, 0xAF, 0x21, 0xFF, 0x9F, 0x32, 0xCB, 0x7C, 0x20, 0xFB, 0x21,
@@ -76,16 +95,20 @@ pub mod mmu {
                     0xFE, 0x23, 0x7D, 0xFE, 0x34, 0x20, 0xF5, 0x06, 0x19, 0x78, 0x86, 0x23, 0x05,
                     0x20, 0xFB, 0x86, 0x20, 0xFE, 0x3E, 0x01, 0xE0, 0x50,
                 ],
-                cartridge: Cartridge.expect("Empty cartridge"),
+                cartridge: Cartridge,
                 PPU,
                 gamepad: gamepad::gamepad::gamepad::default(),
                 external_ram: [0; 0x2000],
-                work_ram: [0; 0x2000],
+                work_ram: [0; 0x8000],
+                work_ram_bank: 1,
                 io_registers: [0; 0x100],
                 high_ram: [0; 0x80],
                 non_io_internal_ram0: [0; 0x60],
                 non_io_internal_ram1: [0; 0x34],
                 is_past_bios: false,
+                cgb_mode,
+                hdma: Hdma::default(),
+                sgb,
 
                 interrupt_master_enabled: false,
                 interrupt_enabled: 0,
@@ -176,23 +199,35 @@ pub mod mmu {
                 0x100..=0x3FFF => self.cartridge.get_item(address),
                 //ROM bank 1-NN
                 0x4000..=0x7FFF => self.cartridge.get_item(address),
-                //VRAM
-                0x8000..=0x9FFF => self.PPU.read_byte(address),
+                //VRAM, the ppu owns it during mode 3
+                0x8000..=0x9FFF => {
+                    if !self.PPU.is_vram_accessible() {
+                        return 0xFF;
+                    }
+                    self.PPU.read_byte(address)
+                }
                 //External RAM
                 0xA000..=0xBFFF => self.cartridge.get_item(address),
                 //WRAM (Work RAM)
-                0xC000..=0xDFFF => self.work_ram[address - 0xC000],
+                0xC000..=0xCFFF => self.work_ram[address - 0xC000],
+                //WRAM bank 1-7
+                0xD000..=0xDFFF => self.work_ram[self.work_ram_bank * 0x1000 + address - 0xD000],
                 //ECHO RAM (use is prohibited by Nintendo!)
                 0xE000..=0xFDFF => {
                     self.read_byte((address - 0x2000) as i32) //redirect to internal ram
                 }
-                //Sprite attribute table
-                0xFE00..=0xFE9F => self.PPU.read_byte(address),
+                //Sprite attribute table, the ppu owns it during modes 2 and 3
+                0xFE00..=0xFE9F => {
+                    if !self.PPU.is_oam_accessible() {
+                        return 0xFF;
+                    }
+                    self.PPU.read_byte(address)
+                }
                 //Not usable (prohibited!)
                 0xFEA0..=0xFEFF => return self.non_io_internal_ram0[address - 0xFEA0],
                 0xFF00..=0xFF4B => {
                     return match address {
-                        0xFF00 => self.gamepad.read(),
+                        0xFF00 => self.read_joypad(),
                         0xFF04 => self.timer_divider,
                         0xFF05 => self.timer_counter,
                         0xFF06 => self.timer_modulo,
@@ -215,8 +250,12 @@ pub mod mmu {
                     };
                 }
                 0xFF4C..=0xFF7F => {
-                    //gbc stuff
-                    self.non_io_internal_ram1[address - 0xFF4C]
+                    return match address {
+                        0xFF4F | 0xFF68..=0xFF6C if self.cgb_mode => self.PPU.read_byte(address),
+                        0xFF51..=0xFF55 if self.cgb_mode => self.read_hdma(address),
+                        0xFF70 if self.cgb_mode => 0xF8 | self.work_ram_bank as u8,
+                        _ => self.non_io_internal_ram1[address - 0xFF4C],
+                    };
                 }
                 0xFF80..=0xFFFE => self.high_ram[address - 0xFF80],
                 //interrupt Enable register
@@ -241,29 +280,44 @@ pub mod mmu {
                 }
                 //VRAM
                 0x8000..=0x9FFF => {
-                    self.PPU.write_byte(address, value);
+                    if self.PPU.is_vram_accessible() {
+                        self.PPU.write_byte(address, value);
+                    }
                 }
                 //External RAM
                 0xA000..=0xBFFF => {
                     self.cartridge.set_item(value, address);
                 }
                 //WRAM (Work RAM)
-                0xC000..=0xDFFF => {
+                0xC000..=0xCFFF => {
                     self.work_ram[address - 0xC000] = value;
                 }
+                //WRAM bank 1-7
+                0xD000..=0xDFFF => {
+                    self.work_ram[self.work_ram_bank * 0x1000 + address - 0xD000] = value;
+                }
                 //ECHO RAM (use is prohibited by Nintendo!)
                 0xE000..=0xFDFF => {
                     self.write_byte((address - 0x2000) as i32, value) //redirect to internal ram
                 }
                 //Sprite attribute table
-                0xFE00..=0xFE9F => self.PPU.write_byte(address, value),
+                0xFE00..=0xFE9F => {
+                    if self.PPU.is_oam_accessible() {
+                        self.PPU.write_byte(address, value);
+                    }
+                }
                 //Not usable (prohibited!)
                 0xFEA0..=0xFEFF => {
                     self.non_io_internal_ram0[address - 0xFEA0] = value;
                 }
                 0xFF00..=0xFF4B => {
                     match address {
-                        0xFF00 => self.gamepad.write(value),
+                        0xFF00 => {
+                            self.gamepad.write(value);
+                            if let Some(sgb) = self.sgb.as_mut() {
+                                sgb.write_joypad(value);
+                            }
+                        }
                         0xFF01 => {
                             //do serial stuff
                             self.io_registers[address - 0xFF00] = value
@@ -279,7 +333,7 @@ pub mod mmu {
                         0xFF0F => self.interrupt_flag = value,
                         0xFF10..=0xFF3F => (), //TODO set sound stuff
                         0xFF40 => self.PPU.set_lcdc(value),
-                        0xFF41 => self.PPU.set_current_mode_from_value(value),
+                        0xFF41 => self.PPU.write_lcd_status(value),
                         0xFF42 => self.PPU.write_byte(address, value),
                         0xFF43 => self.PPU.write_byte(address, value),
                         0xFF44 => self.PPU.write_byte(address, value),
@@ -293,14 +347,16 @@ pub mod mmu {
                         _ => self.io_registers[address - 0xFF00] = value,
                     }
                 }
-                0xFF4C..=0xFF7F => {
-                    //gbc stuff
-                    if address == 0xFF50 && !self.is_past_bios && (value == 0x1 || value == 0x11) {
+                0xFF4C..=0xFF7F => match address {
+                    0xFF50 if !self.is_past_bios && (value == 0x1 || value == 0x11) => {
                         self.is_past_bios = true;
-                    } else {
-                        self.non_io_internal_ram1[address - 0xFF4C] = value
                     }
-                }
+                    0xFF4F | 0xFF68..=0xFF6C if self.cgb_mode => self.PPU.write_byte(address, value),
+                    0xFF51..=0xFF55 if self.cgb_mode => self.write_hdma(address, value),
+                    //bank 0 can't be mapped at 0xD000, writing 0 selects bank 1
+                    0xFF70 if self.cgb_mode => self.work_ram_bank = (value as usize & 0x07).max(1),
+                    _ => self.non_io_internal_ram1[address - 0xFF4C] = value,
+                },
                 0xFF80..=0xFFFE => self.high_ram[address - 0xFF80] = value,
                 0xFFFF => self.interrupt_enabled = value,
                 _ => {
@@ -320,12 +376,54 @@ pub mod mmu {
             (first_8_bits | last_8_bits << 8) as u16
         }
 
+        //in sgb multiplayer mode the selected joypad is reported when no line is selected,
+        //only the first joypad is connected to the keyboard
+        fn read_joypad(&self) -> u8 {
+            match self.sgb.as_ref() {
+                Some(sgb) if sgb.players > 1 => match self.gamepad.value & 0x30 {
+                    0x30 => 0xFF - sgb.current_player,
+                    _ if sgb.current_player != 0 => self.gamepad.value | 0xCF,
+                    _ => self.gamepad.read(),
+                },
+                _ => self.gamepad.read(),
+            }
+        }
+
+        //GameShark codes are applied once per frame
+        pub fn apply_cheats(&mut self) {
+            for (address, value, bank) in self.cartridge.cheats.ram_writes() {
+                match (address, bank) {
+                    (0xA000..=0xBFFF, Some(bank)) => {
+                        self.cartridge
+                            .mbc
+                            .write_ram_bank(bank as usize, address as usize, value)
+                    }
+                    //dmg has a single switchable work ram bank, cgb has 7
+                    (0xD000..=0xDFFF, Some(bank)) => {
+                        let bank = if self.cgb_mode {
+                            (bank as usize & 0x07).max(1)
+                        } else {
+                            1
+                        };
+                        self.work_ram[bank * 0x1000 + address as usize - 0xD000] = value
+                    }
+                    _ => self.write_byte(address as i32, value),
+                }
+            }
+        }
+
         //https://gbdev.io/pandocs/OAM_DMA_Transfer.html
         pub fn transfer_dma(&mut self, value: u8) {
             let target = 0xFE00;
             let offset = value as i32 * 0x100;
+            //dma isn't affected by the vram/oam blocking
             for n in 0..0xA0 {
-                self.write_byte(target + n, self.read_byte(offset + n))
+                let source = (offset + n) as usize;
+                let value = match source {
+                    0x8000..=0x9FFF => self.PPU.read_byte(source),
+                    _ => self.read_byte(offset + n),
+                };
+                self.PPU.write_byte((target + n) as usize, value)
             }
         }
     }
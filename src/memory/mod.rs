@@ -1,4 +1,9 @@
 pub mod cartridge;
+pub mod cheats;
+pub mod dat;
+pub mod hdma;
+pub mod licensee;
 pub mod mbc;
 pub mod mmu;
 pub mod op_codes_parser;
+pub mod patch;
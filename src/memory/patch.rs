@@ -0,0 +1,316 @@
+pub mod patch {
+    use std::fmt;
+    use std::fmt::Formatter;
+
+    const IPS_MAGIC: &[u8] = b"PATCH";
+    const IPS_EOF: &[u8] = b"EOF";
+    const UPS_MAGIC: &[u8] = b"UPS1";
+    const BPS_MAGIC: &[u8] = b"BPS1";
+    //source crc32 + target crc32 + patch crc32
+    const FOOTER_SIZE: usize = 12;
+
+    pub const PATCH_EXTENSIONS: [&str; 3] = ["ips", "ups", "bps"];
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum PatchFormat {
+        Ips,
+        Ups,
+        Bps,
+    }
+
+    #[derive(Debug, PartialEq)]
+    pub enum PatchError {
+        UnknownFormat,
+        UnexpectedEnd,
+        PatchChecksumMismatch { expected: u32, actual: u32 },
+        SourceSizeMismatch { expected: usize, actual: usize },
+        SourceChecksumMismatch { expected: u32, actual: u32 },
+        TargetChecksumMismatch { expected: u32, actual: u32 },
+        InvalidCopy,
+    }
+
+    impl fmt::Display for PatchError {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            match self {
+                PatchError::UnknownFormat => write!(f, "not an IPS, UPS or BPS patch"),
+                PatchError::UnexpectedEnd => write!(f, "patch ends unexpectedly"),
+                PatchError::PatchChecksumMismatch { expected, actual } => write!(
+                    f,
+                    "patch is corrupted, crc32 is {:08x} but should be {:08x}",
+                    actual, expected
+                ),
+                PatchError::SourceSizeMismatch { expected, actual } => write!(
+                    f,
+                    "patch is meant for a {} bytes rom, this one is {} bytes",
+                    expected, actual
+                ),
+                PatchError::SourceChecksumMismatch { expected, actual } => write!(
+                    f,
+                    "patch is meant for a different rom, crc32 is {:08x} but should be {:08x}",
+                    actual, expected
+                ),
+                PatchError::TargetChecksumMismatch { expected, actual } => write!(
+                    f,
+                    "patched rom crc32 is {:08x} but should be {:08x}",
+                    actual, expected
+                ),
+                PatchError::InvalidCopy => write!(f, "patch copies data out of bounds"),
+            }
+        }
+    }
+
+    impl PatchFormat {
+        pub fn detect(patch: &[u8]) -> Option<PatchFormat> {
+            if patch.starts_with(IPS_MAGIC) {
+                Some(PatchFormat::Ips)
+            } else if patch.starts_with(UPS_MAGIC) {
+                Some(PatchFormat::Ups)
+            } else if patch.starts_with(BPS_MAGIC) {
+                Some(PatchFormat::Bps)
+            } else {
+                None
+            }
+        }
+    }
+
+    //keeps track of the current position while reading a patch
+    struct PatchReader<'a> {
+        patch: &'a [u8],
+        offset: usize,
+    }
+
+    impl<'a> PatchReader<'a> {
+        fn new(patch: &'a [u8], offset: usize) -> Self {
+            PatchReader { patch, offset }
+        }
+
+        fn read_byte(&mut self) -> Result<u8, PatchError> {
+            let byte = *self
+                .patch
+                .get(self.offset)
+                .ok_or(PatchError::UnexpectedEnd)?;
+            self.offset += 1;
+            Ok(byte)
+        }
+
+        fn read_bytes(&mut self, length: usize) -> Result<&'a [u8], PatchError> {
+            let bytes = self
+                .patch
+                .get(self.offset..self.offset + length)
+                .ok_or(PatchError::UnexpectedEnd)?;
+            self.offset += length;
+            Ok(bytes)
+        }
+
+        fn read_big_endian(&mut self, length: usize) -> Result<usize, PatchError> {
+            Ok(self
+                .read_bytes(length)?
+                .iter()
+                .fold(0, |value, byte| (value << 8) | *byte as usize))
+        }
+
+        //variable length integer used by UPS and BPS, each byte holds 7 bits and the last one has the MSB set
+        fn read_number(&mut self) -> Result<usize, PatchError> {
+            let mut value: usize = 0;
+            let mut shift: usize = 1;
+            loop {
+                let byte = self.read_byte()?;
+                value += (byte & 0x7F) as usize * shift;
+                if byte & 0x80 != 0 {
+                    return Ok(value);
+                }
+                shift <<= 7;
+                value += shift;
+            }
+        }
+    }
+
+    fn read_crc32(bytes: &[u8]) -> u32 {
+        u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+
+    //UPS and BPS end with the source, target and patch crc32
+    fn read_footer(patch: &[u8]) -> Result<(u32, u32), PatchError> {
+        if patch.len() < FOOTER_SIZE {
+            return Err(PatchError::UnexpectedEnd);
+        }
+        let footer = &patch[patch.len() - FOOTER_SIZE..];
+        let patch_checksum = crc32fast::hash(&patch[..patch.len() - 4]);
+        if read_crc32(&footer[8..12]) != patch_checksum {
+            return Err(PatchError::PatchChecksumMismatch {
+                expected: read_crc32(&footer[8..12]),
+                actual: patch_checksum,
+            });
+        }
+        Ok((read_crc32(&footer[0..4]), read_crc32(&footer[4..8])))
+    }
+
+    fn check_source(rom: &[u8], source_size: usize, source_checksum: u32) -> Result<(), PatchError> {
+        if rom.len() != source_size {
+            return Err(PatchError::SourceSizeMismatch {
+                expected: source_size,
+                actual: rom.len(),
+            });
+        }
+        let actual = crc32fast::hash(rom);
+        if actual != source_checksum {
+            return Err(PatchError::SourceChecksumMismatch {
+                expected: source_checksum,
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    fn check_target(patched_rom: &[u8], target_checksum: u32) -> Result<(), PatchError> {
+        let actual = crc32fast::hash(patched_rom);
+        if actual != target_checksum {
+            return Err(PatchError::TargetChecksumMismatch {
+                expected: target_checksum,
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    //https://zerosoft.zophar.net/ips.php
+    fn apply_ips(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+        let mut patched_rom = rom.to_vec();
+        let mut reader = PatchReader::new(patch, IPS_MAGIC.len());
+
+        loop {
+            if reader.read_bytes(IPS_EOF.len())? == IPS_EOF {
+                //some patches add a 3 bytes truncation length after the EOF marker
+                if let Ok(truncated_size) = reader.read_big_endian(3) {
+                    patched_rom.truncate(truncated_size);
+                }
+                return Ok(patched_rom);
+            }
+            reader.offset -= IPS_EOF.len();
+
+            let offset = reader.read_big_endian(3)?;
+            let size = reader.read_big_endian(2)?;
+            //a zero size means the record is run length encoded
+            let data: Vec<u8> = if size == 0 {
+                let run_length = reader.read_big_endian(2)?;
+                vec![reader.read_byte()?; run_length]
+            } else {
+                reader.read_bytes(size)?.to_vec()
+            };
+
+            if patched_rom.len() < offset + data.len() {
+                patched_rom.resize(offset + data.len(), 0);
+            }
+            patched_rom[offset..offset + data.len()].copy_from_slice(&data);
+        }
+    }
+
+    //http://fileformats.archiveteam.org/wiki/UPS_(binary_patch_format)
+    fn apply_ups(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+        let (source_checksum, target_checksum) = read_footer(patch)?;
+        let mut reader = PatchReader::new(patch, UPS_MAGIC.len());
+        let source_size = reader.read_number()?;
+        let target_size = reader.read_number()?;
+        check_source(rom, source_size, source_checksum)?;
+
+        let mut patched_rom = rom.to_vec();
+        patched_rom.resize(target_size, 0);
+
+        //each hunk skips some bytes and then xors the rom until a zero byte is found
+        let mut output_offset = 0;
+        while reader.offset < patch.len() - FOOTER_SIZE {
+            output_offset += reader.read_number()?;
+            loop {
+                let xor_value = reader.read_byte()?;
+                if output_offset < target_size {
+                    patched_rom[output_offset] ^= xor_value;
+                }
+                output_offset += 1;
+                if xor_value == 0 {
+                    break;
+                }
+            }
+        }
+
+        check_target(&patched_rom, target_checksum)?;
+        Ok(patched_rom)
+    }
+
+    fn read_relative_offset(reader: &mut PatchReader, offset: &mut usize) -> Result<(), PatchError> {
+        let data = reader.read_number()?;
+        let distance = data >> 1;
+        *offset = if data & 1 != 0 {
+            offset.checked_sub(distance).ok_or(PatchError::InvalidCopy)?
+        } else {
+            *offset + distance
+        };
+        Ok(())
+    }
+
+    //https://github.com/blakesmith/rombp/blob/master/docs/bps_spec.md
+    fn apply_bps(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+        let (source_checksum, target_checksum) = read_footer(patch)?;
+        let mut reader = PatchReader::new(patch, BPS_MAGIC.len());
+        let source_size = reader.read_number()?;
+        let target_size = reader.read_number()?;
+        let metadata_size = reader.read_number()?;
+        reader.read_bytes(metadata_size)?;
+        check_source(rom, source_size, source_checksum)?;
+
+        let mut patched_rom: Vec<u8> = Vec::with_capacity(target_size);
+        let (mut source_offset, mut target_offset) = (0, 0);
+
+        while reader.offset < patch.len() - FOOTER_SIZE {
+            let data = reader.read_number()?;
+            let length = (data >> 2) + 1;
+            match data & 3 {
+                //source read, copy from the rom at the same position
+                0 => {
+                    let output_offset = patched_rom.len();
+                    let bytes = rom
+                        .get(output_offset..output_offset + length)
+                        .ok_or(PatchError::InvalidCopy)?;
+                    patched_rom.extend_from_slice(bytes);
+                }
+                //target read, copy from the patch itself
+                1 => patched_rom.extend_from_slice(reader.read_bytes(length)?),
+                //source copy, copy from anywhere in the rom
+                2 => {
+                    read_relative_offset(&mut reader, &mut source_offset)?;
+                    let bytes = rom
+                        .get(source_offset..source_offset + length)
+                        .ok_or(PatchError::InvalidCopy)?;
+                    patched_rom.extend_from_slice(bytes);
+                    source_offset += length;
+                }
+                //target copy, copy from the already patched output (ranges can overlap)
+                _ => {
+                    read_relative_offset(&mut reader, &mut target_offset)?;
+                    for _ in 0..length {
+                        let byte = *patched_rom
+                            .get(target_offset)
+                            .ok_or(PatchError::InvalidCopy)?;
+                        patched_rom.push(byte);
+                        target_offset += 1;
+                    }
+                }
+            }
+        }
+
+        if patched_rom.len() != target_size {
+            return Err(PatchError::UnexpectedEnd);
+        }
+        check_target(&patched_rom, target_checksum)?;
+        Ok(patched_rom)
+    }
+
+    //detect the patch format and return the patched rom
+    pub fn apply_patch(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+        match PatchFormat::detect(patch) {
+            Some(PatchFormat::Ips) => apply_ips(rom, patch),
+            Some(PatchFormat::Ups) => apply_ups(rom, patch),
+            Some(PatchFormat::Bps) => apply_bps(rom, patch),
+            None => Err(PatchError::UnknownFormat),
+        }
+    }
+}
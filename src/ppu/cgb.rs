@@ -0,0 +1,71 @@
+pub mod cgb {
+    use crate::ppu::palette::palette::Color;
+    use crate::ppu::ppu::{LCDCFlags, PpuMode, TilePixelValue, PPU};
+
+    //rgb555 channels are scaled to 8 bits, repeating the top bits to reach full white
+    pub(crate) fn rgb555_to_color(value: u16) -> Color {
+        let scale = |channel: u16| ((channel << 3) | (channel >> 2)) as u8;
+        [
+            scale(value & 0x1F),
+            scale((value >> 5) & 0x1F),
+            scale((value >> 10) & 0x1F),
+            255,
+        ]
+    }
+
+    impl PPU {
+        //palette ram can't be accessed by the cpu during mode 3
+        fn is_palette_ram_accessible(&self) -> bool {
+            !self.get_lcdc_value(LCDCFlags::LCD_enabled) || self.get_current_mode() != PpuMode::VRAM
+        }
+
+        //bcpd/ocpd, reads don't increment the index
+        pub(crate) fn read_palette_data(&self, obj_palettes: bool) -> u8 {
+            if !self.is_palette_ram_accessible() {
+                return 0xFF;
+            }
+            if obj_palettes {
+                self.obj_palette_ram[(self.obj_palette_index & 0x3F) as usize]
+            } else {
+                self.background_palette_ram[(self.background_palette_index & 0x3F) as usize]
+            }
+        }
+
+        //the index is incremented even when the write itself is blocked
+        pub(crate) fn write_palette_data(&mut self, obj_palettes: bool, value: u8) {
+            let accessible = self.is_palette_ram_accessible();
+            let (palette_ram, palette_index) = if obj_palettes {
+                (&mut self.obj_palette_ram, &mut self.obj_palette_index)
+            } else {
+                (
+                    &mut self.background_palette_ram,
+                    &mut self.background_palette_index,
+                )
+            };
+            if accessible {
+                palette_ram[(*palette_index & 0x3F) as usize] = value;
+            }
+            if *palette_index & 0x80 != 0 {
+                *palette_index = 0x80 | ((*palette_index + 1) & 0x3F);
+            }
+        }
+
+        pub(crate) fn get_cgb_color(
+            &self,
+            color_number: TilePixelValue,
+            palette: u8,
+            obj_palettes: bool,
+        ) -> Color {
+            let palette_ram = if obj_palettes {
+                &self.obj_palette_ram
+            } else {
+                &self.background_palette_ram
+            };
+            let index = (palette as usize & 0x07) * 8 + color_number as usize * 2;
+            rgb555_to_color(u16::from_le_bytes([
+                palette_ram[index],
+                palette_ram[index + 1],
+            ]))
+        }
+    }
+}
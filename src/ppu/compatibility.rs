@@ -0,0 +1,255 @@
+pub mod compatibility {
+    use crate::memory::cartridge::cartridge::CartridgeInfo;
+    use crate::ppu::cgb::cgb::rgb555_to_color;
+    use crate::ppu::palette::palette::{rgb, Color, DmgPalette};
+
+    //the cgb boot rom picks a palette for nintendo titles by the sum of their title bytes
+    //the last entries share a checksum and are told apart by the fourth title letter
+    const TITLE_CHECKSUMS: [u8; 94] = [
+        0x00, 0x88, 0x16, 0x36, 0xD1, 0xDB, 0xF2, 0x3C, 0x8C, 0x92, 0x3D, 0x5C, 0x58, 0xC9, 0x3E,
+        0x70, 0x1D, 0x59, 0x69, 0x19, 0x35, 0xA8, 0x14, 0xAA, 0x75, 0x95, 0x99, 0x34, 0x6F, 0x15,
+        0xFF, 0x97, 0x4B, 0x90, 0x17, 0x10, 0x39, 0xF7, 0xF6, 0xA2, 0x49, 0x4E, 0x43, 0x68, 0xE0,
+        0x8B, 0xF0, 0xCE, 0x0C, 0x29, 0xE8, 0xB7, 0x86, 0x9A, 0x52, 0x01, 0x9D, 0x71, 0x9C, 0xBD,
+        0x5D, 0x6D, 0x67, 0x3F, 0x6B, 0xB3, 0x46, 0x28, 0xA5, 0xC6, 0xD3, 0x27, 0x61, 0x18, 0x66,
+        0x6A, 0xBF, 0x0D, 0xF4, 0xB3, 0x46, 0x28, 0xA5, 0xC6, 0xD3, 0x27, 0x61, 0x18, 0x66, 0x6A,
+        0xBF, 0x0D, 0xF4, 0xB3,
+    ];
+    const UNIQUE_TITLE_CHECKSUMS: usize = 65;
+    const FOURTH_TITLE_LETTERS: &[u8; 29] = b"BEFAARBEKEK R-URAR INAILICE R";
+
+    //palette combination of each title, in the order of TITLE_CHECKSUMS
+    const TITLE_PALETTES: [usize; 94] = [
+        0, 4, 5, 35, 34, 3, 31, 15, 10, 5, 19, 36, 7, 37, 30, 44, 21, 32, 31, 20, 5, 33, 13, 14,
+        5, 29, 5, 18, 9, 3, 2, 26, 25, 25, 41, 42, 26, 45, 42, 45, 36, 38, 26, 42, 30, 41, 34,
+        34, 5, 42, 6, 5, 33, 25, 42, 42, 40, 2, 16, 25, 42, 42, 5, 0, 39, 36, 22, 25, 6, 32, 12,
+        36, 11, 39, 18, 39, 24, 31, 50, 17, 46, 6, 27, 0, 47, 41, 41, 0, 0, 19, 34, 23, 18, 29,
+    ];
+
+    //the rgb555 palettes the combinations are made of, 4 colors each
+    const COMPATIBILITY_PALETTES: [[u16; 4]; 30] = [
+        [0x7FFF, 0x32BF, 0x00D0, 0x0000],
+        [0x639F, 0x4279, 0x15B0, 0x04CB],
+        [0x7FFF, 0x6E31, 0x454A, 0x0000],
+        [0x7FFF, 0x1BEF, 0x0200, 0x0000],
+        [0x7FFF, 0x421F, 0x1CF2, 0x0000],
+        [0x7FFF, 0x5294, 0x294A, 0x0000],
+        [0x7FFF, 0x03FF, 0x012F, 0x0000],
+        [0x7FFF, 0x03EF, 0x01D6, 0x0000],
+        [0x7FFF, 0x42B5, 0x3DC8, 0x0000],
+        [0x7E74, 0x03FF, 0x0180, 0x0000],
+        [0x67FF, 0x77AC, 0x1A13, 0x2D6B],
+        [0x7ED6, 0x4BFF, 0x2175, 0x0000],
+        [0x53FF, 0x4A5F, 0x7E52, 0x0000],
+        [0x4FFF, 0x7ED2, 0x3A4C, 0x1CE0],
+        [0x03ED, 0x7FFF, 0x255F, 0x0000],
+        [0x036A, 0x021F, 0x03FF, 0x7FFF],
+        [0x7FFF, 0x01DF, 0x0112, 0x0000],
+        [0x231F, 0x035F, 0x00F2, 0x0009],
+        [0x7FFF, 0x03EA, 0x011F, 0x0000],
+        [0x299F, 0x001A, 0x000C, 0x0000],
+        [0x7FFF, 0x027F, 0x001F, 0x0000],
+        [0x7FFF, 0x03E0, 0x0206, 0x0120],
+        [0x7FFF, 0x7EEB, 0x001F, 0x7C00],
+        [0x7FFF, 0x3FFF, 0x7E00, 0x001F],
+        [0x7FFF, 0x03FF, 0x001F, 0x0000],
+        [0x03FF, 0x001F, 0x000C, 0x0000],
+        [0x7FFF, 0x033F, 0x0193, 0x0000],
+        [0x0000, 0x4200, 0x037F, 0x7FFF],
+        [0x7FFF, 0x7E8C, 0x7C00, 0x0000],
+        [0x7FFF, 0x1BEF, 0x6180, 0x0000],
+    ];
+
+    //obj 0, obj 1 and background of each combination, as offsets in colors into
+    //COMPATIBILITY_PALETTES. the boot rom starts a few of them one color early,
+    //so they begin with the last color of the previous palette
+    const PALETTE_COMBINATIONS: [[usize; 3]; 51] = [
+        [16, 16, 116],
+        [72, 72, 72],
+        [80, 80, 80],
+        [96, 96, 96],
+        [36, 36, 36],
+        [0, 0, 0],
+        [108, 108, 108],
+        [20, 20, 20],
+        [48, 48, 48],
+        [104, 104, 104],
+        [64, 32, 32],
+        [16, 112, 112],
+        [16, 8, 8],
+        [12, 16, 16],
+        [16, 116, 116],
+        [112, 16, 112],
+        [8, 68, 8],
+        [64, 64, 32],
+        [16, 16, 28],
+        [16, 16, 72],
+        [16, 16, 80],
+        [76, 76, 36],
+        [15, 15, 44],
+        [68, 68, 8],
+        [16, 16, 8],
+        [16, 16, 12],
+        [112, 112, 0],
+        [12, 12, 0],
+        [0, 0, 4],
+        [72, 88, 72],
+        [80, 88, 80],
+        [96, 88, 96],
+        [64, 88, 32],
+        [68, 16, 52],
+        [111, 0, 56],
+        [111, 16, 60],
+        [76, 88, 36],
+        [64, 112, 40],
+        [16, 92, 112],
+        [68, 88, 8],
+        [16, 0, 8],
+        [16, 112, 12],
+        [112, 12, 0],
+        [12, 112, 16],
+        [84, 112, 16],
+        [12, 112, 0],
+        [100, 12, 112],
+        [0, 112, 32],
+        [16, 12, 112],
+        [112, 12, 24],
+        [16, 112, 116],
+    ];
+
+    //name, background, obj 0 and obj 1 colors of the palettes picked by holding a direction
+    //(and optionally a or b) while the cgb logo is shown
+    const BUTTON_PALETTES: [(&str, [Color; 4], [Color; 4], [Color; 4]); 12] = [
+        (
+            "up",
+            [rgb(0xFFFFFF), rgb(0xFFAD63), rgb(0x843100), rgb(0x000000)],
+            [rgb(0xFFFFFF), rgb(0xFFAD63), rgb(0x843100), rgb(0x000000)],
+            [rgb(0xFFFFFF), rgb(0xFFAD63), rgb(0x843100), rgb(0x000000)],
+        ),
+        (
+            "up+a",
+            [rgb(0xFFFFFF), rgb(0xFF8584), rgb(0x943A3A), rgb(0x000000)],
+            [rgb(0xFFFFFF), rgb(0x7BFF31), rgb(0x008400), rgb(0x000000)],
+            [rgb(0xFFFFFF), rgb(0x63A5FF), rgb(0x0000FF), rgb(0x000000)],
+        ),
+        (
+            "up+b",
+            [rgb(0xFFE6C5), rgb(0xCE9C84), rgb(0x846B29), rgb(0x5A3108)],
+            [rgb(0xFFFFFF), rgb(0xFFAD63), rgb(0x843100), rgb(0x000000)],
+            [rgb(0xFFFFFF), rgb(0xFFAD63), rgb(0x843100), rgb(0x000000)],
+        ),
+        (
+            "left",
+            [rgb(0xFFFFFF), rgb(0x63A5FF), rgb(0x0000FF), rgb(0x000000)],
+            [rgb(0xFFFFFF), rgb(0xFF8484), rgb(0x943A3A), rgb(0x000000)],
+            [rgb(0xFFFFFF), rgb(0x7BFF31), rgb(0x008400), rgb(0x000000)],
+        ),
+        (
+            "left+a",
+            [rgb(0xFFFFFF), rgb(0x8C8CDE), rgb(0x52528C), rgb(0x000000)],
+            [rgb(0xFFFFFF), rgb(0xFF8484), rgb(0x943A3A), rgb(0x000000)],
+            [rgb(0xFFFFFF), rgb(0xFFAD63), rgb(0x843100), rgb(0x000000)],
+        ),
+        (
+            "left+b",
+            [rgb(0xFFFFFF), rgb(0xA5A5A5), rgb(0x525252), rgb(0x000000)],
+            [rgb(0xFFFFFF), rgb(0xA5A5A5), rgb(0x525252), rgb(0x000000)],
+            [rgb(0xFFFFFF), rgb(0xA5A5A5), rgb(0x525252), rgb(0x000000)],
+        ),
+        (
+            "down",
+            [rgb(0xFFFFA5), rgb(0xFF9494), rgb(0x9494FF), rgb(0x000000)],
+            [rgb(0xFFFFA5), rgb(0xFF9494), rgb(0x9494FF), rgb(0x000000)],
+            [rgb(0xFFFFA5), rgb(0xFF9494), rgb(0x9494FF), rgb(0x000000)],
+        ),
+        (
+            "down+a",
+            [rgb(0xFFFFFF), rgb(0xFFFF00), rgb(0xFF0000), rgb(0x000000)],
+            [rgb(0xFFFFFF), rgb(0xFFFF00), rgb(0xFF0000), rgb(0x000000)],
+            [rgb(0xFFFFFF), rgb(0xFFFF00), rgb(0xFF0000), rgb(0x000000)],
+        ),
+        (
+            "down+b",
+            [rgb(0xFFFFFF), rgb(0xFFFF00), rgb(0x7B4A00), rgb(0x000000)],
+            [rgb(0xFFFFFF), rgb(0x63A5FF), rgb(0x0000FF), rgb(0x000000)],
+            [rgb(0xFFFFFF), rgb(0x7BFF31), rgb(0x008400), rgb(0x000000)],
+        ),
+        (
+            "right",
+            [rgb(0xFFFFFF), rgb(0x52FF00), rgb(0xFF4200), rgb(0x000000)],
+            [rgb(0xFFFFFF), rgb(0x52FF00), rgb(0xFF4200), rgb(0x000000)],
+            [rgb(0xFFFFFF), rgb(0x52FF00), rgb(0xFF4200), rgb(0x000000)],
+        ),
+        (
+            "right+a",
+            [rgb(0xFFFFFF), rgb(0x7BFF31), rgb(0x0063C5), rgb(0x000000)],
+            [rgb(0xFFFFFF), rgb(0xFF8484), rgb(0x943A3A), rgb(0x000000)],
+            [rgb(0xFFFFFF), rgb(0xFF8484), rgb(0x943A3A), rgb(0x000000)],
+        ),
+        (
+            "right+b",
+            [rgb(0x000000), rgb(0x008484), rgb(0xFFDE00), rgb(0xFFFFFF)],
+            [rgb(0x000000), rgb(0x008484), rgb(0xFFDE00), rgb(0xFFFFFF)],
+            [rgb(0x000000), rgb(0x008484), rgb(0xFFDE00), rgb(0xFFFFFF)],
+        ),
+    ];
+
+    //used for unlicensed games and titles missing from the table
+    pub(crate) const DEFAULT_COMPATIBILITY_PALETTE: &str = "right+a";
+
+    pub(crate) fn button_palettes() -> Vec<DmgPalette> {
+        BUTTON_PALETTES
+            .iter()
+            .map(|(name, background, obj_0, obj_1)| DmgPalette {
+                name: name.to_string(),
+                background: *background,
+                obj_0: *obj_0,
+                obj_1: *obj_1,
+            })
+            .collect()
+    }
+
+    //index of the game in the boot rom title table, None when the boot rom wouldn't look it up
+    pub(crate) fn title_index(cartridge_info: &CartridgeInfo) -> Option<usize> {
+        if !cartridge_info.is_nintendo_licensee() {
+            return None;
+        }
+        let title_checksum = cartridge_info.title_checksum();
+        TITLE_CHECKSUMS
+            .iter()
+            .enumerate()
+            .filter(|(_, checksum)| **checksum == title_checksum)
+            .map(|(index, _)| index)
+            .find(|index| {
+                *index < UNIQUE_TITLE_CHECKSUMS
+                    || FOURTH_TITLE_LETTERS[*index - UNIQUE_TITLE_CHECKSUMS]
+                        == cartridge_info.title_byte(3)
+            })
+    }
+
+    fn combination_colors(offset: usize) -> [Color; 4] {
+        std::array::from_fn(|color| {
+            let offset = offset + color;
+            rgb555_to_color(COMPATIBILITY_PALETTES[offset / 4][offset % 4])
+        })
+    }
+
+    //the palette the cgb boot rom would give to a dmg only game
+    pub(crate) fn compatibility_palette(cartridge_info: &CartridgeInfo) -> DmgPalette {
+        let combination = match title_index(cartridge_info) {
+            Some(index) => TITLE_PALETTES[index],
+            None => {
+                return button_palettes()
+                    .into_iter()
+                    .find(|palette| palette.name == DEFAULT_COMPATIBILITY_PALETTE)
+                    .unwrap()
+            }
+        };
+        let [obj_0, obj_1, background] = PALETTE_COMBINATIONS[combination];
+        DmgPalette {
+            name: format!("cgb title palette {}", combination),
+            background: combination_colors(background),
+            obj_0: combination_colors(obj_0),
+            obj_1: combination_colors(obj_1),
+        }
+    }
+}
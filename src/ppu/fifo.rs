@@ -0,0 +1,617 @@
+pub mod fifo {
+    use crate::ppu::hd_pack::hd_pack::TileSource;
+    use crate::ppu::palette::palette::{Color, PaletteLayer};
+    use crate::ppu::ppu::{
+        LCDCFlags, LineRegisters, Sprite, Tile, TilePixelValue, PPU, SCREEN_HORIZONTAL_RESOLUTION,
+    };
+    use image::Rgba;
+    use std::collections::VecDeque;
+
+    const MAX_SPRITES_PER_LINE: usize = 10;
+    const SPRITE_FETCH_DOTS: u8 = 6;
+
+    //each step takes two dots, push is retried every dot until the background fifo is empty
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub(crate) enum FetcherStep {
+        GetTile,
+        GetTileDataLow,
+        GetTileDataHigh,
+        Push,
+    }
+
+    //palette and priority come from the cgb background map attributes, always 0 on dmg
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub(crate) struct BackgroundPixel {
+        pub(crate) color: TilePixelValue,
+        pub(crate) palette: u8,
+        pub(crate) priority: bool,
+        //fetched from the window map, only used by the render mask
+        pub(crate) window: bool,
+        //only set while an hd pack is used
+        pub(crate) source: Option<TileSource>,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub(crate) struct SpritePixel {
+        pub(crate) color: TilePixelValue,
+        pub(crate) palette: bool,
+        pub(crate) cgb_palette: u8,
+        pub(crate) background_priority: bool,
+        pub(crate) oam_index: usize,
+        pub(crate) source: Option<TileSource>,
+    }
+
+    //state of the pixel transfer (mode 3) for the current line
+    #[derive(Debug, Clone)]
+    pub(crate) struct PixelFifo {
+        fetcher_step: FetcherStep,
+        fetcher_dots: u8,
+        //tile column of the next fetch, relative to the left of the screen
+        fetcher_tile_x: u8,
+        //tile column of the next window fetch
+        window_tile_x: u8,
+        //whether the tile being fetched comes from the window
+        fetching_window: bool,
+        tile_id: usize,
+        tile_y: usize,
+        //cgb map attributes: palette (0-2), vram bank (3), x flip (5), y flip (6), priority (7)
+        tile_attributes: u8,
+        tile_row: [TilePixelValue; 8],
+        //hash of the fetched tile for the hd pack
+        tile_hash: Option<u64>,
+        //the first fetch of each line is thrown away
+        first_fetch_done: bool,
+        background_pixels: VecDeque<BackgroundPixel>,
+        sprite_pixels: VecDeque<SpritePixel>,
+        //sprites selected by the oam scan and not fetched yet
+        pub(crate) line_sprites: Vec<Sprite>,
+        //sprites past the per line limit, only drawn when the limit is lifted
+        pub(crate) extra_sprites: Vec<Sprite>,
+        //sprite being fetched and dots spent on it
+        sprite_fetch: Option<(Sprite, u8)>,
+        //scx & 7 pixels are dropped at the start of the line
+        discarded_pixels: u8,
+        //x of the next pixel sent to the lcd
+        pub(crate) x: u8,
+        //set once the window started on this line
+        pub(crate) window_active: bool,
+    }
+
+    impl PixelFifo {
+        pub(crate) fn new() -> PixelFifo {
+            PixelFifo {
+                fetcher_step: FetcherStep::GetTile,
+                fetcher_dots: 0,
+                fetcher_tile_x: 0,
+                window_tile_x: 0,
+                fetching_window: false,
+                tile_id: 0,
+                tile_y: 0,
+                tile_attributes: 0,
+                tile_row: [TilePixelValue::Zero; 8],
+                tile_hash: None,
+                first_fetch_done: false,
+                background_pixels: VecDeque::with_capacity(16),
+                sprite_pixels: VecDeque::with_capacity(8),
+                line_sprites: Vec::with_capacity(MAX_SPRITES_PER_LINE),
+                extra_sprites: vec![],
+                sprite_fetch: None,
+                discarded_pixels: 0,
+                x: 0,
+                window_active: false,
+            }
+        }
+
+        fn reset(&mut self, scroll_x: u8) {
+            self.fetcher_step = FetcherStep::GetTile;
+            self.fetcher_dots = 0;
+            self.fetcher_tile_x = 0;
+            self.window_tile_x = 0;
+            self.first_fetch_done = false;
+            self.background_pixels.clear();
+            self.sprite_pixels.clear();
+            self.sprite_fetch = None;
+            self.discarded_pixels = scroll_x & 7;
+            self.x = 0;
+            self.window_active = false;
+        }
+    }
+
+    impl PPU {
+        pub(crate) fn sprite_height(&self) -> i32 {
+            if self.get_lcdc_value(LCDCFlags::Obj_size) {
+                16
+            } else {
+                8
+            }
+        }
+
+        //mode 2, pick the first sprites (in oam order) overlapping the current line
+        //sprites outside the screen horizontally still count towards the limit
+        pub(crate) fn scan_oam(&mut self) {
+            let line = self.current_line as i32;
+            let sprite_height = self.sprite_height();
+            let mut sprites = self
+                .sprite_set
+                .iter()
+                .filter(|sprite| sprite.y <= line && sprite.y + sprite_height > line)
+                .copied();
+            self.fifo.line_sprites = sprites.by_ref().take(MAX_SPRITES_PER_LINE).collect();
+            self.fifo.extra_sprites = if self.unlimited_sprites {
+                sprites.collect()
+            } else {
+                vec![]
+            };
+        }
+
+        pub(crate) fn start_pixel_transfer(&mut self) {
+            self.fifo.reset(self.scroll_x);
+            self.line_registers[self.current_line as usize] = LineRegisters {
+                scroll_x: self.scroll_x,
+                scroll_y: self.scroll_y,
+                window_x: self.window_x,
+                window_y: self.window_y,
+                lcd_control: self.lcd_control,
+                lcd_status: self.read_byte(0xFF41),
+                background_palette: self.background_palette_data,
+                obj_0_palette: self.obj_0_palette_data,
+                obj_1_palette: self.obj_1_palette_data,
+            };
+        }
+
+        //advance mode 3 by a dot, returns true once the whole line has been sent to the lcd
+        pub(crate) fn pixel_transfer_dot(&mut self) -> bool {
+            if let Some((sprite, dots)) = self.fifo.sprite_fetch {
+                if dots + 1 == SPRITE_FETCH_DOTS {
+                    self.fifo.sprite_fetch = None;
+                    self.merge_sprite(sprite);
+                } else {
+                    self.fifo.sprite_fetch = Some((sprite, dots + 1));
+                }
+                return false;
+            }
+
+            if self.should_start_window() {
+                self.start_window();
+            }
+
+            let sprite_index = if self.get_lcdc_value(LCDCFlags::Obj_enable)
+                && self.fifo.discarded_pixels == 0
+            {
+                //lowest x first, oam order breaks ties
+                let x = self.fifo.x as i32;
+                self.fifo
+                    .line_sprites
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, sprite)| sprite.x <= x)
+                    .min_by_key(|(_, sprite)| sprite.x)
+                    .map(|(sprite_index, _)| sprite_index)
+            } else {
+                None
+            };
+
+            match sprite_index {
+                //the background fetcher has to finish its current tile before a sprite can be fetched
+                Some(sprite_index) => {
+                    let fetcher_idle = self.fifo.fetcher_step == FetcherStep::Push
+                        || (self.fifo.fetcher_step == FetcherStep::GetTile
+                            && self.fifo.fetcher_dots == 0);
+                    if fetcher_idle && !self.fifo.background_pixels.is_empty() {
+                        let sprite = self.fifo.line_sprites.remove(sprite_index);
+                        self.fifo.sprite_fetch = Some((sprite, 1));
+                    } else {
+                        self.fetcher_dot();
+                    }
+                }
+                None => {
+                    self.merge_extra_sprites();
+                    self.shift_pixel();
+                    self.fetcher_dot();
+                }
+            }
+            self.fifo.x as u32 == SCREEN_HORIZONTAL_RESOLUTION
+        }
+
+        fn fetcher_dot(&mut self) {
+            self.fifo.fetcher_dots += 1;
+            match self.fifo.fetcher_step {
+                FetcherStep::GetTile => {
+                    if self.fifo.fetcher_dots == 2 {
+                        //the window can be turned off mid-line, the background is fetched again
+                        self.fifo.fetching_window = self.fifo.window_active
+                            && self.get_lcdc_value(LCDCFlags::Window_enable);
+                        let (tile_map_address, y) = if self.fifo.fetching_window {
+                            self.window_tile_map_address()
+                        } else {
+                            self.background_tile_map_address()
+                        };
+                        self.fifo.tile_id = self.tile_set_index(self.video_ram[tile_map_address]);
+                        self.fifo.tile_attributes = if self.cgb_mode {
+                            self.video_ram_bank_1[tile_map_address]
+                        } else {
+                            0
+                        };
+                        self.fifo.tile_y = if self.fifo.tile_attributes & 0x40 != 0 {
+                            7 - (y & 7)
+                        } else {
+                            y & 7
+                        };
+                        self.fifo.fetcher_step = FetcherStep::GetTileDataLow;
+                        self.fifo.fetcher_dots = 0;
+                    }
+                }
+                FetcherStep::GetTileDataLow => {
+                    if self.fifo.fetcher_dots == 2 {
+                        self.fifo.fetcher_step = FetcherStep::GetTileDataHigh;
+                        self.fifo.fetcher_dots = 0;
+                    }
+                }
+                FetcherStep::GetTileDataHigh => {
+                    if self.fifo.fetcher_dots == 2 {
+                        let tile_set = if self.fifo.tile_attributes & 0x08 != 0 {
+                            &self.tile_set_bank_1
+                        } else {
+                            &self.tile_set
+                        };
+                        let tile = tile_set[self.fifo.tile_id];
+                        self.fifo.tile_row = tile[self.fifo.tile_y];
+                        if self.fifo.tile_attributes & 0x20 != 0 {
+                            self.fifo.tile_row.reverse();
+                        }
+                        self.fifo.tile_hash = self.capture_tile(
+                            &tile,
+                            PaletteLayer::Background,
+                            self.fifo.tile_attributes & 0x07,
+                        );
+                        self.fifo.fetcher_step = FetcherStep::Push;
+                        self.try_push_tile_row();
+                    }
+                }
+                FetcherStep::Push => self.try_push_tile_row(),
+            }
+        }
+
+        //tile map address of the next background tile and the line inside the map
+        fn background_tile_map_address(&self) -> (usize, usize) {
+            let tile_map_address: usize = if self.get_lcdc_value(LCDCFlags::BG_tile_map_area) {
+                0x1C00
+            } else {
+                0x1800
+            };
+            let y = ((self.current_line + self.scroll_y as u32) & 0xFF) as usize;
+            let x_offset = ((self.scroll_x / 8) as usize + self.fifo.fetcher_tile_x as usize) & 31;
+            (tile_map_address + (y / 8) * 32 + x_offset, y)
+        }
+
+        //the window ignores scrolling and uses its own line counter
+        fn window_tile_map_address(&self) -> (usize, usize) {
+            let tile_map_address: usize = if self.get_lcdc_value(LCDCFlags::Window_tile_map_area)
+            {
+                0x1C00
+            } else {
+                0x1800
+            };
+            let y = self.window_line as usize;
+            let x_offset = self.fifo.window_tile_x as usize & 31;
+            (tile_map_address + (y / 8) * 32 + x_offset, y)
+        }
+
+        //wx is the window position plus 7, values above 166 keep it off screen
+        fn should_start_window(&self) -> bool {
+            !self.fifo.window_active
+                && self.window_y_triggered
+                && self.get_lcdc_value(LCDCFlags::Window_enable)
+                && self.fifo.x as u16 + 7 >= self.window_x as u16
+        }
+
+        //throw away the background pixels and restart the fetcher on the window
+        fn start_window(&mut self) {
+            self.fifo.window_active = true;
+            self.fifo.background_pixels.clear();
+            self.fifo.fetcher_step = FetcherStep::GetTile;
+            self.fifo.fetcher_dots = 0;
+            //with wx < 7 the first window pixels are left of the screen
+            if self.fifo.x == 0 {
+                self.fifo.discarded_pixels = 7_u8.saturating_sub(self.window_x);
+            }
+        }
+
+        fn try_push_tile_row(&mut self) {
+            if !self.fifo.first_fetch_done {
+                self.fifo.first_fetch_done = true;
+            } else if self.fifo.background_pixels.is_empty() {
+                let palette = self.fifo.tile_attributes & 0x07;
+                let priority = self.fifo.tile_attributes & 0x80 != 0;
+                let window = self.fifo.fetching_window;
+                let (x_flip, y_flip) = (
+                    self.fifo.tile_attributes & 0x20 != 0,
+                    self.fifo.tile_attributes & 0x40 != 0,
+                );
+                let (tile_hash, tile_y) = (self.fifo.tile_hash, self.fifo.tile_y as u8);
+                self.fifo
+                    .background_pixels
+                    .extend(self.fifo.tile_row.iter().enumerate().map(|(x, color)| {
+                        BackgroundPixel {
+                            color: *color,
+                            palette,
+                            priority,
+                            window,
+                            source: tile_hash.map(|hash| TileSource {
+                                hash,
+                                x: if x_flip { 7 - x as u8 } else { x as u8 },
+                                y: tile_y,
+                                x_flip,
+                                y_flip,
+                            }),
+                        }
+                    }));
+                if self.fifo.fetching_window {
+                    self.fifo.window_tile_x += 1;
+                } else {
+                    self.fifo.fetcher_tile_x += 1;
+                }
+            } else {
+                return;
+            }
+            self.fifo.fetcher_step = FetcherStep::GetTile;
+            self.fifo.fetcher_dots = 0;
+        }
+
+        //tile and row of the tile drawn on a line of a sprite, after the y flip
+        //8x16 sprites use an even/odd tile pair, the lowest bit of the tile number is ignored
+        fn sprite_tile(&self, sprite: Sprite, mut sprite_y: i32) -> (Tile, usize) {
+            let sprite_height = self.sprite_height();
+            if sprite.y_flip {
+                sprite_y = sprite_height - 1 - sprite_y;
+            }
+            let tile_number = if sprite_height == 16 {
+                (sprite.tile_number & 0xFE) as usize + (sprite_y / 8) as usize
+            } else {
+                sprite.tile_number as usize
+            };
+            let tile_set = if self.cgb_mode && sprite.tile_bank == 1 {
+                &self.tile_set_bank_1
+            } else {
+                &self.tile_set
+            };
+            (tile_set[tile_number], (sprite_y & 7) as usize)
+        }
+
+        //row of a sprite as it is drawn, after the flips
+        pub(crate) fn sprite_row(&self, sprite: Sprite, sprite_y: i32) -> [TilePixelValue; 8] {
+            let (tile, row) = self.sprite_tile(sprite, sprite_y);
+            let mut tile_row = tile[row];
+            if sprite.x_flip {
+                tile_row.reverse();
+            }
+            tile_row
+        }
+
+        //the 4 colors of a palette, dmg sprites pick obp0 or obp1 with the layer
+        fn tile_colors(&self, palette_layer: PaletteLayer, cgb_palette: u8) -> [Color; 4] {
+            [
+                TilePixelValue::Zero,
+                TilePixelValue::One,
+                TilePixelValue::Two,
+                TilePixelValue::Three,
+            ]
+            .map(|color_number| {
+                if self.cgb_mode {
+                    let obj = palette_layer != PaletteLayer::Background;
+                    self.get_cgb_color(color_number, cgb_palette, obj)
+                } else {
+                    self.get_dmg_color_and_shade(color_number, palette_layer).0
+                }
+            })
+        }
+
+        //what tiles are hashed with for the hd packs: on dmg the shades the palette register
+        //gives, so choosing another output palette keeps the replacements, on cgb the colors
+        pub(crate) fn tile_palette(&self, palette_layer: PaletteLayer, cgb_palette: u8) -> Vec<u8> {
+            if self.cgb_mode {
+                let colors = self.tile_colors(palette_layer, cgb_palette);
+                return colors.iter().flatten().copied().collect();
+            }
+            let shades = [
+                TilePixelValue::Zero,
+                TilePixelValue::One,
+                TilePixelValue::Two,
+                TilePixelValue::Three,
+            ]
+            .map(|color_number| self.get_shade_from_palette(color_number, palette_layer));
+            //the layer keeps sprites apart from background tiles with the same shades,
+            //as the output palette can color them differently
+            let layer = match palette_layer {
+                PaletteLayer::Background => 0,
+                PaletteLayer::Obj0 => 1,
+                PaletteLayer::Obj1 => 2,
+            };
+            std::iter::once(layer).chain(shades).collect()
+        }
+
+        //hash of a tile drawn with a palette, only while an hd pack is used
+        fn capture_tile(
+            &mut self,
+            tile: &Tile,
+            palette_layer: PaletteLayer,
+            cgb_palette: u8,
+        ) -> Option<u64> {
+            self.tile_capture.as_ref()?;
+            let palette = self.tile_palette(palette_layer, cgb_palette);
+            let colors = self.tile_colors(palette_layer, cgb_palette);
+            self.tile_capture
+                .as_mut()
+                .map(|capture| capture.hash(tile, &palette, &colors))
+        }
+
+        //sprites past the limit are drawn once the fetched ones at the same x are merged,
+        //without stalling the fetcher so mode 3 lasts as long as with the limit
+        fn merge_extra_sprites(&mut self) {
+            if self.fifo.extra_sprites.is_empty()
+                || !self.get_lcdc_value(LCDCFlags::Obj_enable)
+                || self.fifo.discarded_pixels > 0
+            {
+                return;
+            }
+            let x = self.fifo.x as i32;
+            while let Some(sprite_index) = self
+                .fifo
+                .extra_sprites
+                .iter()
+                .enumerate()
+                .filter(|(_, sprite)| sprite.x <= x)
+                .min_by_key(|(_, sprite)| sprite.x)
+                .map(|(sprite_index, _)| sprite_index)
+            {
+                let sprite = self.fifo.extra_sprites.remove(sprite_index);
+                self.merge_sprite(sprite);
+            }
+        }
+
+        //sprite pixels only replace transparent ones, so sprites fetched first win
+        //cgb sprites overlap by oam order instead, unless opri asks for the dmg behaviour
+        fn merge_sprite(&mut self, sprite: Sprite) {
+            let oam_priority = self.cgb_mode && self.object_priority_mode & 0x01 == 0;
+            //hidden sprites still take their fetch time but leave the fifo untouched
+            let visible = self.render_mask.is_sprite_visible(sprite.oam_index);
+            let sprite_y = self.current_line as i32 - sprite.y;
+            let tile_row = self.sprite_row(sprite, sprite_y);
+            let (tile, tile_y) = self.sprite_tile(sprite, sprite_y);
+            let palette_layer = if sprite.palette {
+                PaletteLayer::Obj1
+            } else {
+                PaletteLayer::Obj0
+            };
+            let tile_hash = self.capture_tile(&tile, palette_layer, sprite.cgb_palette);
+            //pixels left of the current position are not drawn
+            let clipped_pixels = (self.fifo.x as i32 - sprite.x).max(0) as usize;
+
+            while self.fifo.sprite_pixels.len() < 8_usize.saturating_sub(clipped_pixels) {
+                self.fifo.sprite_pixels.push_back(SpritePixel {
+                    color: TilePixelValue::Zero,
+                    palette: false,
+                    cgb_palette: 0,
+                    background_priority: false,
+                    oam_index: usize::MAX,
+                    source: None,
+                });
+            }
+            for (fifo_index, color) in tile_row.iter().skip(clipped_pixels).enumerate() {
+                let pixel = &mut self.fifo.sprite_pixels[fifo_index];
+                let wins_overlap = oam_priority
+                    && *color != TilePixelValue::Zero
+                    && sprite.oam_index < pixel.oam_index;
+                if visible && (pixel.color == TilePixelValue::Zero || wins_overlap) {
+                    *pixel = SpritePixel {
+                        color: *color,
+                        palette: sprite.palette,
+                        cgb_palette: sprite.cgb_palette,
+                        background_priority: sprite.background_priority,
+                        oam_index: sprite.oam_index,
+                        source: tile_hash.map(|hash| {
+                            let x = (fifo_index + clipped_pixels) as u8;
+                            TileSource {
+                                hash,
+                                x: if sprite.x_flip { 7 - x } else { x },
+                                y: tile_y as u8,
+                                x_flip: sprite.x_flip,
+                                y_flip: sprite.y_flip,
+                            }
+                        }),
+                    };
+                }
+            }
+        }
+
+        fn get_dmg_color_and_shade(
+            &self,
+            color_number: TilePixelValue,
+            palette_layer: PaletteLayer,
+        ) -> (Color, u8) {
+            let shade = self.get_shade_from_palette(color_number, palette_layer);
+            (self.palette.colors(palette_layer)[shade as usize], shade)
+        }
+
+        //mix the next background and sprite pixels and send the result to the lcd
+        fn shift_pixel(&mut self) {
+            let background_pixel = match self.fifo.background_pixels.pop_front() {
+                Some(pixel) => pixel,
+                None => return,
+            };
+            if self.fifo.discarded_pixels > 0 {
+                self.fifo.discarded_pixels -= 1;
+                return;
+            }
+
+            //on cgb lcdc bit 0 doesn't hide the background, it only drops its priority over sprites
+            let layer_visible = if background_pixel.window {
+                self.render_mask.window
+            } else {
+                self.render_mask.background
+            };
+            let background_enabled =
+                (self.cgb_mode || self.get_lcdc_value(LCDCFlags::Bg_enable)) && layer_visible;
+            let background_has_priority = !self.cgb_mode || self.get_lcdc_value(LCDCFlags::Bg_enable);
+            let background_color = if background_enabled {
+                background_pixel.color
+            } else {
+                TilePixelValue::Zero
+            };
+            let sprite_pixel = self
+                .fifo
+                .sprite_pixels
+                .pop_front()
+                .filter(|pixel| pixel.color != TilePixelValue::Zero)
+                .filter(|_| self.get_lcdc_value(LCDCFlags::Obj_enable))
+                .filter(|pixel| {
+                    !(background_has_priority
+                        && (pixel.background_priority || background_pixel.priority)
+                        && background_color != TilePixelValue::Zero)
+                });
+
+            //palettes are applied here, so mid-line writes show up from the next pixel
+            let (color, shade) = match sprite_pixel {
+                Some(pixel) if self.cgb_mode => {
+                    (self.get_cgb_color(pixel.color, pixel.cgb_palette, true), 0)
+                }
+                Some(pixel) => {
+                    let palette_layer = if pixel.palette {
+                        PaletteLayer::Obj1
+                    } else {
+                        PaletteLayer::Obj0
+                    };
+                    self.get_dmg_color_and_shade(pixel.color, palette_layer)
+                }
+                //a hidden layer shows the backdrop, color 0 of the first palette
+                None if !background_enabled && self.cgb_mode => {
+                    (self.get_cgb_color(TilePixelValue::Zero, 0, false), 0)
+                }
+                None if !background_enabled => (self.palette.background[0], 0),
+                None if self.cgb_mode => (
+                    self.get_cgb_color(background_color, background_pixel.palette, false),
+                    0,
+                ),
+                None => self.get_dmg_color_and_shade(background_color, PaletteLayer::Background),
+            };
+
+            let source = match sprite_pixel {
+                Some(pixel) => pixel.source,
+                None if background_enabled => background_pixel.source,
+                None => None,
+            };
+
+            if !self.blank_frame {
+                let index = (self.current_line * SCREEN_HORIZONTAL_RESOLUTION + self.fifo.x as u32)
+                    as usize;
+                self.image_buffer
+                    .put_pixel(self.fifo.x as u32, self.current_line, Rgba(color));
+                self.shade_buffer[index] = shade;
+                if let Some(capture) = self.tile_capture.as_mut() {
+                    capture.sources[index] = source;
+                }
+            }
+            self.fifo.x += 1;
+        }
+    }
+}
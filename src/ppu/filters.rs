@@ -0,0 +1,160 @@
+pub mod filters {
+    use crate::ppu::palette::palette::Color;
+    use image::{Rgba, RgbaImage};
+    use serde::{Deserialize, Serialize};
+
+    //share of the previous frames kept by the ghosting model, the lcd takes a few frames to settle
+    const GHOSTING_PERSISTENCE: f32 = 0.45;
+    const LCD_GAMMA: f32 = 2.2;
+
+    //raw rgb555 colors look oversaturated on modern displays
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+    #[serde(rename_all = "snake_case")]
+    pub(crate) enum ColorCorrection {
+        #[default]
+        None,
+        Cgb,
+        Agb,
+    }
+
+    //games rely on the slow lcd response for transparency and flicker effects
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+    #[serde(rename_all = "snake_case")]
+    pub(crate) enum FrameBlending {
+        #[default]
+        None,
+        //each frame is mixed with the previous one
+        Average,
+        //pixels move towards the new color over several frames
+        Ghosting,
+    }
+
+    //rows are the output channels, columns the contribution of the input red, green and blue
+    //the agb matrix works on linear light, the cgb one on the raw values like gambatte does
+    const CGB_MATRIX: [[f32; 3]; 3] = [
+        [13.0 / 16.0, 2.0 / 16.0, 1.0 / 16.0],
+        [0.0, 12.0 / 16.0, 4.0 / 16.0],
+        [3.0 / 16.0, 2.0 / 16.0, 11.0 / 16.0],
+    ];
+    const AGB_MATRIX: [[f32; 3]; 3] = [
+        [0.80, 0.275, -0.075],
+        [0.135, 0.64, 0.225],
+        [0.195, 0.155, 0.65],
+    ];
+
+    fn apply_matrix(matrix: &[[f32; 3]; 3], color: [f32; 3]) -> [f32; 3] {
+        let mut result = [0.0; 3];
+        for (channel, row) in result.iter_mut().zip(matrix.iter()) {
+            *channel =
+                (row[0] * color[0] + row[1] * color[1] + row[2] * color[2]).clamp(0.0, 1.0);
+        }
+        result
+    }
+
+    impl ColorCorrection {
+        pub(crate) fn from_name(name: &str) -> Option<ColorCorrection> {
+            match name.to_lowercase().as_str() {
+                "none" => Some(ColorCorrection::None),
+                "cgb" => Some(ColorCorrection::Cgb),
+                "agb" => Some(ColorCorrection::Agb),
+                _ => None,
+            }
+        }
+
+        pub(crate) fn correct(&self, color: Color) -> Color {
+            let channels = [color[0], color[1], color[2]].map(|channel| channel as f32 / 255.0);
+            let corrected = match self {
+                ColorCorrection::None => return color,
+                ColorCorrection::Cgb => apply_matrix(&CGB_MATRIX, channels),
+                ColorCorrection::Agb => {
+                    let linear = channels.map(|channel| channel.powf(LCD_GAMMA));
+                    apply_matrix(&AGB_MATRIX, linear)
+                        .map(|channel| channel.powf(1.0 / LCD_GAMMA))
+                }
+            };
+            let [red, green, blue] = corrected.map(|channel| (channel * 255.0).round() as u8);
+            [red, green, blue, color[3]]
+        }
+    }
+
+    impl FrameBlending {
+        pub(crate) fn from_name(name: &str) -> Option<FrameBlending> {
+            match name.to_lowercase().as_str() {
+                "none" => Some(FrameBlending::None),
+                "average" => Some(FrameBlending::Average),
+                "ghosting" => Some(FrameBlending::Ghosting),
+                _ => None,
+            }
+        }
+    }
+
+    //post processing applied to each frame before it's sent to the window
+    #[derive(Debug, Clone, Default)]
+    pub(crate) struct FrameFilter {
+        pub(crate) color_correction: ColorCorrection,
+        pub(crate) frame_blending: FrameBlending,
+        //corrected colors of the last frame, or the running ghosting average
+        previous_frame: Vec<[f32; 3]>,
+    }
+
+    impl FrameFilter {
+        pub(crate) fn new(
+            color_correction: ColorCorrection,
+            frame_blending: FrameBlending,
+        ) -> FrameFilter {
+            FrameFilter {
+                color_correction,
+                frame_blending,
+                previous_frame: vec![],
+            }
+        }
+
+        pub(crate) fn apply(&mut self, frame: &RgbaImage) -> RgbaImage {
+            if self.color_correction == ColorCorrection::None
+                && self.frame_blending == FrameBlending::None
+            {
+                return frame.clone();
+            }
+
+            let pixel_count = (frame.width() * frame.height()) as usize;
+            if self.previous_frame.len() != pixel_count {
+                self.previous_frame.clear();
+            }
+            let mut output = RgbaImage::new(frame.width(), frame.height());
+            for (index, (pixel, output_pixel)) in
+                frame.pixels().zip(output.pixels_mut()).enumerate()
+            {
+                let corrected = self.color_correction.correct(pixel.0);
+                let current =
+                    [corrected[0], corrected[1], corrected[2]].map(|channel| channel as f32);
+                //the first frame has nothing to blend with
+                let previous = self.previous_frame.get(index).copied().unwrap_or(current);
+                let (blended, kept) = match self.frame_blending {
+                    FrameBlending::None => (current, current),
+                    FrameBlending::Average => (mix(previous, current, 0.5), current),
+                    FrameBlending::Ghosting => {
+                        let ghost = mix(previous, current, 1.0 - GHOSTING_PERSISTENCE);
+                        (ghost, ghost)
+                    }
+                };
+                if self.previous_frame.len() < pixel_count {
+                    self.previous_frame.push(kept);
+                } else {
+                    self.previous_frame[index] = kept;
+                }
+                let [red, green, blue] = blended.map(|channel| channel.round() as u8);
+                *output_pixel = Rgba([red, green, blue, corrected[3]]);
+            }
+            output
+        }
+    }
+
+    //amount is the weight of to
+    fn mix(from: [f32; 3], to: [f32; 3], amount: f32) -> [f32; 3] {
+        [
+            from[0] + (to[0] - from[0]) * amount,
+            from[1] + (to[1] - from[1]) * amount,
+            from[2] + (to[2] - from[2]) * amount,
+        ]
+    }
+}
@@ -0,0 +1,211 @@
+pub mod hd_pack {
+    use crate::ppu::palette::palette::Color;
+    use crate::ppu::ppu::{Tile, PPU};
+    use image::{Rgba, RgbaImage};
+    use std::collections::{HashMap, HashSet};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    const SCREEN_WIDTH: u32 = 160;
+    const SCREEN_HEIGHT: u32 = 144;
+    const MAX_SCALE: u32 = 16;
+    const FNV_OFFSET: u64 = 0xCBF2_9CE4_8422_2325;
+    const FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
+
+    //the tile pixel a screen pixel was drawn from, x and y are in the unflipped tile
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub(crate) struct TileSource {
+        pub(crate) hash: u64,
+        pub(crate) x: u8,
+        pub(crate) y: u8,
+        pub(crate) x_flip: bool,
+        pub(crate) y_flip: bool,
+    }
+
+    //fnv-1a of the color numbers and the palette they are drawn with (PPU::tile_palette),
+    //so the same tile gets a different replacement for each palette
+    pub(crate) fn tile_hash(tile: &Tile, palette: &[u8]) -> u64 {
+        let color_numbers = tile.iter().flatten().map(|pixel| *pixel as u8);
+        color_numbers
+            .chain(palette.iter().copied())
+            .fold(FNV_OFFSET, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+            })
+    }
+
+    //file name of a tile inside a pack or the dump directory
+    pub(crate) fn tile_file_name(hash: u64) -> String {
+        format!("{:016X}.png", hash)
+    }
+
+    //filled by the ppu while a pack is used, the tiles are only kept when they get dumped
+    #[derive(Debug, Clone)]
+    pub(crate) struct TileCapture {
+        pub(crate) sources: Vec<Option<TileSource>>,
+        pub(crate) tiles: HashMap<u64, (Tile, [Color; 4])>,
+        keep_tiles: bool,
+    }
+
+    impl TileCapture {
+        pub(crate) fn new(keep_tiles: bool) -> TileCapture {
+            TileCapture {
+                sources: vec![None; (SCREEN_WIDTH * SCREEN_HEIGHT) as usize],
+                tiles: HashMap::new(),
+                keep_tiles,
+            }
+        }
+
+        //the colors are only used to dump the tile
+        pub(crate) fn hash(&mut self, tile: &Tile, palette: &[u8], colors: &[Color; 4]) -> u64 {
+            let hash = tile_hash(tile, palette);
+            if self.keep_tiles {
+                self.tiles.entry(hash).or_insert((*tile, *colors));
+            }
+            hash
+        }
+    }
+
+    //tiles drawn at scale times the game resolution, named by the hash of the tile they replace
+    #[derive(Debug)]
+    pub(crate) struct HdPack {
+        pub(crate) scale: u32,
+        tiles: HashMap<u64, RgbaImage>,
+        //tiles without a replacement are saved here as 8x8 pngs
+        dump_directory: Option<PathBuf>,
+        dumped: HashSet<u64>,
+    }
+
+    fn parse_tile_file_name(path: &Path) -> Option<u64> {
+        if path.extension()?.to_str()?.to_lowercase() != "png" {
+            return None;
+        }
+        u64::from_str_radix(path.file_stem()?.to_str()?, 16).ok()
+    }
+
+    impl HdPack {
+        //the scale comes from the size of the replacements, which must all be the same,
+        //without a pack directory the tiles are only dumped
+        pub(crate) fn load(
+            pack_directory: Option<&Path>,
+            dump_directory: Option<&Path>,
+        ) -> Result<HdPack, String> {
+            let mut tiles = HashMap::new();
+            let mut scale = None;
+            if let Some(pack_directory) = pack_directory {
+                let entries = fs::read_dir(pack_directory).map_err(|error| error.to_string())?;
+                for entry in entries {
+                    let path = entry.map_err(|error| error.to_string())?.path();
+                    let hash = match parse_tile_file_name(&path) {
+                        Some(hash) => hash,
+                        None => continue,
+                    };
+                    let image = image::open(&path)
+                        .map_err(|error| format!("{}: {}", path.display(), error))?
+                        .to_rgba8();
+                    let (width, height) = image.dimensions();
+                    let tile_scale = *scale.get_or_insert(width / 8);
+                    if width != height || width != tile_scale * 8 || tile_scale == 0 {
+                        return Err(format!(
+                            "{} is {}x{}, tiles should be {}x{}",
+                            path.display(),
+                            width,
+                            height,
+                            tile_scale * 8,
+                            tile_scale * 8
+                        ));
+                    }
+                    if tile_scale > MAX_SCALE {
+                        return Err(format!("tiles are bigger than {}x", MAX_SCALE));
+                    }
+                    tiles.insert(hash, image);
+                }
+            }
+
+            //tiles dumped by earlier runs aren't saved again
+            let mut dumped = HashSet::new();
+            if let Some(dump_directory) = dump_directory {
+                fs::create_dir_all(dump_directory).map_err(|error| error.to_string())?;
+                for entry in fs::read_dir(dump_directory).map_err(|error| error.to_string())? {
+                    let path = entry.map_err(|error| error.to_string())?.path();
+                    dumped.extend(parse_tile_file_name(&path));
+                }
+            }
+
+            Ok(HdPack {
+                scale: scale.unwrap_or(1),
+                tiles,
+                dump_directory: dump_directory.map(Path::to_path_buf),
+                dumped,
+            })
+        }
+
+        pub(crate) fn len(&self) -> usize {
+            self.tiles.len()
+        }
+
+        //starts recording which tile each pixel comes from
+        pub(crate) fn attach(&self, ppu: &mut PPU) {
+            ppu.tile_capture = Some(TileCapture::new(self.dump_directory.is_some()));
+        }
+
+        //the last frame at scale times its size, pixels drawn from a replaced tile are taken from
+        //the replacement and the rest are scaled up, transparent replacement pixels keep the original
+        pub(crate) fn compose(&mut self, ppu: &mut PPU) -> RgbaImage {
+            let scale = self.scale;
+            let mut frame = RgbaImage::new(SCREEN_WIDTH * scale, SCREEN_HEIGHT * scale);
+            let capture = match ppu.tile_capture.as_mut() {
+                Some(capture) => capture,
+                None => return frame,
+            };
+            for (x, y, pixel) in ppu.image_buffer.enumerate_pixels() {
+                let source = capture.sources[(y * SCREEN_WIDTH + x) as usize];
+                let replacement =
+                    source.and_then(|source| Some((source, self.tiles.get(&source.hash)?)));
+                for block_y in 0..scale {
+                    for block_x in 0..scale {
+                        let color = replacement
+                            .map(|(source, tile)| {
+                                let tile_x = if source.x_flip {
+                                    scale - 1 - block_x
+                                } else {
+                                    block_x
+                                };
+                                let tile_y = if source.y_flip {
+                                    scale - 1 - block_y
+                                } else {
+                                    block_y
+                                };
+                                *tile.get_pixel(
+                                    source.x as u32 * scale + tile_x,
+                                    source.y as u32 * scale + tile_y,
+                                )
+                            })
+                            .filter(|color| color.0[3] != 0)
+                            .unwrap_or(*pixel);
+                        frame.put_pixel(x * scale + block_x, y * scale + block_y, color);
+                    }
+                }
+            }
+
+            let seen_tiles: Vec<(u64, (Tile, [Color; 4]))> = capture.tiles.drain().collect();
+            if let Some(dump_directory) = &self.dump_directory {
+                for (hash, (tile, colors)) in seen_tiles {
+                    if self.tiles.contains_key(&hash) || !self.dumped.insert(hash) {
+                        continue;
+                    }
+                    let mut image = RgbaImage::new(8, 8);
+                    for (y, row) in tile.iter().enumerate() {
+                        for (x, pixel) in row.iter().enumerate() {
+                            image.put_pixel(x as u32, y as u32, Rgba(colors[*pixel as usize]));
+                        }
+                    }
+                    let path = dump_directory.join(tile_file_name(hash));
+                    if let Err(error) = image.save(&path) {
+                        println!("Can't dump tile to {}: {}", path.display(), error);
+                    }
+                }
+            }
+            frame
+        }
+    }
+}
@@ -1,19 +1,30 @@
+mod cgb;
+pub(crate) mod compatibility;
+mod fifo;
+pub(crate) mod filters;
+pub(crate) mod hd_pack;
+pub(crate) mod palette;
+pub(crate) mod scalers;
+pub(crate) mod sgb;
+
 pub mod ppu {
+    use crate::ppu::fifo::fifo::PixelFifo;
+    use crate::ppu::hd_pack::hd_pack::TileCapture;
+    use crate::ppu::palette::palette::{Color, DmgPalette, PaletteLayer};
     use image::{Rgba, RgbaImage};
     use piston_window::math::add;
     use std::borrow::BorrowMut;
     use std::fmt::{format, Debug, Display, Formatter};
 
-    const PPU_TILES_NUMBER: usize = 384;
-    const PPU_SPRITES_NUMBER: usize = 40;
+    pub(crate) const PPU_TILES_NUMBER: usize = 384;
+    pub(crate) const PPU_SPRITES_NUMBER: usize = 40;
     const TOTAL_SCANLINES: u32 = 153;
     const VISIBLE_SCANLINES: u8 = 144;
-    const HBLANK_DURATION_DOTS: u32 = 204;
-    const VBLANK_DURATION_DOTS: u32 = 456;
+    //mode 3 length depends on scrolling and sprites, hblank fills the rest of the line
+    const SCANLINE_DURATION_DOTS: u32 = 456;
     const OAM_DURATION_DOTS: u32 = 80;
-    const VRAM_DURATION_DOTS: u32 = 172;
 
-    const SCREEN_HORIZONTAL_RESOLUTION: u32 = 160;
+    pub(crate) const SCREEN_HORIZONTAL_RESOLUTION: u32 = 160;
     const SCREEN_VERTICAL_RESOLUTION: u32 = 144;
 
     const TILE_SIZE: u32 = 8;
@@ -21,13 +32,94 @@ pub mod ppu {
 
     #[derive(Copy, Clone, Debug, PartialEq)]
     pub(crate) struct Sprite {
-        x: i32,
-        y: i32,
-        tile_number: u8,
-        background_priority: bool,
-        y_flip: bool,
-        x_flip: bool,
-        palette: bool,
+        pub(crate) x: i32,
+        pub(crate) y: i32,
+        pub(crate) tile_number: u8,
+        pub(crate) background_priority: bool,
+        pub(crate) y_flip: bool,
+        pub(crate) x_flip: bool,
+        pub(crate) palette: bool,
+        //cgb only, obj palette 0-7 and vram bank of the tile
+        pub(crate) cgb_palette: u8,
+        pub(crate) tile_bank: usize,
+        //cgb sprites overlap by oam order instead of x
+        pub(crate) oam_index: usize,
+    }
+
+    //layers hidden by the emulator, the game still sees the real lcdc and oam
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub(crate) struct RenderMask {
+        pub(crate) background: bool,
+        pub(crate) window: bool,
+        pub(crate) sprites: bool,
+        //one bit per oam entry, set for the sprites that are hidden
+        pub(crate) hidden_sprites: u64,
+    }
+
+    impl Default for RenderMask {
+        fn default() -> Self {
+            RenderMask {
+                background: true,
+                window: true,
+                sprites: true,
+                hidden_sprites: 0,
+            }
+        }
+    }
+
+    impl RenderMask {
+        pub(crate) fn is_sprite_visible(&self, oam_index: usize) -> bool {
+            self.sprites && self.hidden_sprites & (1 << oam_index) == 0
+        }
+
+        pub(crate) fn toggle_sprite(&mut self, oam_index: usize) {
+            self.hidden_sprites ^= 1 << oam_index;
+        }
+    }
+
+    //registers as they were when a line started its pixel transfer
+    #[derive(Debug, Clone, Copy, PartialEq, Default)]
+    pub(crate) struct LineRegisters {
+        pub(crate) scroll_x: u8,
+        pub(crate) scroll_y: u8,
+        pub(crate) window_x: u8,
+        pub(crate) window_y: u8,
+        pub(crate) lcd_control: u8,
+        pub(crate) lcd_status: u8,
+        pub(crate) background_palette: u8,
+        pub(crate) obj_0_palette: u8,
+        pub(crate) obj_1_palette: u8,
+    }
+
+    impl LineRegisters {
+        pub(crate) const NAMES: [&'static str; 9] =
+            ["SCX", "SCY", "WX", "WY", "LCDC", "STAT", "BGP", "OBP0", "OBP1"];
+
+        //values in the order of NAMES
+        pub(crate) fn values(&self) -> [u8; 9] {
+            [
+                self.scroll_x,
+                self.scroll_y,
+                self.window_x,
+                self.window_y,
+                self.lcd_control,
+                self.lcd_status,
+                self.background_palette,
+                self.obj_0_palette,
+                self.obj_1_palette,
+            ]
+        }
+    }
+
+    impl Display for LineRegisters {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            let registers: Vec<String> = LineRegisters::NAMES
+                .iter()
+                .zip(self.values())
+                .map(|(name, value)| format!("{} {:02X}", name, value))
+                .collect();
+            write!(f, "{}", registers.join(" "))
+        }
     }
 
     //each tile is 8x8 pixels
@@ -59,6 +151,9 @@ pub mod ppu {
         LYC_EQUALS_LY = 6,
     }
 
+    //set in stat when ly == lyc
+    const STAT_LYC_FLAG: u8 = 1 << 2;
+
     pub(crate) enum LCDCFlags {
         LCD_enabled = 7,
         Window_tile_map_area = 6, //determines which background map to use 0=9800-9BFF, 1=9C00-9FFF
@@ -122,7 +217,7 @@ pub mod ppu {
         [[TilePixelValue::Zero; TILE_SIZE as usize]; TILE_SIZE as usize]
     }
 
-    fn create_empty_sprite() -> Sprite {
+    fn create_empty_sprite(oam_index: usize) -> Sprite {
         Sprite {
             x: -16,
             y: -8,
@@ -131,6 +226,9 @@ pub mod ppu {
             y_flip: false,
             x_flip: false,
             palette: false,
+            cgb_palette: 0,
+            tile_bank: 0,
+            oam_index,
         }
     }
 
@@ -162,6 +260,21 @@ pub mod ppu {
         dump
     }
 
+    //one line per scanline, marked when a register changed since the previous one
+    pub(crate) fn dump_line_registers(ppu: &PPU) -> String {
+        let mut dump = String::new();
+        for (line, registers) in ppu.line_registers.iter().enumerate() {
+            let changed = line > 0 && ppu.line_registers[line - 1] != *registers;
+            dump.push_str(&format!(
+                "{:03}{} {}\n",
+                line,
+                if changed { "*" } else { " " },
+                registers
+            ));
+        }
+        dump
+    }
+
     pub fn dump_current_screen_tiles(
         mut ppu: &mut PPU,
     ) -> [TileRow; (SCREEN_VERTICAL_RESOLUTION / TILE_SIZE) as usize] {
@@ -169,20 +282,21 @@ pub mod ppu {
             [TileRow([(0, 0); (SCREEN_HORIZONTAL_RESOLUTION / TILE_SIZE) as usize]);
                 (SCREEN_VERTICAL_RESOLUTION / TILE_SIZE) as usize];
         for screen_line in 0..SCREEN_VERTICAL_RESOLUTION / TILE_SIZE {
-            screen_dump[screen_line as usize] = ppu.render_background(screen_line * TILE_SIZE);
+            screen_dump[screen_line as usize] = ppu.background_tile_row(screen_line * TILE_SIZE);
         }
         screen_dump
     }
 
-    //given a tile and a mutable RgbaImage reference, draw the tile into the image using default color mapping and given x,y offsets
+    //given a tile and a mutable RgbaImage reference, draw the tile into the image using the given colors and x,y offsets
     pub(crate) fn add_tile_to_rgba_image(
         Tile: Tile,
         rgba_image: &mut RgbaImage,
         (x_offset, y_offset): (u32, u32),
+        colors: &[Color; 4],
     ) {
         for (y, tile_row) in Tile.iter().enumerate() {
             for (x, tile_pixel) in tile_row.iter().enumerate() {
-                let color_at_coordinate = COLORS[*tile_pixel as usize];
+                let color_at_coordinate = colors[*tile_pixel as usize];
                 rgba_image.put_pixel(
                     x as u32 + x_offset,
                     y as u32 + y_offset,
@@ -193,7 +307,10 @@ pub mod ppu {
     }
 
     //dump a tile set into an RgbaImage
-    pub(crate) fn tile_set_to_rgba_image(tile_set: [Tile; PPU_TILES_NUMBER]) -> RgbaImage {
+    pub(crate) fn tile_set_to_rgba_image(
+        tile_set: [Tile; PPU_TILES_NUMBER],
+        colors: &[Color; 4],
+    ) -> RgbaImage {
         let (columns_number, rows_number): (u32, u32) = (20, 20);
         let mut rgba_image = RgbaImage::new(rows_number * 8, columns_number * 8);
 
@@ -206,6 +323,7 @@ pub mod ppu {
                         current_tile,
                         rgba_image.borrow_mut(),
                         (x_offset * 8, y_offset * 8),
+                        colors,
                     );
                 }
             }
@@ -213,32 +331,64 @@ pub mod ppu {
         rgba_image
     }
 
-    pub(crate) const COLORS: [[u8; 4]; 4] = [
-        //cool red palette, colors should be based on palette map
-        [124, 63, 88, 255],   //#7c3f58           [248, 227, 196, 255],
-        [235, 107, 111, 255], //#eb6b6f         [204, 52, 149, 255],
-        [249, 168, 117, 255], //#f9a875        [107, 31, 177, 255],
-        [255, 246, 211, 255], //#fff6d3        [11, 6, 48, 255],
-    ];
-
     pub struct PPU {
+        //dots elapsed in the current line
         clock: u32,
-        current_line: u32,         //ly
-        current_line_compare: u32, //lyc
+        pub(crate) current_line: u32, //ly
+        current_line_compare: u32,    //lyc
         pub(crate) lcd_status: u8,
+        //internal stat interrupt line, interrupts are requested on its rising edge
+        stat_line: bool,
+        //stat interrupt requested outside of step (dmg stat write quirk)
+        pending_stat_interrupt: bool,
         pub(crate) lcd_control: u8,
-        scroll_y: u8,
-        scroll_x: u8,
-        window_x: u8,
-        window_y: u8,
-        background_palette_data: u8,
-        obj_0_palette_data: u8,
-        obj_1_palette_data: u8,
+        pub(crate) scroll_y: u8,
+        pub(crate) scroll_x: u8,
+        pub(crate) window_x: u8,
+        pub(crate) window_y: u8,
+        //internal window line counter, only advances on lines where the window was drawn
+        pub(crate) window_line: u8,
+        //set once ly == wy during the current frame
+        pub(crate) window_y_triggered: bool,
+        //set for the first frame after the lcd is turned on, pixels are not drawn
+        pub(crate) blank_frame: bool,
+        //set when mode 3 ends, cleared by the hblank dma
+        hblank_started: bool,
+        pub(crate) background_palette_data: u8,
+        pub(crate) obj_0_palette_data: u8,
+        pub(crate) obj_1_palette_data: u8,
         pub(crate) image_buffer: RgbaImage,
+        //dmg shade (0-3, after bgp/obp0/obp1) of each pixel, colored by the sgb
+        pub(crate) shade_buffer: Vec<u8>,
         pub(crate) oam: [u8; 0x2000],
         pub(crate) video_ram: [u8; 0x2000],
         pub(crate) tile_set: [Tile; PPU_TILES_NUMBER],
         pub(crate) sprite_set: [Sprite; PPU_SPRITES_NUMBER],
+        pub(crate) fifo: PixelFifo,
+        //output colors, shades come from bgp/obp0/obp1
+        pub(crate) palette: DmgPalette,
+        //set when the cartridge header asks for cgb features
+        pub(crate) cgb_mode: bool,
+        //vbk, selects the vram bank seen by the cpu
+        pub(crate) vram_bank: usize,
+        //bank 1 holds extra tiles and the background map attributes
+        pub(crate) video_ram_bank_1: [u8; 0x2000],
+        pub(crate) tile_set_bank_1: [Tile; PPU_TILES_NUMBER],
+        //8 palettes of 4 little endian rgb555 colors each
+        pub(crate) background_palette_ram: [u8; 64],
+        pub(crate) obj_palette_ram: [u8; 64],
+        //bcps and ocps, bit 7 increments the index after each data write
+        pub(crate) background_palette_index: u8,
+        pub(crate) obj_palette_index: u8,
+        //opri, bit 0 clear means sprites overlap by oam order
+        pub(crate) object_priority_mode: u8,
+        pub(crate) render_mask: RenderMask,
+        //registers of each visible line of the last frame, for raster effects debugging
+        pub(crate) line_registers: [LineRegisters; VISIBLE_SCANLINES as usize],
+        //set while an hd pack is used, where each pixel of the frame comes from
+        pub(crate) tile_capture: Option<TileCapture>,
+        //draw every sprite of a line instead of the first 10, mode 3 timing is unchanged
+        pub(crate) unlimited_sprites: bool,
     }
 
     impl Debug for PPU {
@@ -255,12 +405,18 @@ pub mod ppu {
                 current_line_compare: 0,
                 clock: 0,
                 lcd_status: 0,
+                stat_line: false,
+                pending_stat_interrupt: false,
                 tile_set: [create_empty_tile(); PPU_TILES_NUMBER],
                 lcd_control: 0,
                 scroll_y: 0,
                 scroll_x: 0,
                 window_x: 0,
                 window_y: 0,
+                window_line: 0,
+                window_y_triggered: false,
+                blank_frame: false,
+                hblank_started: false,
                 background_palette_data: 0,
                 obj_0_palette_data: 0,
                 obj_1_palette_data: 0,
@@ -269,128 +425,256 @@ pub mod ppu {
                     SCREEN_HORIZONTAL_RESOLUTION,
                     SCREEN_VERTICAL_RESOLUTION,
                 ),
-                sprite_set: [create_empty_sprite(); PPU_SPRITES_NUMBER],
+                shade_buffer: vec![
+                    0;
+                    (SCREEN_HORIZONTAL_RESOLUTION * SCREEN_VERTICAL_RESOLUTION) as usize
+                ],
+                sprite_set: std::array::from_fn(create_empty_sprite),
+                fifo: PixelFifo::new(),
+                palette: DmgPalette::default(),
+                cgb_mode: false,
+                vram_bank: 0,
+                video_ram_bank_1: [0; 0x2000],
+                tile_set_bank_1: [create_empty_tile(); PPU_TILES_NUMBER],
+                //the boot rom leaves every palette white
+                background_palette_ram: [0xFF; 64],
+                obj_palette_ram: [0xFF; 64],
+                background_palette_index: 0,
+                obj_palette_index: 0,
+                object_priority_mode: 0,
+                render_mask: RenderMask::default(),
+                line_registers: [LineRegisters::default(); VISIBLE_SCANLINES as usize],
+                tile_capture: None,
+                unlimited_sprites: false,
             }
         }
 
         pub(crate) fn set_lcdc(&mut self, value: u8) {
+            let was_enabled = self.get_lcdc_value(LCDCFlags::LCD_enabled);
             self.lcd_control = value;
+            let is_enabled = self.get_lcdc_value(LCDCFlags::LCD_enabled);
 
-            if !self.get_lcdc_value(LCDCFlags::LCD_enabled) {
+            if was_enabled && !is_enabled {
+                //ly is held at 0 and the screen goes blank while the lcd is off
                 self.clock = 0;
-                //some checks should be performed before hard-changing ppu mode
+                self.current_line = 0;
                 self.set_current_mode(PpuMode::HBlank);
-                //next mode should be 2
-                self.current_line = 0
+                self.update_lyc_flag();
+                self.stat_line = false;
+                self.clear_screen();
+            } else if !was_enabled && is_enabled {
+                //the first frame after turning the lcd on isn't sent to the screen
+                self.clock = 0;
+                self.current_line = 0;
+                self.set_current_mode(PpuMode::OAM);
+                self.blank_frame = true;
+                self.clear_screen();
+                self.window_line = 0;
+                self.window_y_triggered = false;
+            }
+        }
+
+        //a disabled cgb lcd is white
+        fn clear_screen(&mut self) {
+            let color = if self.cgb_mode {
+                [255, 255, 255, 255]
+            } else {
+                self.palette.background[0]
+            };
+            for pixel in self.image_buffer.pixels_mut() {
+                *pixel = Rgba(color);
             }
+            self.shade_buffer.fill(0);
+        }
+
+        pub(crate) fn is_lcd_enabled(&self) -> bool {
+            self.get_lcdc_value(LCDCFlags::LCD_enabled)
+        }
+
+        //returns true once for each hblank of a visible line
+        pub(crate) fn take_hblank_started(&mut self) -> bool {
+            std::mem::take(&mut self.hblank_started)
+        }
+
+        //the cpu can't access vram during mode 3
+        pub(crate) fn is_vram_accessible(&self) -> bool {
+            !self.get_lcdc_value(LCDCFlags::LCD_enabled) || self.get_current_mode() != PpuMode::VRAM
+        }
+
+        //the cpu can't access oam during modes 2 and 3
+        pub(crate) fn is_oam_accessible(&self) -> bool {
+            !self.get_lcdc_value(LCDCFlags::LCD_enabled)
+                || !matches!(self.get_current_mode(), PpuMode::OAM | PpuMode::VRAM)
         }
 
         pub(crate) fn get_current_mode(&self) -> PpuMode {
             return PpuMode::try_from((self.lcd_status & 0b11)).unwrap();
         }
 
-        pub(crate) fn set_current_mode_from_value(&mut self, mut value: u8) {
-            //TODO mask given value
-            self.lcd_status = value
+        //only the interrupt enable bits (3-6) are writable, mode and ly=lyc flag are read only
+        pub(crate) fn write_lcd_status(&mut self, value: u8) {
+            //dmg quirk: for one cycle the write behaves as if every source was enabled,
+            //so a stat interrupt fires if the ppu is in hblank, vblank or ly == lyc.
+            //the cgb doesn't have it
+            let stat_line_on_write = matches!(
+                self.get_current_mode(),
+                PpuMode::HBlank | PpuMode::VBlank
+            ) || self.lcd_status & STAT_LYC_FLAG != 0;
+            if !self.cgb_mode
+                && stat_line_on_write
+                && !self.stat_line
+                && self.get_lcdc_value(LCDCFlags::LCD_enabled)
+            {
+                self.pending_stat_interrupt = true;
+            }
+
+            self.lcd_status = (self.lcd_status & 0b0000_0111) | (value & 0b0111_1000);
+            self.stat_line = self.get_stat_line();
         }
 
         pub(crate) fn set_current_mode(&mut self, ppu_mode: PpuMode) {
-            self.lcd_status = (self.lcd_control & 0b00) | ppu_mode as u8;
+            self.lcd_status = (self.lcd_status & !0b11) | ppu_mode as u8;
         }
 
-        fn should_try_to_request_lyc_ly_interrupt(&mut self) -> bool {
+        fn update_lyc_flag(&mut self) {
             if self.current_line == self.current_line_compare {
-                return self.should_rise_lcdc_interrupt(StatInterruptType::LYC_EQUALS_LY);
+                self.lcd_status |= STAT_LYC_FLAG;
+            } else {
+                self.lcd_status &= !STAT_LYC_FLAG;
             }
-            return false;
         }
 
-        fn should_rise_lcdc_interrupt(&mut self, stat_interrupt_type: StatInterruptType) -> bool {
-            //check if stat interrupt type is enabled in lcd_status
-            return (self.lcd_status & (1 << stat_interrupt_type as u8)) != 0;
+        fn is_stat_interrupt_enabled(&self, stat_interrupt_type: StatInterruptType) -> bool {
+            (self.lcd_status & (1 << stat_interrupt_type as u8)) != 0
         }
 
+        //all the enabled stat sources are or-ed into a single line
+        fn get_stat_line(&self) -> bool {
+            let mode_source = match self.get_current_mode() {
+                PpuMode::HBlank => self.is_stat_interrupt_enabled(StatInterruptType::HBlank),
+                //the oam source also fires when line 144 starts
+                PpuMode::VBlank => {
+                    self.is_stat_interrupt_enabled(StatInterruptType::VBlank)
+                        || (self.current_line == VISIBLE_SCANLINES as u32
+                            && self.clock == 0
+                            && self.is_stat_interrupt_enabled(StatInterruptType::OAM))
+                }
+                PpuMode::OAM => self.is_stat_interrupt_enabled(StatInterruptType::OAM),
+                PpuMode::VRAM => false,
+            };
+            mode_source
+                || (self.lcd_status & STAT_LYC_FLAG != 0
+                    && self.is_stat_interrupt_enabled(StatInterruptType::LYC_EQUALS_LY))
+        }
+
+        //the interrupt is only requested when the line goes from low to high
+        fn update_stat_line(&mut self) -> bool {
+            self.update_lyc_flag();
+            let stat_line = self.get_stat_line();
+            let rising_edge = stat_line && !self.stat_line;
+            self.stat_line = stat_line;
+            rising_edge
+        }
+
+        //advance the ppu by the given amount of dots
         pub(crate) fn step(&mut self, clock: u32) -> (PpuMode, bool, bool) {
-            self.clock += clock;
             let (mut should_rise_vblank_interrupt, mut should_rise_stat_interrupt) = (false, false);
+            if !self.get_lcdc_value(LCDCFlags::LCD_enabled) {
+                return (self.get_current_mode(), false, false);
+            }
+            for _ in 0..clock {
+                should_rise_vblank_interrupt |= self.step_dot();
+                should_rise_stat_interrupt |= self.update_stat_line();
+            }
+            if self.pending_stat_interrupt {
+                self.pending_stat_interrupt = false;
+                should_rise_stat_interrupt = true;
+            }
+            return (
+                self.get_current_mode(),
+                should_rise_vblank_interrupt,
+                should_rise_stat_interrupt,
+            );
+        }
+
+        //returns true when vblank starts
+        fn step_dot(&mut self) -> bool {
+            let mut should_rise_vblank_interrupt = false;
 
             match self.get_current_mode() {
+                // OAM read
+                PpuMode::OAM => {
+                    if self.clock == 0 {
+                        if self.current_line == self.window_y as u32 {
+                            self.window_y_triggered = true;
+                        }
+                        self.scan_oam();
+                    }
+                    self.clock += 1;
+                    if self.clock == OAM_DURATION_DOTS {
+                        self.set_current_mode(PpuMode::VRAM);
+                        self.start_pixel_transfer();
+                    }
+                }
+                // VRAM read, pixels are pushed to the lcd one dot at a time
+                PpuMode::VRAM => {
+                    self.clock += 1;
+                    if self.pixel_transfer_dot() {
+                        if self.fifo.window_active {
+                            self.window_line = self.window_line.wrapping_add(1);
+                        }
+                        self.set_current_mode(PpuMode::HBlank);
+                        self.hblank_started = true;
+                    }
+                }
                 //horizontal blanking
                 PpuMode::HBlank => {
-                    if self.clock >= HBLANK_DURATION_DOTS {
+                    self.clock += 1;
+                    if self.clock == SCANLINE_DURATION_DOTS {
                         self.clock = 0;
                         self.current_line += 1;
 
-                        if self.current_line == (VISIBLE_SCANLINES - 1) as u32 {
+                        if self.current_line == VISIBLE_SCANLINES as u32 {
                             //last line, go to v blank
                             should_rise_vblank_interrupt = true;
                             self.set_current_mode(PpuMode::VBlank);
-                            should_rise_stat_interrupt =
-                                self.should_rise_lcdc_interrupt(StatInterruptType::VBlank)
                         } else {
                             //scan another line
                             self.set_current_mode(PpuMode::OAM);
                         }
-
-                        should_rise_stat_interrupt =
-                            self.should_rise_lcdc_interrupt(StatInterruptType::OAM);
-                        should_rise_stat_interrupt = self.should_try_to_request_lyc_ly_interrupt();
                     }
                 }
                 //vertical blanking
                 PpuMode::VBlank => {
-                    if self.clock >= VBLANK_DURATION_DOTS {
+                    self.clock += 1;
+                    if self.clock == SCANLINE_DURATION_DOTS {
                         self.clock = 0;
                         self.current_line += 1;
 
                         if self.current_line > TOTAL_SCANLINES {
                             self.set_current_mode(PpuMode::OAM);
-                            should_rise_stat_interrupt =
-                                self.should_rise_lcdc_interrupt(StatInterruptType::OAM);
                             self.current_line = 0;
+                            self.window_line = 0;
+                            self.window_y_triggered = false;
+                            self.blank_frame = false;
                         }
                     }
-                    should_rise_stat_interrupt = self.should_try_to_request_lyc_ly_interrupt();
                 }
-                // OAM read
-                PpuMode::OAM => {
-                    if self.clock >= OAM_DURATION_DOTS {
-                        self.clock = 0;
-                        self.set_current_mode(PpuMode::VRAM);
-                    }
-                }
-                // VRAM read and complete line scan
-                PpuMode::VRAM => {
-                    if self.clock >= VRAM_DURATION_DOTS {
-                        self.clock = 0;
-                        self.render_current_line();
-                        self.set_current_mode(PpuMode::HBlank);
-                        should_rise_stat_interrupt =
-                            self.should_rise_lcdc_interrupt(StatInterruptType::HBlank)
-                    }
-                }
-                _ => {}
             }
-            return (
-                self.get_current_mode(),
-                should_rise_vblank_interrupt,
-                should_rise_stat_interrupt,
-            );
+            should_rise_vblank_interrupt
         }
 
-        pub(crate) fn render_current_line(&mut self) {
-            if self.get_lcdc_value(LCDCFlags::Window_enable) {}
-            if self.get_lcdc_value(LCDCFlags::Bg_enable) {
-                self.render_background(self.current_line);
-            }
-
-            if self.get_lcdc_value(LCDCFlags::Obj_enable) {
-                self.render_sprites(self.current_line);
+        //index in tile_set of a tile id read from a tile map, 0x8800 addressing uses signed ids
+        pub(crate) fn tile_set_index(&self, tile_id: u8) -> usize {
+            if self.get_lcdc_value(LCDCFlags::BG_tile_set_area) {
+                tile_id as usize
+            } else {
+                256_u16.wrapping_add((tile_id as i8) as u16) as usize
             }
         }
 
-        pub(crate) fn render_background(&mut self, line: u32) -> TileRow {
-            //the tile map contains the index of the tile to be displayed
+        //tiles (and their tile map address) used by the background at the given line
+        pub(crate) fn background_tile_row(&self, line: u32) -> TileRow {
             let background_tile_map_starting_address: usize =
                 if self.get_lcdc_value(LCDCFlags::BG_tile_map_area) {
                     0x1C00
@@ -398,111 +682,43 @@ pub mod ppu {
                     0x1800
                 };
 
-            //tile offset to render tiles sub-portions
-            let mut x_tile_offset = self.scroll_x & 7;
-            let y_tile_offset = (line + self.scroll_y as u32) & 7;
-
             let mut used_tiles: TileRow = TileRow([(0, 0); TILES_IN_VISIBLE_LINE as usize]);
-            for pixel in 0..SCREEN_HORIZONTAL_RESOLUTION {
-                //viewport offsets used to retrieve right tile id from tile_map in vram
-                // each row (y) is 32 tiles (from the total 256x256 viewport), each tile is 8 pixel (hence 8*32)
-                let y_offset = (((line + self.scroll_y as u32) / 8 % 32) * 32) as usize;
-                let x_offset = ((pixel as u8 + self.scroll_x) / 8) as usize;
-
-                let mut tile_id = self.video_ram
-                    [(background_tile_map_starting_address + y_offset + x_offset) as usize]
-                    as u16;
-                used_tiles.0[(pixel / 8) as usize] = (
-                    tile_id,
-                    (background_tile_map_starting_address + y_offset + x_offset) as u16,
-                );
-                let mut tile = None;
-
-                if !self.get_lcdc_value(LCDCFlags::BG_tile_set_area) {
-                    let fixed_tile_id = 256_u16.wrapping_add((tile_id as i8) as u16);
-                    used_tiles.0[(pixel / 8) as usize] = (
-                        fixed_tile_id,
-                        (background_tile_map_starting_address + y_offset + x_offset) as u16,
-                    );
-                    tile = Some(self.tile_set[fixed_tile_id as usize]);
-                } else {
-                    tile = Some(self.tile_set[tile_id as usize]);
-                }
-
-                let color_number_at_coordinates: TilePixelValue =
-                    tile.unwrap()[y_tile_offset as usize][x_tile_offset as usize];
-
-                let color_at_coordinate = self.get_color_from_palette(
-                    color_number_at_coordinates,
-                    self.background_palette_data,
+            // each row (y) is 32 tiles (from the total 256x256 viewport)
+            let y_offset = ((((line + self.scroll_y as u32) & 0xFF) / 8) * 32) as usize;
+            for tile in 0..TILES_IN_VISIBLE_LINE as usize {
+                let x_offset = ((self.scroll_x / 8) as usize + tile) & 31;
+                let tile_map_address = background_tile_map_starting_address + y_offset + x_offset;
+                used_tiles.0[tile] = (
+                    self.tile_set_index(self.video_ram[tile_map_address]) as u16,
+                    tile_map_address as u16,
                 );
-
-                x_tile_offset = (x_tile_offset + 1) % 8;
-
-                self.image_buffer
-                    .put_pixel(pixel, line, Rgba(color_at_coordinate));
             }
             used_tiles
         }
 
-        pub(crate) fn render_sprites(&mut self, line: u32) {
-            for sprite_index in 0..PPU_SPRITES_NUMBER - 1 {
-                let sprite = self.sprite_set[sprite_index];
-                let sprite_height = if self.get_lcdc_value(LCDCFlags::Obj_size) {
-                    16
-                } else {
-                    8
-                };
-
-                //check if sprite is in current line
-                //todo 8 should be sprite height
-                if sprite.y as i32 <= line as i32 && sprite.y as i32 + 8 > line as i32 {
-                    let palette = if sprite.palette {
-                        self.obj_1_palette_data
-                    } else {
-                        self.obj_0_palette_data
-                    };
-
-                    //current tile row
-                    let current_tile = self.tile_set[sprite.tile_number as usize];
-                    let tile_row = current_tile[(line - sprite.y as u32) as usize];
-                    //todo check sprite y flip
-
-                    for x in 0..8 {
-                        //todo priority and background check
-                        //if sprite is visible and sprite is within viewport and pixel is not transparent
-                        if (sprite.x + x) > 0
-                            && (sprite.x as i32 + 8 as i32) < 160
-                            && tile_row[x as usize] != TilePixelValue::Zero
-                        {
-                            //todo check x-flip
-                            let color_number_at_coordinates: TilePixelValue = tile_row[x as usize];
-                            let color_at_coordinate =
-                                self.get_color_from_palette(color_number_at_coordinates, palette);
-                            self.image_buffer.put_pixel(
-                                (sprite.x + x) as u32,
-                                line,
-                                Rgba(color_at_coordinate),
-                            );
-                        }
-                    }
-                }
-            }
+        //given a TilePixelValue returns the shade (0-3) picked by its palette map (stored at 0xFF47-0xFF49)
+        pub(crate) fn get_shade_from_palette(
+            &self,
+            color_number: TilePixelValue,
+            palette_layer: PaletteLayer,
+        ) -> u8 {
+            let palette = match palette_layer {
+                PaletteLayer::Background => self.background_palette_data,
+                PaletteLayer::Obj0 => self.obj_0_palette_data,
+                PaletteLayer::Obj1 => self.obj_1_palette_data,
+            };
+            // get bits couples by moving right by number * 2 and mask with 3 (b11) to get the value
+            (palette >> (color_number as u8 * 2)) & 0x3
         }
 
-        //given a TilePixelValue returns corresponding palette color, using palette map (stored at 0xFF47)
+        //given a TilePixelValue returns corresponding layer color
         pub(crate) fn get_color_from_palette(
-            &mut self,
+            &self,
             color_number: TilePixelValue,
-            palette: u8,
-        ) -> [u8; 4] {
-            let color_number = color_number as u8;
-            if color_number < 4 {
-                // get bits couples by moving right by number * 2 and mask with 3 (b11) to get the value
-                let value_for_color = (palette >> (color_number * 2)) & 0x3;
-                return COLORS[value_for_color as usize];
-            }
-            COLORS[0]
+            palette_layer: PaletteLayer,
+        ) -> Color {
+            let shade = self.get_shade_from_palette(color_number, palette_layer);
+            self.palette.colors(palette_layer)[shade as usize]
         }
 
         pub(crate) fn update_sprite(&mut self, address: usize, value: u8) {
@@ -514,19 +730,27 @@ pub mod ppu {
                     1 => self.sprite_set[sprite_index].x = value as i32 - 8,
                     2 => self.sprite_set[sprite_index].tile_number = value,
                     3 => {
-                        self.sprite_set[sprite_index].palette = (value & 0x10) == 1;
-                        self.sprite_set[sprite_index].x_flip = (value & 0x20) == 1;
-                        self.sprite_set[sprite_index].y_flip = (value & 0x40) == 1;
-                        self.sprite_set[sprite_index].background_priority = (value & 0x80) == 1;
+                        self.sprite_set[sprite_index].cgb_palette = value & 0x07;
+                        self.sprite_set[sprite_index].tile_bank = ((value & 0x08) >> 3) as usize;
+                        self.sprite_set[sprite_index].palette = (value & 0x10) != 0;
+                        self.sprite_set[sprite_index].x_flip = (value & 0x20) != 0;
+                        self.sprite_set[sprite_index].y_flip = (value & 0x40) != 0;
+                        //bg colors 1-3 are drawn over the sprite
+                        self.sprite_set[sprite_index].background_priority = (value & 0x80) != 0;
                     }
                     _ => {}
                 }
             }
         }
 
-        pub(crate) fn update_tile(&mut self, address: usize) {
+        pub(crate) fn update_tile(&mut self, bank: usize, address: usize) {
             //address is normalized removing LSB
             let address = address & 0x1FFE;
+            let (video_ram, tile_set) = if bank == 1 {
+                (&self.video_ram_bank_1, &mut self.tile_set_bank_1)
+            } else {
+                (&self.video_ram, &mut self.tile_set)
+            };
 
             //each tile occupies 16 bytes, we find tile index dividing the address by 16
             let tile_number = address / 16;
@@ -544,13 +768,13 @@ pub mod ppu {
                 let current_column_mask_position = 1 << (7 - tile_column);
 
                 let bit_value_for_position =
-                    if (self.video_ram[address] & current_column_mask_position) >= 1 {
+                    if (video_ram[address] & current_column_mask_position) >= 1 {
                         1
                     } else {
                         0
                     };
                 let bit_value_for_next_position =
-                    if (self.video_ram[address + 1] & current_column_mask_position) >= 1 {
+                    if (video_ram[address + 1] & current_column_mask_position) >= 1 {
                         2
                     } else {
                         0
@@ -564,12 +788,13 @@ pub mod ppu {
                     value => panic!("{} Invalid tile value", value),
                 };
 
-                self.tile_set[tile_number][tile_row][tile_column] = tile_value;
+                tile_set[tile_number][tile_row][tile_column] = tile_value;
             }
         }
 
         pub(crate) fn read_byte(&self, address: usize) -> u8 {
             match address {
+                0x8000..=0x9FFF if self.vram_bank == 1 => self.video_ram_bank_1[address - 0x8000],
                 0x8000..=0x9FFF => self.video_ram[address - 0x8000],
                 0xFE00..=0xFEFF => {
                     if address < 0xFEA0 {
@@ -578,12 +803,8 @@ pub mod ppu {
                     0
                 }
                 0xFF40 => self.lcd_control,
-                0xFF41 => {
-                    if self.current_line == self.current_line_compare {
-                        return self.lcd_status | 1 << 2;
-                    }
-                    self.lcd_status
-                }
+                //bit 7 is unused and always reads 1
+                0xFF41 => 0x80 | self.lcd_status,
                 0xFF42 => self.scroll_y,
                 0xFF43 => self.scroll_x,
                 0xFF44 => self.current_line as u8,
@@ -593,6 +814,12 @@ pub mod ppu {
                 0xFF49 => self.obj_1_palette_data,
                 0xFF4A => self.window_y,
                 0xFF4B => self.window_x,
+                0xFF4F => 0xFE | self.vram_bank as u8,
+                0xFF68 => 0x40 | self.background_palette_index,
+                0xFF69 => self.read_palette_data(false),
+                0xFF6A => 0x40 | self.obj_palette_index,
+                0xFF6B => self.read_palette_data(true),
+                0xFF6C => 0xFE | self.object_priority_mode,
                 _ => 0,
             }
         }
@@ -600,10 +827,13 @@ pub mod ppu {
         pub(crate) fn write_byte(&mut self, address: usize, value: u8) {
             match address {
                 0x8000..=0x9FFF => {
-                    //TODO implement bank switching, this could write to bank vram 0 or 1
-                    self.video_ram[address - 0x8000] = value;
+                    if self.vram_bank == 1 {
+                        self.video_ram_bank_1[address - 0x8000] = value;
+                    } else {
+                        self.video_ram[address - 0x8000] = value;
+                    }
                     if address < 0x9800 {
-                        self.update_tile(address);
+                        self.update_tile(self.vram_bank, address);
                     }
                 }
                 0xFE00..=0xFEFF => {
@@ -612,12 +842,8 @@ pub mod ppu {
                         self.update_sprite(address - 0xFE00, value);
                     }
                 }
-                0xFF40 => {
-                    self.lcd_control = value;
-                }
-                0xFF41 => {
-                    self.lcd_status = value;
-                }
+                0xFF40 => self.set_lcdc(value),
+                0xFF41 => self.write_lcd_status(value),
                 0xFF42 => {
                     self.scroll_y = value;
                 }
@@ -625,7 +851,10 @@ pub mod ppu {
                     self.scroll_x = value;
                 }
                 0xFF44 => self.current_line = 0,
-                0xFF45 => self.current_line_compare = value as u32,
+                0xFF45 => {
+                    self.current_line_compare = value as u32;
+                    self.update_lyc_flag();
+                }
                 0xFF47 => {
                     //TODO should clear specific cache
                     self.background_palette_data = value;
@@ -639,12 +868,17 @@ pub mod ppu {
                     self.obj_1_palette_data = value;
                 }
                 0xFF4A => {
-                    //TODO check window x/y usage in PPU
                     self.window_y = value;
                 }
                 0xFF4B => {
-                    self.window_y = value;
+                    self.window_x = value;
                 }
+                0xFF4F => self.vram_bank = (value & 0x01) as usize,
+                0xFF68 => self.background_palette_index = value & 0xBF,
+                0xFF69 => self.write_palette_data(false, value),
+                0xFF6A => self.obj_palette_index = value & 0xBF,
+                0xFF6B => self.write_palette_data(true, value),
+                0xFF6C => self.object_priority_mode = value & 0x01,
                 _ => (),
             }
         }
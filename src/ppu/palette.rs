@@ -0,0 +1,213 @@
+pub mod palette {
+    use crate::ppu::compatibility::compatibility::button_palettes;
+    use serde::Deserialize;
+    use std::fs;
+    use std::path::Path;
+
+    pub(crate) type Color = [u8; 4];
+
+    //colors for shades 0-3 of each layer, selected through bgp, obp0 and obp1
+    #[derive(Debug, Clone, PartialEq)]
+    pub(crate) struct DmgPalette {
+        pub(crate) name: String,
+        pub(crate) background: [Color; 4],
+        pub(crate) obj_0: [Color; 4],
+        pub(crate) obj_1: [Color; 4],
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub(crate) enum PaletteLayer {
+        Background,
+        Obj0,
+        Obj1,
+    }
+
+    pub(crate) const fn rgb(hex: u32) -> Color {
+        [(hex >> 16) as u8, (hex >> 8) as u8, hex as u8, 255]
+    }
+
+    //the default palette, lightest first like the other presets
+    pub(crate) const POG_RED: [Color; 4] = [
+        rgb(0xFFF6D3),
+        rgb(0xF9A875),
+        rgb(0xEB6B6F),
+        rgb(0x7C3F58),
+    ];
+
+    //name, background, obj 0 and obj 1 colors
+    const PALETTE_PRESETS: [(&str, [Color; 4], [Color; 4], [Color; 4]); 6] = [
+        ("pog red", POG_RED, POG_RED, POG_RED),
+        (
+            "dmg green",
+            [rgb(0x9BBC0F), rgb(0x8BAC0F), rgb(0x306230), rgb(0x0F380F)],
+            [rgb(0x9BBC0F), rgb(0x8BAC0F), rgb(0x306230), rgb(0x0F380F)],
+            [rgb(0x9BBC0F), rgb(0x8BAC0F), rgb(0x306230), rgb(0x0F380F)],
+        ),
+        (
+            "pocket grey",
+            [rgb(0xE0DBCD), rgb(0xA89F94), rgb(0x706B66), rgb(0x2B2B26)],
+            [rgb(0xE0DBCD), rgb(0xA89F94), rgb(0x706B66), rgb(0x2B2B26)],
+            [rgb(0xE0DBCD), rgb(0xA89F94), rgb(0x706B66), rgb(0x2B2B26)],
+        ),
+        (
+            "light",
+            [rgb(0x00B581), rgb(0x009A71), rgb(0x00694A), rgb(0x004F3B)],
+            [rgb(0x00B581), rgb(0x009A71), rgb(0x00694A), rgb(0x004F3B)],
+            [rgb(0x00B581), rgb(0x009A71), rgb(0x00694A), rgb(0x004F3B)],
+        ),
+        (
+            "high contrast",
+            [rgb(0xFFFFFF), rgb(0xAAAAAA), rgb(0x555555), rgb(0x000000)],
+            [rgb(0xFFFFFF), rgb(0xAAAAAA), rgb(0x555555), rgb(0x000000)],
+            [rgb(0xFFFFFF), rgb(0xAAAAAA), rgb(0x555555), rgb(0x000000)],
+        ),
+        //greys for the background, blue and orange sprites (Okabe-Ito hues)
+        (
+            "colour blind",
+            [rgb(0xFFFFFF), rgb(0xB0B0B0), rgb(0x606060), rgb(0x000000)],
+            [rgb(0xFFFFFF), rgb(0x56B4E9), rgb(0x0072B2), rgb(0x002040)],
+            [rgb(0xFFFFFF), rgb(0xF0C060), rgb(0xE69F00), rgb(0x804000)],
+        ),
+    ];
+
+    //palettes as written in the user file, obj palettes default to the background one
+    #[derive(Deserialize)]
+    struct UserPalette {
+        name: String,
+        background: [String; 4],
+        obj_0: Option<[String; 4]>,
+        obj_1: Option<[String; 4]>,
+    }
+
+    impl Default for DmgPalette {
+        fn default() -> Self {
+            DmgPalette {
+                name: PALETTE_PRESETS[0].0.to_owned(),
+                background: POG_RED,
+                obj_0: POG_RED,
+                obj_1: POG_RED,
+            }
+        }
+    }
+
+    impl DmgPalette {
+        pub(crate) fn colors(&self, palette_layer: PaletteLayer) -> &[Color; 4] {
+            match palette_layer {
+                PaletteLayer::Background => &self.background,
+                PaletteLayer::Obj0 => &self.obj_0,
+                PaletteLayer::Obj1 => &self.obj_1,
+            }
+        }
+    }
+
+    pub(crate) fn palette_presets() -> Vec<DmgPalette> {
+        PALETTE_PRESETS
+            .iter()
+            .map(|(name, background, obj_0, obj_1)| DmgPalette {
+                name: name.to_string(),
+                background: *background,
+                obj_0: *obj_0,
+                obj_1: *obj_1,
+            })
+            .collect()
+    }
+
+    //#RRGGBB or RRGGBB
+    pub(crate) fn parse_color(value: &str) -> Result<Color, String> {
+        let hex_value = value.trim().trim_start_matches('#');
+        if hex_value.len() != 6 {
+            return Err(format!("{} should be #RRGGBB", value));
+        }
+        u32::from_str_radix(hex_value, 16)
+            .map(rgb)
+            .map_err(|_| format!("{} should be #RRGGBB", value))
+    }
+
+    fn parse_colors(values: &[String; 4]) -> Result<[Color; 4], String> {
+        let mut colors = [[0; 4]; 4];
+        for (color, value) in colors.iter_mut().zip(values.iter()) {
+            *color = parse_color(value)?;
+        }
+        Ok(colors)
+    }
+
+    //a json list of palettes, each with a name and 4 colors (lightest first) per layer
+    pub(crate) fn parse_user_palettes(content: &str) -> Result<Vec<DmgPalette>, String> {
+        let user_palettes: Vec<UserPalette> =
+            serde_json::from_str(content).map_err(|error| error.to_string())?;
+        user_palettes
+            .iter()
+            .map(|user_palette| {
+                let background = parse_colors(&user_palette.background)?;
+                Ok(DmgPalette {
+                    name: user_palette.name.clone(),
+                    background,
+                    obj_0: match &user_palette.obj_0 {
+                        Some(obj_0) => parse_colors(obj_0)?,
+                        None => background,
+                    },
+                    obj_1: match &user_palette.obj_1 {
+                        Some(obj_1) => parse_colors(obj_1)?,
+                        None => background,
+                    },
+                })
+            })
+            .collect()
+    }
+
+    pub(crate) fn load_user_palettes(path: &Path) -> Result<Vec<DmgPalette>, String> {
+        let content = fs::read_to_string(path).map_err(|error| error.to_string())?;
+        parse_user_palettes(&content)
+    }
+
+    //presets, cgb button combination palettes and user palettes, cycled with a hotkey
+    #[derive(Debug, Clone)]
+    pub(crate) struct PaletteSelector {
+        palettes: Vec<DmgPalette>,
+        current: usize,
+    }
+
+    impl PaletteSelector {
+        pub(crate) fn new(user_palettes: Vec<DmgPalette>) -> PaletteSelector {
+            let mut palettes = palette_presets();
+            palettes.extend(button_palettes());
+            palettes.extend(user_palettes);
+            PaletteSelector {
+                palettes,
+                current: 0,
+            }
+        }
+
+        pub(crate) fn current(&self) -> &DmgPalette {
+            &self.palettes[self.current]
+        }
+
+        //returns false if there is no palette with the given name
+        pub(crate) fn select(&mut self, name: &str) -> bool {
+            match self
+                .palettes
+                .iter()
+                .position(|palette| palette.name.eq_ignore_ascii_case(name))
+            {
+                Some(index) => {
+                    self.current = index;
+                    true
+                }
+                None => false,
+            }
+        }
+
+        //selects the palette with the same name, or adds it after the others
+        pub(crate) fn insert(&mut self, palette: DmgPalette) {
+            if !self.select(&palette.name) {
+                self.palettes.push(palette);
+                self.current = self.palettes.len() - 1;
+            }
+        }
+
+        pub(crate) fn next(&mut self) -> &DmgPalette {
+            self.current = (self.current + 1) % self.palettes.len();
+            self.current()
+        }
+    }
+}
@@ -0,0 +1,760 @@
+pub mod scalers {
+    use crate::ppu::palette::palette::Color;
+    use image::{Rgba, RgbaImage};
+    use serde::{Deserialize, Serialize};
+
+    const MAX_NEAREST_FACTOR: u32 = 8;
+    const MAX_XBRZ_FACTOR: u32 = 6;
+    //hq2x treats two colors as different when one of the yuv channels is further apart than this
+    const HQX_THRESHOLDS: [f32; 3] = [48.0, 7.0, 6.0];
+    //xbrz colors closer than this are equal, and a direction needs to be this many times
+    //stronger than the other one to be dominant or to make a steep or shallow line
+    const XBRZ_EQUAL_COLOR_TOLERANCE: f64 = 30.0;
+    const XBRZ_DOMINANT_DIRECTION_THRESHOLD: f64 = 3.6;
+    const XBRZ_STEEP_DIRECTION_THRESHOLD: f64 = 2.2;
+    //how much of the original brightness the overlay lines keep
+    const GRID_BRIGHTNESS: f32 = 0.75;
+    const SCANLINE_BRIGHTNESS: f32 = 0.6;
+
+    //software upscalers, the output is bigger than the emulated screen
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+    #[serde(rename_all = "snake_case")]
+    pub(crate) enum Scaler {
+        #[default]
+        None,
+        //integer scaling by the configured factor
+        Nearest,
+        //epx, corners take the color of matching neighbours
+        Scale2x,
+        Scale3x,
+        //each output pixel is interpolated by the rule the hq2x table gives
+        //for the neighbours that differ from the pixel in yuv
+        Hq2x,
+        //the same with the hq4x table, each quarter of the 4x4 block follows its corner
+        Hq4x,
+        //corners are blended along the edges the xbrz rules find, by the configured factor
+        Xbrz,
+    }
+
+    //lines drawn between the scaled pixels to look like an lcd
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+    #[serde(rename_all = "snake_case")]
+    pub(crate) enum Overlay {
+        #[default]
+        None,
+        Grid,
+        Scanlines,
+    }
+
+    impl Scaler {
+        pub(crate) fn from_name(name: &str) -> Option<Scaler> {
+            match name.to_lowercase().as_str() {
+                "none" => Some(Scaler::None),
+                "nearest" => Some(Scaler::Nearest),
+                "scale2x" => Some(Scaler::Scale2x),
+                "scale3x" => Some(Scaler::Scale3x),
+                "hq2x" => Some(Scaler::Hq2x),
+                "hq4x" => Some(Scaler::Hq4x),
+                "xbrz" => Some(Scaler::Xbrz),
+                _ => None,
+            }
+        }
+    }
+
+    impl Overlay {
+        pub(crate) fn from_name(name: &str) -> Option<Overlay> {
+            match name.to_lowercase().as_str() {
+                "none" => Some(Overlay::None),
+                "grid" => Some(Overlay::Grid),
+                "scanlines" => Some(Overlay::Scanlines),
+                _ => None,
+            }
+        }
+    }
+
+    fn to_yuv(color: Color) -> [f32; 3] {
+        let [red, green, blue] = [color[0], color[1], color[2]].map(|channel| channel as f32);
+        [
+            0.299 * red + 0.587 * green + 0.114 * blue,
+            -0.169 * red - 0.331 * green + 0.5 * blue,
+            0.5 * red - 0.419 * green - 0.081 * blue,
+        ]
+    }
+
+    fn yuv_differs(first: Color, second: Color) -> bool {
+        let (first, second) = (to_yuv(first), to_yuv(second));
+        (0..3).any(|channel| (first[channel] - second[channel]).abs() > HQX_THRESHOLDS[channel])
+    }
+
+    //the ycbcr distance xbrz compares colors and edges with
+    fn color_distance(first: Color, second: Color) -> f64 {
+        let [red, green, blue] =
+            [0, 1, 2].map(|channel| first[channel] as f64 - second[channel] as f64);
+        let (k_blue, k_red) = (0.0593, 0.2627);
+        let luma = k_red * red + (1.0 - k_blue - k_red) * green + k_blue * blue;
+        let blue_chroma = 0.5 / (1.0 - k_blue) * (blue - luma);
+        let red_chroma = 0.5 / (1.0 - k_red) * (red - luma);
+        (luma * luma + blue_chroma * blue_chroma + red_chroma * red_chroma).sqrt()
+    }
+
+    //weighted average of the colors, rounded down like the original filters
+    fn interpolate(colors: &[(Color, u32)]) -> Color {
+        let total: u32 = colors.iter().map(|(_, weight)| weight).sum();
+        let mut result = [0; 4];
+        for (channel, value) in result.iter_mut().enumerate() {
+            let sum: u32 = colors
+                .iter()
+                .map(|(color, weight)| color[channel] as u32 * weight)
+                .sum();
+            *value = (sum / total) as u8;
+        }
+        result
+    }
+
+    //where a position of a size x size square was before the square was turned clockwise
+    fn rotate((row, column): (usize, usize), size: usize, quarter_turns: usize) -> (usize, usize) {
+        (0..quarter_turns).fold((row, column), |(row, column), _| (size - 1 - column, row))
+    }
+
+    //the 5x5 pixels around the one being scaled, borders repeat the edge pixels
+    struct Neighbourhood([[Color; 5]; 5]);
+
+    impl Neighbourhood {
+        fn new(frame: &RgbaImage, x: u32, y: u32) -> Neighbourhood {
+            let (last_x, last_y) = (frame.width() as i32 - 1, frame.height() as i32 - 1);
+            let mut pixels = [[[0; 4]; 5]; 5];
+            for (row, line) in pixels.iter_mut().enumerate() {
+                for (column, pixel) in line.iter_mut().enumerate() {
+                    let pixel_x = (x as i32 + column as i32 - 2).clamp(0, last_x);
+                    let pixel_y = (y as i32 + row as i32 - 2).clamp(0, last_y);
+                    *pixel = frame.get_pixel(pixel_x as u32, pixel_y as u32).0;
+                }
+            }
+            Neighbourhood(pixels)
+        }
+
+        fn get(&self, x: i32, y: i32) -> Color {
+            self.0[(y + 2) as usize][(x + 2) as usize]
+        }
+
+        fn center(&self) -> Color {
+            self.get(0, 0)
+        }
+    }
+
+    fn scale2x(pixels: &Neighbourhood) -> Vec<Color> {
+        let (b, d, e, f, h) = (
+            pixels.get(0, -1),
+            pixels.get(-1, 0),
+            pixels.center(),
+            pixels.get(1, 0),
+            pixels.get(0, 1),
+        );
+        if b == h || d == f {
+            return vec![e; 4];
+        }
+        vec![
+            if d == b { d } else { e },
+            if b == f { f } else { e },
+            if d == h { d } else { e },
+            if h == f { f } else { e },
+        ]
+    }
+
+    fn scale3x(pixels: &Neighbourhood) -> Vec<Color> {
+        let (a, b, c) = (pixels.get(-1, -1), pixels.get(0, -1), pixels.get(1, -1));
+        let (d, e, f) = (pixels.get(-1, 0), pixels.center(), pixels.get(1, 0));
+        let (g, h, i) = (pixels.get(-1, 1), pixels.get(0, 1), pixels.get(1, 1));
+        if b == h || d == f {
+            return vec![e; 9];
+        }
+        vec![
+            if d == b { d } else { e },
+            if (d == b && e != c) || (b == f && e != a) {
+                b
+            } else {
+                e
+            },
+            if b == f { f } else { e },
+            if (d == b && e != g) || (d == h && e != a) {
+                d
+            } else {
+                e
+            },
+            e,
+            if (b == f && e != i) || (h == f && e != c) {
+                f
+            } else {
+                e
+            },
+            if d == h { d } else { e },
+            if (d == h && e != i) || (h == f && e != g) {
+                h
+            } else {
+                e
+            },
+            if h == f { f } else { e },
+        ]
+    }
+
+    //rule of the top left output pixel for each pattern of neighbours that differ from the
+    //center, bits 0 to 7 are a, b, c, d, f, g, h and i. the other output pixels use the same
+    //table on the neighbourhood rotated to bring their corner to the top left
+    #[rustfmt::skip]
+    const HQ2X_RULES: [u8; 256] = [
+        4, 4, 6,  2, 4, 4, 6,  2, 5,  3, 15, 12, 5,  3, 17, 13,
+        4, 4, 6, 18, 4, 4, 6, 18, 5,  3, 12, 12, 5,  3,  1, 12,
+        4, 4, 6,  2, 4, 4, 6,  2, 5,  3, 17, 13, 5,  3, 16, 14,
+        4, 4, 6, 18, 4, 4, 6, 18, 5,  3, 16, 12, 5,  3,  1, 14,
+        4, 4, 6,  2, 4, 4, 6,  2, 5, 19, 12, 12, 5, 19, 16, 12,
+        4, 4, 6,  2, 4, 4, 6,  2, 5,  3, 16, 12, 5,  3, 16, 12,
+        4, 4, 6,  2, 4, 4, 6,  2, 5, 19,  1, 12, 5, 19,  1, 14,
+        4, 4, 6,  2, 4, 4, 6, 18, 5,  3, 16, 12, 5, 19,  1, 14,
+        4, 4, 6,  2, 4, 4, 6,  2, 5,  3, 15, 12, 5,  3, 17, 13,
+        4, 4, 6,  2, 4, 4, 6,  2, 5,  3, 16, 12, 5,  3, 16, 12,
+        4, 4, 6,  2, 4, 4, 6,  2, 5,  3, 17, 13, 5,  3, 16, 14,
+        4, 4, 6,  2, 4, 4, 6,  2, 5,  3, 16, 13, 5,  3,  1, 14,
+        4, 4, 6,  2, 4, 4, 6,  2, 5,  3, 16, 12, 5,  3, 16, 13,
+        4, 4, 6,  2, 4, 4, 6,  2, 5,  3, 16, 12, 5,  3,  1, 12,
+        4, 4, 6,  2, 4, 4, 6,  2, 5,  3, 16, 12, 5,  3,  1, 14,
+        4, 4, 6,  2, 4, 4, 6,  2, 5,  3,  1, 12, 5,  3,  1, 14,
+    ];
+
+    //the interpolations of the original hq2x, e is the pixel being scaled
+    fn hq2x_pixel(rule: u8, [a, b, d, e, f, h]: [Color; 6]) -> Color {
+        let same = |first: Color, second: Color| !yuv_differs(first, second);
+        //the last rules pick one of the others depending on an edge
+        let rule = match rule {
+            12..=14 if !same(b, d) => 0,
+            15..=17 if !same(b, d) => 1,
+            12 | 15 => 4,
+            16 => 9,
+            13 | 17 => 10,
+            14 => 11,
+            18 if same(b, f) => 7,
+            18 => 2,
+            19 if same(d, h) => 8,
+            19 => 3,
+            rule => rule,
+        };
+        match rule {
+            1 => interpolate(&[(e, 3), (a, 1)]),
+            2 => interpolate(&[(e, 3), (d, 1)]),
+            3 => interpolate(&[(e, 3), (b, 1)]),
+            4 => interpolate(&[(e, 2), (d, 1), (b, 1)]),
+            5 => interpolate(&[(e, 2), (a, 1), (b, 1)]),
+            6 => interpolate(&[(e, 2), (a, 1), (d, 1)]),
+            7 => interpolate(&[(e, 5), (b, 2), (d, 1)]),
+            8 => interpolate(&[(e, 5), (d, 2), (b, 1)]),
+            9 => interpolate(&[(e, 6), (d, 1), (b, 1)]),
+            10 => interpolate(&[(e, 2), (d, 3), (b, 3)]),
+            11 => interpolate(&[(e, 14), (d, 1), (b, 1)]),
+            _ => e,
+        }
+    }
+
+    //the pattern of neighbours differing from the center and the a, b, d, e, f and h pixels
+    //of each corner, top left, top right, bottom left and bottom right, turned to the top left
+    fn hqx_corners(pixels: &Neighbourhood) -> [(usize, [Color; 6], usize); 4] {
+        let center = pixels.center();
+        [0, 3, 1, 2].map(|quarter_turns| {
+            let at = |row: usize, column: usize| {
+                let (row, column) = rotate((row, column), 3, quarter_turns);
+                pixels.get(column as i32 - 1, row as i32 - 1)
+            };
+            let neighbours = [
+                at(0, 0),
+                at(0, 1),
+                at(0, 2),
+                at(1, 0),
+                at(1, 2),
+                at(2, 0),
+                at(2, 1),
+                at(2, 2),
+            ];
+            let pattern = neighbours
+                .iter()
+                .enumerate()
+                .filter(|(_, neighbour)| yuv_differs(center, **neighbour))
+                .fold(0, |pattern, (bit, _)| pattern | 1 << bit);
+            let [a, b, _, d, f, _, h, _] = neighbours;
+            (pattern, [a, b, d, center, f, h], quarter_turns)
+        })
+    }
+
+    fn hq2x(pixels: &Neighbourhood) -> Vec<Color> {
+        hqx_corners(pixels)
+            .into_iter()
+            .map(|(pattern, neighbours, _)| hq2x_pixel(HQ2X_RULES[pattern], neighbours))
+            .collect()
+    }
+
+    //rule of the top left 2x2 quarter of the hq4x block, indexed like HQ2X_RULES
+    #[rustfmt::skip]
+    const HQ4X_RULES: [u8; 256] = [
+         0,  0,  2,  4,  0,  0,  2,  4,  3,  5, 14, 15,  3,  5, 16, 17,
+         0,  0,  2, 22,  0,  0,  2, 22,  3,  5, 15, 15,  3,  5,  1, 15,
+         0,  0,  2,  4,  0,  0,  2,  4,  3,  5, 18, 19,  3,  5, 20, 21,
+         0,  0,  2, 22,  0,  0,  2, 22,  3,  5, 20, 15,  3,  5,  1, 21,
+         0,  0,  2,  4,  0,  0,  2,  4,  3, 23, 15, 15,  3, 23, 20, 15,
+         0,  0,  2,  4,  0,  0,  2,  4,  3,  5, 20, 15,  3,  5, 20, 15,
+         0,  0,  2,  4,  0,  0,  2,  4,  3, 23,  1, 15,  3, 23,  1, 21,
+         0,  0,  2,  4,  0,  0,  2, 22,  3,  5, 20, 15,  3, 23,  1, 21,
+         0,  0,  2,  4,  0,  0,  2,  4,  3,  5, 14, 15,  3,  5, 16, 17,
+         0,  0,  2,  4,  0,  0,  2,  4,  3,  5, 20, 15,  3,  5, 20, 15,
+         0,  0,  2,  4,  0,  0,  2,  4,  3,  5, 18, 19,  3,  5, 20, 21,
+         0,  0,  2,  4,  0,  0,  2,  4,  3,  5, 20, 19,  3,  5,  1, 21,
+         0,  0,  2,  4,  0,  0,  2,  4,  3,  5, 20, 15,  3,  5, 20, 17,
+         0,  0,  2,  4,  0,  0,  2,  4,  3,  5, 20, 15,  3,  5,  1, 15,
+         0,  0,  2,  4,  0,  0,  2,  4,  3,  5, 20, 15,  3,  5,  1, 21,
+         0,  0,  2,  4,  0,  0,  2,  4,  3,  5,  1, 15,  3,  5,  1, 21,
+    ];
+
+    //the interpolations of the original hq4x for the top left quarter of the block,
+    //in the order top left, top right, bottom left and bottom right
+    fn hq4x_quarter(rule: u8, [a, b, d, e, f, h]: [Color; 6]) -> [Color; 4] {
+        let same = |first: Color, second: Color| !yuv_differs(first, second);
+        //the last rules pick one of the others depending on an edge
+        let rule = match rule {
+            14 | 16 | 18 | 20 if !same(b, d) => 1,
+            15 | 17 | 19 | 21 if !same(b, d) => 6,
+            14 | 15 => 7,
+            16 | 17 => 8,
+            18 | 19 => 9,
+            20 => 10,
+            21 => 11,
+            22 if same(b, f) => 12,
+            22 => 4,
+            23 if same(d, h) => 13,
+            23 => 5,
+            rule => rule,
+        };
+        match rule {
+            0 => [
+                interpolate(&[(e, 2), (b, 1), (d, 1)]),
+                interpolate(&[(e, 5), (b, 2), (d, 1)]),
+                interpolate(&[(e, 5), (d, 2), (b, 1)]),
+                interpolate(&[(e, 6), (b, 1), (d, 1)]),
+            ],
+            1 => [
+                interpolate(&[(e, 5), (a, 3)]),
+                interpolate(&[(e, 3), (a, 1)]),
+                interpolate(&[(e, 3), (a, 1)]),
+                interpolate(&[(e, 7), (a, 1)]),
+            ],
+            2 => [
+                interpolate(&[(e, 5), (a, 3)]),
+                interpolate(&[(e, 3), (a, 1)]),
+                interpolate(&[(e, 5), (d, 2), (a, 1)]),
+                interpolate(&[(e, 7), (a, 1)]),
+            ],
+            3 => [
+                interpolate(&[(e, 5), (a, 3)]),
+                interpolate(&[(e, 5), (b, 2), (a, 1)]),
+                interpolate(&[(e, 3), (a, 1)]),
+                interpolate(&[(e, 7), (a, 1)]),
+            ],
+            4 => [
+                interpolate(&[(e, 5), (d, 3)]),
+                interpolate(&[(e, 7), (d, 1)]),
+                interpolate(&[(e, 5), (d, 3)]),
+                interpolate(&[(e, 7), (d, 1)]),
+            ],
+            5 => [
+                interpolate(&[(e, 5), (b, 3)]),
+                interpolate(&[(e, 5), (b, 3)]),
+                interpolate(&[(e, 7), (b, 1)]),
+                interpolate(&[(e, 7), (b, 1)]),
+            ],
+            7 => [
+                interpolate(&[(b, 1), (d, 1)]),
+                interpolate(&[(e, 1), (b, 1)]),
+                interpolate(&[(e, 1), (d, 1)]),
+                e,
+            ],
+            8 => [
+                interpolate(&[(b, 1), (d, 1)]),
+                interpolate(&[(b, 5), (d, 3)]),
+                interpolate(&[(e, 1), (d, 2), (b, 1)]),
+                interpolate(&[(e, 6), (b, 1), (d, 1)]),
+            ],
+            9 => [
+                interpolate(&[(b, 1), (d, 1)]),
+                interpolate(&[(e, 1), (b, 2), (d, 1)]),
+                interpolate(&[(d, 5), (b, 3)]),
+                interpolate(&[(e, 6), (b, 1), (d, 1)]),
+            ],
+            10 => [
+                interpolate(&[(e, 2), (b, 1), (d, 1)]),
+                interpolate(&[(e, 3), (b, 1)]),
+                interpolate(&[(e, 3), (d, 1)]),
+                e,
+            ],
+            11 => [interpolate(&[(e, 2), (b, 1), (d, 1)]), e, e, e],
+            12 => [
+                interpolate(&[(e, 3), (b, 1)]),
+                interpolate(&[(e, 1), (b, 3)]),
+                interpolate(&[(e, 5), (d, 3)]),
+                interpolate(&[(e, 7), (d, 1)]),
+            ],
+            13 => [
+                interpolate(&[(e, 3), (d, 1)]),
+                interpolate(&[(e, 5), (b, 3)]),
+                interpolate(&[(e, 1), (d, 3)]),
+                interpolate(&[(e, 7), (b, 1)]),
+            ],
+            _ => [e; 4],
+        }
+    }
+
+    //each quarter of the 4x4 block is computed turned to the top left and turned back
+    fn hq4x(pixels: &Neighbourhood) -> Vec<Color> {
+        let mut block = vec![pixels.center(); 16];
+        for (pattern, neighbours, quarter_turns) in hqx_corners(pixels) {
+            let quarter = hq4x_quarter(HQ4X_RULES[pattern], neighbours);
+            for (index, color) in quarter.into_iter().enumerate() {
+                let (row, column) = rotate((index / 2, index % 2), 4, quarter_turns);
+                block[row * 4 + column] = color;
+            }
+        }
+        block
+    }
+
+    //how strongly xbrz wants to blend a corner of a pixel
+    #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+    enum Blend {
+        None,
+        Normal,
+        Dominant,
+    }
+
+    //output pixels changed by an xbrz blend as row, column and the weight of the new color,
+    //in the frame where the corner being blended is the bottom right one
+    type BlendSteps = &'static [(usize, usize, u32, u32)];
+
+    //shallow lines, lines both steep and shallow, diagonal lines and rounded corners.
+    //steep lines are the shallow ones with rows and columns swapped
+    const XBRZ_BLENDS: [[BlendSteps; 4]; 5] = [
+        [
+            &[(1, 0, 1, 4), (1, 1, 3, 4)],
+            &[(1, 0, 1, 4), (0, 1, 1, 4), (1, 1, 5, 6)],
+            &[(1, 1, 1, 2)],
+            &[(1, 1, 21, 100)],
+        ],
+        [
+            &[(2, 0, 1, 4), (1, 2, 1, 4), (2, 1, 3, 4), (2, 2, 1, 1)],
+            &[
+                (2, 0, 1, 4),
+                (0, 2, 1, 4),
+                (2, 1, 3, 4),
+                (1, 2, 3, 4),
+                (2, 2, 1, 1),
+            ],
+            &[(1, 2, 1, 8), (2, 1, 1, 8), (2, 2, 7, 8)],
+            &[(2, 2, 45, 100)],
+        ],
+        [
+            &[
+                (3, 0, 1, 4),
+                (2, 2, 1, 4),
+                (3, 1, 3, 4),
+                (2, 3, 3, 4),
+                (3, 2, 1, 1),
+                (3, 3, 1, 1),
+            ],
+            &[
+                (3, 1, 3, 4),
+                (1, 3, 3, 4),
+                (3, 0, 1, 4),
+                (0, 3, 1, 4),
+                (2, 2, 1, 3),
+                (3, 3, 1, 1),
+                (3, 2, 1, 1),
+                (2, 3, 1, 1),
+            ],
+            &[(3, 2, 1, 2), (2, 3, 1, 2), (3, 3, 1, 1)],
+            &[(3, 3, 68, 100), (3, 2, 9, 100), (2, 3, 9, 100)],
+        ],
+        [
+            &[
+                (4, 0, 1, 4),
+                (3, 2, 1, 4),
+                (2, 4, 1, 4),
+                (4, 1, 3, 4),
+                (3, 3, 3, 4),
+                (4, 2, 1, 1),
+                (4, 3, 1, 1),
+                (4, 4, 1, 1),
+                (3, 4, 1, 1),
+            ],
+            &[
+                (0, 4, 1, 4),
+                (2, 3, 1, 4),
+                (1, 4, 3, 4),
+                (4, 0, 1, 4),
+                (3, 2, 1, 4),
+                (4, 1, 3, 4),
+                (3, 3, 2, 3),
+                (2, 4, 1, 1),
+                (3, 4, 1, 1),
+                (4, 4, 1, 1),
+                (4, 2, 1, 1),
+                (4, 3, 1, 1),
+            ],
+            &[
+                (4, 2, 1, 8),
+                (3, 3, 1, 8),
+                (2, 4, 1, 8),
+                (4, 3, 7, 8),
+                (3, 4, 7, 8),
+                (4, 4, 1, 1),
+            ],
+            &[(4, 4, 86, 100), (4, 3, 23, 100), (3, 4, 23, 100)],
+        ],
+        [
+            &[
+                (5, 0, 1, 4),
+                (4, 2, 1, 4),
+                (3, 4, 1, 4),
+                (5, 1, 3, 4),
+                (4, 3, 3, 4),
+                (3, 5, 3, 4),
+                (5, 2, 1, 1),
+                (5, 3, 1, 1),
+                (5, 4, 1, 1),
+                (5, 5, 1, 1),
+                (4, 4, 1, 1),
+                (4, 5, 1, 1),
+            ],
+            &[
+                (0, 5, 1, 4),
+                (2, 4, 1, 4),
+                (1, 5, 3, 4),
+                (3, 4, 3, 4),
+                (5, 0, 1, 4),
+                (4, 2, 1, 4),
+                (5, 1, 3, 4),
+                (4, 3, 3, 4),
+                (2, 5, 1, 1),
+                (3, 5, 1, 1),
+                (4, 5, 1, 1),
+                (5, 5, 1, 1),
+                (4, 4, 1, 1),
+                (5, 4, 1, 1),
+                (5, 2, 1, 1),
+                (5, 3, 1, 1),
+            ],
+            &[
+                (5, 3, 1, 2),
+                (4, 4, 1, 2),
+                (3, 5, 1, 2),
+                (4, 5, 1, 1),
+                (5, 5, 1, 1),
+                (5, 4, 1, 1),
+            ],
+            &[
+                (5, 5, 97, 100),
+                (4, 5, 42, 100),
+                (5, 4, 42, 100),
+                (5, 3, 6, 100),
+                (3, 5, 6, 100),
+            ],
+        ],
+    ];
+
+    //blend types of the 4 pixels f, g, j and k of the 2x2 block at x, y, found by comparing
+    //the gradients along both diagonals of the 4x4 pixels around it
+    fn xbrz_block_blends(pixels: &Neighbourhood, x: i32, y: i32) -> [Blend; 4] {
+        let at = |column: i32, row: i32| pixels.get(x + column - 1, y + row - 1);
+        let (b, c) = (at(1, 0), at(2, 0));
+        let (e, f, g, h) = (at(0, 1), at(1, 1), at(2, 1), at(3, 1));
+        let (i, j, k, l) = (at(0, 2), at(1, 2), at(2, 2), at(3, 2));
+        let (n, o) = (at(1, 3), at(2, 3));
+        let mut blends = [Blend::None; 4];
+        if (f == g && j == k) || (f == j && g == k) {
+            return blends;
+        }
+
+        let distance = color_distance;
+        let jg = distance(i, f)
+            + distance(f, c)
+            + distance(n, k)
+            + distance(k, h)
+            + 4.0 * distance(j, g);
+        let fk = distance(e, j)
+            + distance(j, o)
+            + distance(b, g)
+            + distance(g, l)
+            + 4.0 * distance(f, k);
+        let strength = |weaker: f64, stronger: f64| {
+            if XBRZ_DOMINANT_DIRECTION_THRESHOLD * weaker < stronger {
+                Blend::Dominant
+            } else {
+                Blend::Normal
+            }
+        };
+        if jg < fk {
+            if f != g && f != j {
+                blends[0] = strength(jg, fk);
+            }
+            if k != j && k != g {
+                blends[3] = strength(jg, fk);
+            }
+        } else if fk < jg {
+            if j != f && j != k {
+                blends[2] = strength(fk, jg);
+            }
+            if g != f && g != k {
+                blends[1] = strength(fk, jg);
+            }
+        }
+        blends
+    }
+
+    fn xbrz(pixels: &Neighbourhood, factor: u32) -> Vec<Color> {
+        let factor = factor as usize;
+        let center = pixels.center();
+        let mut block = vec![center; factor * factor];
+        //top left, top right, bottom right and bottom left corners, each one comes from
+        //the block where the pixel is k, j, f and g
+        let corners = [
+            xbrz_block_blends(pixels, -1, -1)[3],
+            xbrz_block_blends(pixels, 0, -1)[2],
+            xbrz_block_blends(pixels, 0, 0)[0],
+            xbrz_block_blends(pixels, -1, 0)[1],
+        ];
+        if corners.iter().all(|corner| *corner == Blend::None) {
+            return block;
+        }
+
+        let eq = |first: Color, second: Color| {
+            color_distance(first, second) < XBRZ_EQUAL_COLOR_TOLERANCE
+        };
+        let [shallow, steep_and_shallow, diagonal, corner] = XBRZ_BLENDS[factor - 2];
+        //each corner is turned to the bottom right, the kernel and the block turn with it
+        for quarter_turns in 0..4 {
+            let corner_blend = |corner: usize| corners[(corner + 4 - quarter_turns) % 4];
+            if corner_blend(2) == Blend::None {
+                continue;
+            }
+            let at = |row: usize, column: usize| {
+                let (row, column) = rotate((row, column), 3, quarter_turns);
+                pixels.get(column as i32 - 1, row as i32 - 1)
+            };
+            let (b, c) = (at(0, 1), at(0, 2));
+            let (d, e, f) = (at(1, 0), at(1, 1), at(1, 2));
+            let (g, h, i) = (at(2, 0), at(2, 1), at(2, 2));
+
+            //no line through a pixel already blended on an adjacent corner,
+            //and only the corner of l shapes
+            let line_blend = corner_blend(2) == Blend::Dominant
+                || !((corner_blend(1) != Blend::None && !eq(e, g))
+                    || (corner_blend(3) != Blend::None && !eq(e, c))
+                    || (!eq(e, i) && eq(g, h) && eq(h, i) && eq(i, f) && eq(f, c)));
+            let color = if color_distance(e, f) <= color_distance(e, h) {
+                f
+            } else {
+                h
+            };
+            let (steps, transposed) = if line_blend {
+                let (fg, hc) = (color_distance(f, g), color_distance(h, c));
+                let shallow_line = XBRZ_STEEP_DIRECTION_THRESHOLD * fg <= hc && e != g && d != g;
+                let steep_line = XBRZ_STEEP_DIRECTION_THRESHOLD * hc <= fg && e != c && b != c;
+                match (shallow_line, steep_line) {
+                    (true, true) => (steep_and_shallow, false),
+                    (true, false) => (shallow, false),
+                    (false, true) => (shallow, true),
+                    (false, false) => (diagonal, false),
+                }
+            } else {
+                (corner, false)
+            };
+            for &(row, column, numerator, denominator) in steps {
+                let (row, column) = if transposed {
+                    (column, row)
+                } else {
+                    (row, column)
+                };
+                let (row, column) = rotate((row, column), factor, quarter_turns);
+                let pixel = &mut block[row * factor + column];
+                *pixel = interpolate(&[(color, numerator), (*pixel, denominator - numerator)]);
+            }
+        }
+        block
+    }
+
+    //scales frames and draws the overlay on top
+    #[derive(Debug, Clone, Copy)]
+    pub(crate) struct Upscaler {
+        scaler: Scaler,
+        factor: u32,
+        overlay: Overlay,
+    }
+
+    impl Upscaler {
+        //scale is only used by nearest and xbrz, the other scalers have a fixed factor.
+        //an overlay needs room between the pixels, so it scales by at least 2
+        pub(crate) fn new(scaler: Scaler, scale: u32, overlay: Overlay) -> Upscaler {
+            let minimum_factor = if overlay == Overlay::None { 1 } else { 2 };
+            let factor = match scaler {
+                Scaler::None => 1,
+                Scaler::Nearest => scale.clamp(1, MAX_NEAREST_FACTOR),
+                Scaler::Scale2x | Scaler::Hq2x => 2,
+                Scaler::Scale3x => 3,
+                Scaler::Hq4x => 4,
+                Scaler::Xbrz => scale.clamp(2, MAX_XBRZ_FACTOR),
+            }
+            .max(minimum_factor);
+            Upscaler {
+                scaler,
+                factor,
+                overlay,
+            }
+        }
+
+        pub(crate) fn apply(&self, frame: &RgbaImage) -> RgbaImage {
+            if self.factor == 1 {
+                return frame.clone();
+            }
+            let factor = self.factor;
+            let mut output = RgbaImage::new(frame.width() * factor, frame.height() * factor);
+            for y in 0..frame.height() {
+                for x in 0..frame.width() {
+                    let block = match self.scaler {
+                        Scaler::Scale2x => scale2x(&Neighbourhood::new(frame, x, y)),
+                        Scaler::Scale3x => scale3x(&Neighbourhood::new(frame, x, y)),
+                        Scaler::Hq2x => hq2x(&Neighbourhood::new(frame, x, y)),
+                        Scaler::Hq4x => hq4x(&Neighbourhood::new(frame, x, y)),
+                        Scaler::Xbrz => xbrz(&Neighbourhood::new(frame, x, y), factor),
+                        Scaler::None | Scaler::Nearest => {
+                            vec![frame.get_pixel(x, y).0; (factor * factor) as usize]
+                        }
+                    };
+                    for (index, color) in block.into_iter().enumerate() {
+                        let (block_x, block_y) = (index as u32 % factor, index as u32 / factor);
+                        output.put_pixel(x * factor + block_x, y * factor + block_y, Rgba(color));
+                    }
+                }
+            }
+            self.draw_overlay(&mut output);
+            output
+        }
+
+        //the last column and row of each scaled pixel are darkened
+        fn draw_overlay(&self, output: &mut RgbaImage) {
+            let factor = self.factor;
+            for (x, y, pixel) in output.enumerate_pixels_mut() {
+                let last_row = y % factor == factor - 1;
+                let last_column = x % factor == factor - 1;
+                let brightness = match self.overlay {
+                    Overlay::Grid if last_row || last_column => GRID_BRIGHTNESS,
+                    Overlay::Scanlines if last_row => SCANLINE_BRIGHTNESS,
+                    _ => continue,
+                };
+                for channel in pixel.0.iter_mut().take(3) {
+                    *channel = (*channel as f32 * brightness).round() as u8;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,526 @@
+pub mod sgb {
+    use crate::ppu::cgb::cgb::rgb555_to_color;
+    use crate::ppu::palette::palette::Color;
+    use crate::ppu::ppu::{LCDCFlags, PPU};
+    use image::{Rgba, RgbaImage};
+
+    pub(crate) const SGB_SCREEN_WIDTH: u32 = 256;
+    pub(crate) const SGB_SCREEN_HEIGHT: u32 = 224;
+    //the game screen is centered inside the border
+    const GAME_SCREEN_X: u32 = 48;
+    const GAME_SCREEN_Y: u32 = 40;
+    const GAME_SCREEN_WIDTH: u32 = 160;
+    const GAME_SCREEN_HEIGHT: u32 = 144;
+
+    //128 bits followed by a stop bit
+    const PACKET_SIZE: usize = 16;
+    const PACKET_BITS: usize = PACKET_SIZE * 8;
+    //vram transfers send the tile data of the first 256 background tiles on screen
+    const VRAM_TRANSFER_SIZE: usize = 0x1000;
+    const TRANSFER_TILES: usize = VRAM_TRANSFER_SIZE / 16;
+
+    //the screen is colored in 8x8 cells
+    const ATTRIBUTE_COLUMNS: usize = 20;
+    const ATTRIBUTE_ROWS: usize = 18;
+    const ATTRIBUTE_CELLS: usize = ATTRIBUTE_COLUMNS * ATTRIBUTE_ROWS;
+    const ATTRIBUTE_FILES: usize = 45;
+    const ATTRIBUTE_FILE_SIZE: usize = ATTRIBUTE_CELLS / 4;
+    const SYSTEM_PALETTES: usize = 512;
+
+    const BORDER_COLUMNS: usize = 32;
+    const BORDER_ROWS: usize = 28;
+    const BORDER_TILES: usize = 256;
+    const BORDER_PALETTES_OFFSET: usize = 0x800;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub(crate) enum SgbCommand {
+        Pal01 = 0x00,
+        Pal23 = 0x01,
+        Pal03 = 0x02,
+        Pal12 = 0x03,
+        AttrBlk = 0x04,
+        AttrLin = 0x05,
+        AttrDiv = 0x06,
+        AttrChr = 0x07,
+        PalSet = 0x0A,
+        PalTrn = 0x0B,
+        MltReq = 0x11,
+        ChrTrn = 0x13,
+        PctTrn = 0x14,
+        AttrTrn = 0x15,
+        AttrSet = 0x16,
+        MaskEn = 0x17,
+    }
+
+    //what the sgb shows instead of the game screen, used by games while they upload data
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub(crate) enum ScreenMask {
+        Cancel,
+        Freeze,
+        Black,
+        Color0,
+    }
+
+    impl SgbCommand {
+        fn from_code(code: u8) -> Option<SgbCommand> {
+            match code {
+                0x00 => Some(SgbCommand::Pal01),
+                0x01 => Some(SgbCommand::Pal23),
+                0x02 => Some(SgbCommand::Pal03),
+                0x03 => Some(SgbCommand::Pal12),
+                0x04 => Some(SgbCommand::AttrBlk),
+                0x05 => Some(SgbCommand::AttrLin),
+                0x06 => Some(SgbCommand::AttrDiv),
+                0x07 => Some(SgbCommand::AttrChr),
+                0x0A => Some(SgbCommand::PalSet),
+                0x0B => Some(SgbCommand::PalTrn),
+                0x11 => Some(SgbCommand::MltReq),
+                0x13 => Some(SgbCommand::ChrTrn),
+                0x14 => Some(SgbCommand::PctTrn),
+                0x15 => Some(SgbCommand::AttrTrn),
+                0x16 => Some(SgbCommand::AttrSet),
+                0x17 => Some(SgbCommand::MaskEn),
+                _ => None,
+            }
+        }
+    }
+
+    fn read_color(data: &[u8], offset: usize) -> Color {
+        rgb555_to_color(u16::from_le_bytes([data[offset], data[offset + 1]]))
+    }
+
+    #[derive(Debug, Clone)]
+    pub(crate) struct Sgb {
+        //bits of the packet being received, None while waiting for a reset pulse
+        packet_bits: Option<usize>,
+        packet: [u8; PACKET_SIZE],
+        previous_joypad_write: u8,
+        //packets of a command longer than one packet
+        command_data: Vec<u8>,
+        //transfers read vram once the next frame has been drawn
+        pending_transfer: Option<(SgbCommand, u8)>,
+        //color 0 is shared by every palette
+        pub(crate) palettes: [[Color; 4]; 4],
+        system_palettes: Vec<[Color; 4]>,
+        //palette (0-3) of each 8x8 cell of the screen
+        pub(crate) attribute_map: [u8; ATTRIBUTE_CELLS],
+        attribute_files: Vec<[u8; ATTRIBUTE_CELLS]>,
+        //4bpp snes tiles, map entries and the 4 border palettes of 16 colors
+        border_tiles: Vec<[[u8; 8]; 8]>,
+        border_map: Vec<u16>,
+        border_palettes: [[Color; 16]; 4],
+        pub(crate) mask: ScreenMask,
+        game_screen: RgbaImage,
+        pub(crate) players: u8,
+        pub(crate) current_player: u8,
+    }
+
+    impl Sgb {
+        //until the game sends its own palettes the dmg ones are used
+        pub(crate) fn new(colors: &[Color; 4]) -> Sgb {
+            Sgb {
+                packet_bits: None,
+                packet: [0; PACKET_SIZE],
+                previous_joypad_write: 0x30,
+                command_data: Vec::with_capacity(PACKET_SIZE * 7),
+                pending_transfer: None,
+                palettes: [*colors; 4],
+                system_palettes: vec![[[0, 0, 0, 255]; 4]; SYSTEM_PALETTES],
+                attribute_map: [0; ATTRIBUTE_CELLS],
+                attribute_files: vec![[0; ATTRIBUTE_CELLS]; ATTRIBUTE_FILES],
+                border_tiles: vec![[[0; 8]; 8]; BORDER_TILES],
+                border_map: vec![0; BORDER_COLUMNS * BORDER_ROWS],
+                border_palettes: [[[0, 0, 0, 255]; 16]; 4],
+                mask: ScreenMask::Cancel,
+                game_screen: RgbaImage::new(GAME_SCREEN_WIDTH, GAME_SCREEN_HEIGHT),
+                players: 1,
+                current_player: 0,
+            }
+        }
+
+        //packets are sent a bit at a time through p14/p15: both low resets the transfer,
+        //p14 low sends a 0 and p15 low a 1, every pulse is followed by both lines high
+        pub(crate) fn write_joypad(&mut self, value: u8) {
+            let lines = value & 0x30;
+            let previous_lines = self.previous_joypad_write;
+            self.previous_joypad_write = lines;
+
+            if lines == 0x00 {
+                self.packet_bits = Some(0);
+                self.packet = [0; PACKET_SIZE];
+                return;
+            }
+            if lines == 0x30 {
+                //with multiplayer on, the next joypad is selected when p15 goes back high
+                if self.packet_bits.is_none() && previous_lines == 0x10 && self.players > 1 {
+                    self.current_player = (self.current_player + 1) % self.players;
+                }
+                return;
+            }
+            if previous_lines != 0x30 {
+                return;
+            }
+            let bits = match self.packet_bits {
+                Some(bits) => bits,
+                None => return,
+            };
+            let bit = (lines == 0x10) as u8;
+            if bits < PACKET_BITS {
+                self.packet[bits / 8] |= bit << (bits % 8);
+                self.packet_bits = Some(bits + 1);
+            } else {
+                //stop bit
+                self.packet_bits = None;
+                self.receive_packet();
+            }
+        }
+
+        fn receive_packet(&mut self) {
+            if self.command_data.is_empty() && self.packet[0] & 0x07 == 0 {
+                return;
+            }
+            self.command_data.extend_from_slice(&self.packet);
+            let packets = (self.command_data[0] & 0x07) as usize;
+            if self.command_data.len() >= packets * PACKET_SIZE {
+                let command_data = std::mem::take(&mut self.command_data);
+                if let Some(command) = SgbCommand::from_code(command_data[0] >> 3) {
+                    self.execute(command, &command_data);
+                }
+            }
+        }
+
+        fn execute(&mut self, command: SgbCommand, data: &[u8]) {
+            match command {
+                SgbCommand::Pal01 => self.set_palette_pair(data, 0, 1),
+                SgbCommand::Pal23 => self.set_palette_pair(data, 2, 3),
+                SgbCommand::Pal03 => self.set_palette_pair(data, 0, 3),
+                SgbCommand::Pal12 => self.set_palette_pair(data, 1, 2),
+                SgbCommand::AttrBlk => self.attribute_blocks(data),
+                SgbCommand::AttrLin => self.attribute_lines(data),
+                SgbCommand::AttrDiv => self.attribute_divide(data),
+                SgbCommand::AttrChr => self.attribute_cells(data),
+                SgbCommand::PalSet => {
+                    for palette in 0..4 {
+                        let system_palette =
+                            u16::from_le_bytes([data[1 + palette * 2], data[2 + palette * 2]]);
+                        self.palettes[palette] =
+                            self.system_palettes[system_palette as usize % SYSTEM_PALETTES];
+                    }
+                    self.share_color_0(self.palettes[0][0]);
+                    //bit 7 applies the attribute file and bit 6 cancels the mask
+                    if data[9] & 0x80 != 0 {
+                        self.apply_attribute_file(data[9]);
+                    }
+                    if data[9] & 0x40 != 0 {
+                        self.mask = ScreenMask::Cancel;
+                    }
+                }
+                SgbCommand::AttrSet => {
+                    self.apply_attribute_file(data[1]);
+                    if data[1] & 0x40 != 0 {
+                        self.mask = ScreenMask::Cancel;
+                    }
+                }
+                SgbCommand::MltReq => {
+                    self.players = match data[1] & 0x03 {
+                        1 => 2,
+                        3 => 4,
+                        _ => 1,
+                    };
+                    self.current_player = 0;
+                }
+                SgbCommand::MaskEn => {
+                    self.mask = match data[1] & 0x03 {
+                        1 => ScreenMask::Freeze,
+                        2 => ScreenMask::Black,
+                        3 => ScreenMask::Color0,
+                        _ => ScreenMask::Cancel,
+                    }
+                }
+                SgbCommand::PalTrn
+                | SgbCommand::ChrTrn
+                | SgbCommand::PctTrn
+                | SgbCommand::AttrTrn => self.pending_transfer = Some((command, data[1])),
+            }
+        }
+
+        fn share_color_0(&mut self, color: Color) {
+            for palette in self.palettes.iter_mut() {
+                palette[0] = color;
+            }
+        }
+
+        //color 0 followed by colors 1-3 of both palettes
+        fn set_palette_pair(&mut self, data: &[u8], first: usize, second: usize) {
+            self.share_color_0(read_color(data, 1));
+            for color in 0..3 {
+                self.palettes[first][color + 1] = read_color(data, 3 + color * 2);
+                self.palettes[second][color + 1] = read_color(data, 9 + color * 2);
+            }
+        }
+
+        //bits 0-5 pick the attribute file
+        fn apply_attribute_file(&mut self, file: u8) {
+            if let Some(attributes) = self.attribute_files.get((file & 0x3F) as usize) {
+                self.attribute_map = *attributes;
+            }
+        }
+
+        fn set_attribute(&mut self, x: usize, y: usize, palette: u8) {
+            if x < ATTRIBUTE_COLUMNS && y < ATTRIBUTE_ROWS {
+                self.attribute_map[y * ATTRIBUTE_COLUMNS + x] = palette & 0x03;
+            }
+        }
+
+        //rectangles with a palette for the cells inside, on and outside their border
+        fn attribute_blocks(&mut self, data: &[u8]) {
+            let blocks = (data[1] & 0x1F) as usize;
+            for block in data[2..].chunks_exact(6).take(blocks) {
+                let control = block[0] & 0x07;
+                let (inside_palette, border_palette, outside_palette) = (
+                    block[1] & 0x03,
+                    (block[1] >> 2) & 0x03,
+                    (block[1] >> 4) & 0x03,
+                );
+                //when only the inside or the outside is changed the border follows it
+                let (inside, border, outside) = match control {
+                    0x01 => (Some(inside_palette), Some(inside_palette), None),
+                    0x04 => (None, Some(outside_palette), Some(outside_palette)),
+                    _ => (
+                        (control & 0x01 != 0).then_some(inside_palette),
+                        (control & 0x02 != 0).then_some(border_palette),
+                        (control & 0x04 != 0).then_some(outside_palette),
+                    ),
+                };
+                let (x1, y1, x2, y2) = (
+                    (block[2] & 0x1F) as usize,
+                    (block[3] & 0x1F) as usize,
+                    (block[4] & 0x1F) as usize,
+                    (block[5] & 0x1F) as usize,
+                );
+                for y in 0..ATTRIBUTE_ROWS {
+                    for x in 0..ATTRIBUTE_COLUMNS {
+                        let palette = if x > x1 && x < x2 && y > y1 && y < y2 {
+                            inside
+                        } else if x >= x1 && x <= x2 && y >= y1 && y <= y2 {
+                            border
+                        } else {
+                            outside
+                        };
+                        if let Some(palette) = palette {
+                            self.set_attribute(x, y, palette);
+                        }
+                    }
+                }
+            }
+        }
+
+        //bits 0-4 are the line, 5-6 the palette, bit 7 set for rows and clear for columns
+        fn attribute_lines(&mut self, data: &[u8]) {
+            let lines = data[1] as usize;
+            for line in data[2..].iter().take(lines) {
+                let (position, palette) = ((line & 0x1F) as usize, (line >> 5) & 0x03);
+                if line & 0x80 != 0 {
+                    for x in 0..ATTRIBUTE_COLUMNS {
+                        self.set_attribute(x, position, palette);
+                    }
+                } else {
+                    for y in 0..ATTRIBUTE_ROWS {
+                        self.set_attribute(position, y, palette);
+                    }
+                }
+            }
+        }
+
+        //splits the screen in two at a row (bit 6 set) or column, the line itself has its own palette
+        fn attribute_divide(&mut self, data: &[u8]) {
+            let (after_palette, before_palette, line_palette) =
+                (data[1] & 0x03, (data[1] >> 2) & 0x03, (data[1] >> 4) & 0x03);
+            let by_row = data[1] & 0x40 != 0;
+            let line = (data[2] & 0x1F) as usize;
+            for y in 0..ATTRIBUTE_ROWS {
+                for x in 0..ATTRIBUTE_COLUMNS {
+                    let position = if by_row { y } else { x };
+                    let palette = match position {
+                        position if position < line => before_palette,
+                        position if position == line => line_palette,
+                        _ => after_palette,
+                    };
+                    self.set_attribute(x, y, palette);
+                }
+            }
+        }
+
+        //a palette for each cell starting from x, y, packed 4 per byte (msb first)
+        fn attribute_cells(&mut self, data: &[u8]) {
+            let (mut x, mut y) = (data[1] as usize, data[2] as usize);
+            let cells = (u16::from_le_bytes([data[3], data[4]]) as usize).min(ATTRIBUTE_CELLS);
+            let top_to_bottom = data[5] & 0x01 != 0;
+            for cell in 0..cells {
+                let packed = match data.get(6 + cell / 4) {
+                    Some(packed) => *packed,
+                    None => break,
+                };
+                if x >= ATTRIBUTE_COLUMNS || y >= ATTRIBUTE_ROWS {
+                    break;
+                }
+                self.set_attribute(x, y, packed >> (6 - (cell % 4) * 2));
+                if top_to_bottom {
+                    y += 1;
+                    if y == ATTRIBUTE_ROWS {
+                        y = 0;
+                        x += 1;
+                    }
+                } else {
+                    x += 1;
+                    if x == ATTRIBUTE_COLUMNS {
+                        x = 0;
+                        y += 1;
+                    }
+                }
+            }
+        }
+
+        //the data is the tile data of the first 256 background tiles, 20 tiles per row
+        fn read_transfer_data(ppu: &PPU) -> Vec<u8> {
+            let map_address = if ppu.get_lcdc_value(LCDCFlags::BG_tile_map_area) {
+                0x1C00
+            } else {
+                0x1800
+            };
+            let mut data = Vec::with_capacity(VRAM_TRANSFER_SIZE);
+            for tile in 0..TRANSFER_TILES {
+                let map_offset = (tile / ATTRIBUTE_COLUMNS) * 32 + tile % ATTRIBUTE_COLUMNS;
+                let tile_number = ppu.video_ram[map_address + map_offset];
+                let tile_address = if ppu.get_lcdc_value(LCDCFlags::BG_tile_set_area) {
+                    tile_number as usize * 16
+                } else {
+                    (0x1000 + (tile_number as i8 as i32) * 16) as usize
+                };
+                data.extend_from_slice(&ppu.video_ram[tile_address..tile_address + 16]);
+            }
+            data
+        }
+
+        //transfers are done once the frame with the data on screen has been drawn
+        fn run_pending_transfer(&mut self, ppu: &PPU) {
+            let (command, argument) = match self.pending_transfer.take() {
+                Some(pending_transfer) => pending_transfer,
+                None => return,
+            };
+            let data = Sgb::read_transfer_data(ppu);
+            match command {
+                SgbCommand::PalTrn => {
+                    for (palette, colors) in self.system_palettes.iter_mut().enumerate() {
+                        for (color, value) in colors.iter_mut().enumerate() {
+                            *value = read_color(&data, palette * 8 + color * 2);
+                        }
+                    }
+                }
+                SgbCommand::ChrTrn => {
+                    //snes 4bpp tiles, planes 0/1 interleaved in the first 16 bytes, 2/3 in the rest
+                    let first_tile = (argument as usize & 0x01) * 128;
+                    for (index, tile_data) in data.chunks_exact(32).enumerate() {
+                        let tile = &mut self.border_tiles[first_tile + index];
+                        for (row, pixels) in tile.iter_mut().enumerate() {
+                            let planes = [
+                                tile_data[row * 2],
+                                tile_data[row * 2 + 1],
+                                tile_data[16 + row * 2],
+                                tile_data[16 + row * 2 + 1],
+                            ];
+                            for (x, pixel) in pixels.iter_mut().enumerate() {
+                                *pixel = planes
+                                    .iter()
+                                    .enumerate()
+                                    .map(|(plane, value)| ((value >> (7 - x)) & 0x01) << plane)
+                                    .sum();
+                            }
+                        }
+                    }
+                }
+                SgbCommand::PctTrn => {
+                    for (index, entry) in self.border_map.iter_mut().enumerate() {
+                        *entry = u16::from_le_bytes([data[index * 2], data[index * 2 + 1]]);
+                    }
+                    for (palette, colors) in self.border_palettes.iter_mut().enumerate() {
+                        for (color, value) in colors.iter_mut().enumerate() {
+                            let offset = BORDER_PALETTES_OFFSET + palette * 32 + color * 2;
+                            *value = read_color(&data, offset);
+                        }
+                    }
+                }
+                SgbCommand::AttrTrn => {
+                    for (file, attributes) in self.attribute_files.iter_mut().enumerate() {
+                        let file_data = &data[file * ATTRIBUTE_FILE_SIZE..][..ATTRIBUTE_FILE_SIZE];
+                        for (cell, attribute) in attributes.iter_mut().enumerate() {
+                            *attribute = (file_data[cell / 4] >> (6 - (cell % 4) * 2)) & 0x03;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        //the border with the game screen in the middle, colored with the sgb palettes
+        pub(crate) fn compose_frame(&mut self, ppu: &PPU) -> RgbaImage {
+            self.run_pending_transfer(ppu);
+            let backdrop = self.palettes[0][0];
+
+            match self.mask {
+                ScreenMask::Cancel => {
+                    for (x, y, pixel) in self.game_screen.enumerate_pixels_mut() {
+                        let cell = (y as usize / 8) * ATTRIBUTE_COLUMNS + x as usize / 8;
+                        let shade = ppu.shade_buffer[(y * GAME_SCREEN_WIDTH + x) as usize];
+                        let palette = self.attribute_map[cell] as usize;
+                        *pixel = Rgba(self.palettes[palette][shade as usize & 0x03]);
+                    }
+                }
+                //the last frame stays on screen
+                ScreenMask::Freeze => {}
+                ScreenMask::Black => {
+                    for pixel in self.game_screen.pixels_mut() {
+                        *pixel = Rgba([0, 0, 0, 255]);
+                    }
+                }
+                ScreenMask::Color0 => {
+                    for pixel in self.game_screen.pixels_mut() {
+                        *pixel = Rgba(backdrop);
+                    }
+                }
+            }
+
+            let mut frame = RgbaImage::new(SGB_SCREEN_WIDTH, SGB_SCREEN_HEIGHT);
+            for (x, y, pixel) in frame.enumerate_pixels_mut() {
+                let entry = self.border_map[(y as usize / 8) * BORDER_COLUMNS + x as usize / 8];
+                //palettes 4-7 are the border ones
+                let palette = ((entry >> 10) & 0x03) as usize;
+                let tile_x = if entry & 0x4000 != 0 {
+                    7 - x % 8
+                } else {
+                    x % 8
+                };
+                let tile_y = if entry & 0x8000 != 0 {
+                    7 - y % 8
+                } else {
+                    y % 8
+                };
+                let color =
+                    self.border_tiles[(entry & 0xFF) as usize][tile_y as usize][tile_x as usize];
+                //color 0 is transparent and shows the backdrop
+                *pixel = if color == 0 {
+                    Rgba(backdrop)
+                } else {
+                    Rgba(self.border_palettes[palette][color as usize])
+                };
+            }
+            image::imageops::replace(
+                &mut frame,
+                &self.game_screen,
+                GAME_SCREEN_X as i64,
+                GAME_SCREEN_Y as i64,
+            );
+            frame
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use crate::config::config::{Config, WindowConfig};
+use crate::ppu::filters::filters::{ColorCorrection, FrameBlending};
+use std::path::Path;
+
+#[test]
+fn config_is_read_with_defaults() {
+    let config = Config::parse(r#"{"color_correction": "agb"}"#).unwrap();
+    assert_eq!(config.color_correction, ColorCorrection::Agb);
+    assert_eq!(config.frame_blending, FrameBlending::None);
+    assert!(config.sgb);
+
+    let config = Config::parse(r#"{"frame_blending": "ghosting", "unknown": 1}"#).unwrap();
+    assert_eq!(config.frame_blending, FrameBlending::Ghosting);
+    assert!(Config::parse(r#"{"frame_blending": "blur"}"#).is_err());
+    assert!(!Config::parse(r#"{"sgb": false}"#).unwrap().sgb);
+
+    assert_eq!(Config::load(Path::new("missing_config.json")), Config::default());
+}
+
+#[test]
+fn window_settings_are_remembered() {
+    let window = WindowConfig::default();
+    assert_eq!(window.initial_size(), [480, 432]);
+
+    let path = std::env::temp_dir().join("pog_boy_window_test.json");
+    std::fs::write(&path, r#"{"frame_blending": "average", "window": {"scale": 2}}"#).unwrap();
+    let remembered = WindowConfig {
+        size: Some([640, 480]),
+        fullscreen: true,
+        ..WindowConfig::default()
+    };
+    Config::save_window(&path, &remembered).unwrap();
+    //the other settings are left as they were
+    let config = Config::load(&path);
+    assert_eq!(config.frame_blending, FrameBlending::Average);
+    assert_eq!(config.window, remembered);
+    assert_eq!(config.window.initial_size(), [640, 480]);
+
+    //a broken file isn't overwritten
+    std::fs::write(&path, "{broken").unwrap();
+    assert!(Config::save_window(&path, &remembered).is_err());
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "{broken");
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,224 @@
+use crate::debugger::debugger::{DebugView, Viewer};
+use crate::debugger::line_viewer::line_viewer::{changed_registers, LineViewer};
+use crate::debugger::map_viewer::map_viewer::{export_tile_map, render_tile_map, MapViewer};
+use crate::debugger::oam_viewer::oam_viewer::{
+    export_sprite_sheet, render_sprite_sheet, sprite_info, OamViewer,
+};
+use crate::debugger::tile_viewer::tile_viewer::{tile_info, TilePalette, TileViewer};
+use crate::ppu::palette::palette::{PaletteLayer, POG_RED};
+use crate::ppu::ppu::TilePixelValue;
+use crate::tests::factories::create_dummy_ppu;
+use piston_window::Key;
+
+#[test]
+fn tile_viewer_shows_tiles_and_what_uses_them() {
+    let mut ppu = create_dummy_ppu();
+    //tile 1 is all color 1, tile 257 (0x9010) all color 3
+    for row in 0..8 {
+        ppu.write_byte(0x8010 + row * 2, 0xFF);
+        ppu.write_byte(0x9010 + row * 2, 0xFF);
+        ppu.write_byte(0x9010 + row * 2 + 1, 0xFF);
+    }
+    ppu.write_byte(0x9800, 0x01);
+    ppu.write_byte(0x9801, 0x01);
+    ppu.write_byte(0x9C00, 0x01);
+    ppu.write_byte(0xFE00 + 3 * 4 + 2, 0x01);
+    ppu.write_byte(0xFF47, 0xE4);
+    ppu.write_byte(0xFF40, 0x11);
+
+    let mut tile_viewer = TileViewer::default();
+    let image = tile_viewer.render(&ppu);
+    assert_eq!(image.dimensions(), (128, 192));
+    //16 tiles per row
+    assert_eq!(image.get_pixel(0, 0).0, POG_RED[0]);
+    assert_eq!(image.get_pixel(8, 0).0, POG_RED[1]);
+    assert_eq!(image.get_pixel(8, 16 * 8).0, POG_RED[3]);
+
+    //with 8000 addressing the maps point to tile 1, sprites always do
+    assert_eq!(tile_viewer.tile_at(9, 1), Some((0, 1)));
+    let info = tile_info(&ppu, 0, 1);
+    assert_eq!(info.address, 0x8010);
+    assert_eq!(info.map_references, [2, 1]);
+    assert_eq!(info.sprites, vec![3]);
+    assert_eq!(
+        tile_viewer.describe(&ppu, 9, 1).unwrap(),
+        "tile 1 bank 0 at 0x8010, 9800 map: 2, 9C00 map: 1, sprites: 3"
+    );
+    //with 8800 addressing they point to tile 257 instead
+    ppu.write_byte(0xFF40, 0x01);
+    assert_eq!(tile_info(&ppu, 0, 1).map_references, [0, 0]);
+    assert_eq!(tile_info(&ppu, 0, 257).map_references, [2, 1]);
+    assert!(tile_info(&ppu, 0, 257).sprites.is_empty());
+
+    //the palette is cycled with p, cgb palettes are skipped on dmg
+    assert!(tile_viewer.handle_key(Key::P));
+    assert_eq!(tile_viewer.palette, TilePalette::Obj0);
+    //keys the view doesn't use are left to the game
+    assert!(!tile_viewer.handle_key(Key::Z));
+    assert_eq!(tile_viewer.palette, TilePalette::Obj0);
+    for _ in 0..3 {
+        tile_viewer.handle_key(Key::P);
+    }
+    assert_eq!(tile_viewer.palette, TilePalette::Background);
+    assert_eq!(TilePalette::Obj1.next(true), TilePalette::CgbBackground(0));
+
+    //on cgb the second bank is drawn next to the first one
+    ppu.cgb_mode = true;
+    let image = tile_viewer.render(&ppu);
+    assert_eq!(image.dimensions(), (264, 192));
+    assert_eq!(tile_viewer.tile_at(130, 0), None);
+    assert_eq!(tile_viewer.tile_at(136 + 9, 0), Some((1, 1)));
+
+    //the view is only drawn while its window is open
+    let mut debug_view = DebugView::new(Box::new(TileViewer::default()));
+    debug_view.cursor = Some((9, 1));
+    debug_view.update(&ppu);
+    assert_eq!(debug_view.image.dimensions(), (1, 1));
+    debug_view.open = true;
+    debug_view.update(&ppu);
+    assert_eq!(debug_view.image.dimensions(), (264, 192));
+    assert!(debug_view.window_title().starts_with("Tiles (bgp) - tile 1 bank 0"));
+}
+
+#[test]
+fn map_viewer_shows_the_viewport_window_and_attributes() {
+    let mut ppu = create_dummy_ppu();
+    for row in 0..8 {
+        ppu.write_byte(0x8010 + row * 2, 0xFF);
+    }
+    ppu.write_byte(0x9821, 0x01);
+    ppu.write_byte(0xFF47, 0xE4);
+    ppu.write_byte(0xFF43, 250);
+    ppu.write_byte(0xFF42, 20);
+    //window from the 9c00 map at 87, 100 on screen
+    ppu.write_byte(0xFF4B, 94);
+    ppu.write_byte(0xFF4A, 100);
+    ppu.write_byte(0xFF40, 0x71);
+
+    let tile_map = render_tile_map(&ppu, 0);
+    assert_eq!(tile_map.dimensions(), (256, 256));
+    assert_eq!(tile_map.get_pixel(8, 8).0, POG_RED[1]);
+    assert_eq!(tile_map.get_pixel(0, 0).0, POG_RED[0]);
+
+    //the viewport wraps around the right edge of the map
+    let (red, blue) = ([255, 0, 0, 255], [0, 96, 255, 255]);
+    let mut map_viewer = MapViewer::default();
+    let image = map_viewer.render(&ppu);
+    assert_eq!(image.get_pixel(250, 20).0, red);
+    assert_eq!(image.get_pixel(100, 20).0, red);
+    assert_eq!(image.get_pixel((250 + 159) % 256, 60).0, red);
+    assert_eq!(image.get_pixel(100, 163).0, red);
+    assert_eq!(image.get_pixel(100, 60).0, POG_RED[0]);
+    assert_eq!(
+        map_viewer.describe(&ppu, 9, 9).unwrap(),
+        "1, 1 at 0x9821: tile 0x01 at 0x8010"
+    );
+
+    //the visible part of the window is outlined on its own map
+    map_viewer.handle_key(Key::M);
+    let image = map_viewer.render(&ppu);
+    assert_eq!(image.get_pixel(0, 0).0, blue);
+    assert_eq!(image.get_pixel(72, 43).0, blue);
+    assert_eq!(image.get_pixel(73, 43).0, POG_RED[0]);
+    assert_eq!(image.get_pixel(250, 20).0, POG_RED[0]);
+
+    //cgb attributes pick bank, palette and flips, the overlay tints by palette
+    ppu.cgb_mode = true;
+    ppu.write_byte(0xFF4F, 1);
+    ppu.write_byte(0x9821, 0x22);
+    ppu.write_byte(0xFF4F, 0);
+    assert_eq!(
+        MapViewer::default().describe(&ppu, 9, 9).unwrap(),
+        "1, 1 at 0x9821: tile 0x01 at 0x8010, bank 0, palette 2, x flip"
+    );
+    let plain = render_tile_map(&ppu, 0);
+    let tinted = MapViewer::default().render(&ppu);
+    assert_ne!(plain.get_pixel(8, 8), tinted.get_pixel(8, 8));
+
+    let path = std::env::temp_dir().join("pog_boy_tile_map_test.png");
+    export_tile_map(&ppu, 0, &path).unwrap();
+    assert_eq!(image::open(&path).unwrap().to_rgba8(), plain);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn oam_viewer_lists_previews_and_exports_sprites() {
+    let mut ppu = create_dummy_ppu();
+    ppu.write_byte(0x8010, 0xFF);
+    ppu.write_byte(0x8011, 0xFF);
+    ppu.write_byte(0xFF49, 0xE4);
+    //sprite 2 at 30, 40 with obp1, flipped vertically
+    ppu.write_byte(0xFE08, 40 + 16);
+    ppu.write_byte(0xFE09, 30 + 8);
+    ppu.write_byte(0xFE0A, 0x01);
+    ppu.write_byte(0xFE0B, 0x50);
+    let sprite_color = ppu.get_color_from_palette(TilePixelValue::Three, PaletteLayer::Obj1);
+
+    assert_eq!(
+        sprite_info(&ppu, 2),
+        "sprite 2: x 30, y 40, tile 0x01 at 0x8010, obp1, y flip"
+    );
+    let sheet = render_sprite_sheet(&ppu);
+    assert_eq!(sheet.dimensions(), (64, 40));
+    assert_eq!(sheet.get_pixel(16, 7).0, sprite_color);
+    assert_eq!(sheet.get_pixel(16, 0).0, [0, 0, 0, 0]);
+
+    //the selected sprite is outlined in the grid and on the screen
+    let selection = [255, 220, 0, 255];
+    let mut oam_viewer = OamViewer::default();
+    oam_viewer.handle_key(Key::Right);
+    oam_viewer.handle_key(Key::Right);
+    assert!(!oam_viewer.handle_key(Key::Return));
+    assert_eq!(oam_viewer.selected, 2);
+    let image = oam_viewer.render(&ppu);
+    assert_eq!(image.dimensions(), (296, 144));
+    assert_eq!(image.get_pixel(32, 0).0, selection);
+    assert_eq!(image.get_pixel(36, 11).0, sprite_color);
+    assert_eq!(image.get_pixel(136 + 29, 39).0, selection);
+    assert_eq!(image.get_pixel(136 + 38, 48).0, selection);
+    assert_eq!(
+        oam_viewer.describe(&ppu, 136 + 30, 40),
+        Some(sprite_info(&ppu, 2))
+    );
+    assert_eq!(oam_viewer.describe(&ppu, 33, 1), Some(sprite_info(&ppu, 2)));
+    assert_eq!(oam_viewer.describe(&ppu, 136 + 100, 100), None);
+    oam_viewer.handle_key(Key::H);
+    oam_viewer.update_ppu(&mut ppu);
+    assert!(!ppu.render_mask.is_sprite_visible(2));
+    assert!(sprite_info(&ppu, 2).ends_with("y flip, hidden"));
+    oam_viewer.handle_key(Key::Up);
+    assert_eq!(oam_viewer.selected, 34);
+
+    let path = std::env::temp_dir().join("pog_boy_sprite_sheet_test.png");
+    export_sprite_sheet(&ppu, &path).unwrap();
+    assert_eq!(image::open(&path).unwrap().to_rgba8(), sheet);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn line_viewer_marks_registers_changed_between_lines() {
+    let mut ppu = create_dummy_ppu();
+    ppu.line_registers[30].scroll_x = 0x10;
+    ppu.line_registers[30].background_palette = 0xE4;
+    ppu.line_registers[31].background_palette = 0xE4;
+
+    assert_eq!(
+        changed_registers(&ppu, 30),
+        [true, false, false, false, false, false, true, false, false]
+    );
+    assert!(!changed_registers(&ppu, 31)[6]);
+    assert!(changed_registers(&ppu, 31)[0]);
+    assert_eq!(changed_registers(&ppu, 0), [false; 9]);
+
+    let mut line_viewer = LineViewer::default();
+    let image = line_viewer.render(&ppu);
+    assert_eq!(image.dimensions(), (160 + 8 + 9 * 6, 144));
+    assert_eq!(image.get_pixel(168, 30).0, [230, 25, 75, 255]);
+    assert_eq!(image.get_pixel(168 + 6 * 6, 30).0, [70, 240, 240, 255]);
+    assert_eq!(image.get_pixel(168 + 6 * 6, 31).0, [70, 70, 70, 255]);
+    assert_eq!(
+        line_viewer.describe(&ppu, 0, 30).unwrap(),
+        "line 30: SCX 10 SCY 00 WX 00 WY 00 LCDC 00 STAT 00 BGP E4 OBP0 00 OBP1 00"
+    );
+    assert_eq!(line_viewer.describe(&ppu, 0, 144), None);
+}
@@ -24,6 +24,18 @@ pub(crate) fn create_dummy_cartridge() -> Cartridge {
     }
 }
 
+//32 KiB rom with a "POGBOY" MBC1+RAM+BATTERY header, checksums are left to zero
+pub(crate) fn create_dummy_rom() -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    rom[0x134..0x13A].copy_from_slice(b"POGBOY");
+    rom[0x146] = 0x03; //sgb flag
+    rom[0x147] = 0x03; //MBC1+RAM+BATTERY
+    rom[0x148] = 0x00; //32 KiB
+    rom[0x149] = 0x02; //8 KiB
+    rom[0x14B] = 0x01; //Nintendo
+    rom
+}
+
 pub(crate) fn create_dummy_tile() -> Tile {
     [
         [
@@ -122,7 +134,7 @@ pub(crate) fn create_dummy_mmu(dummy_ppu: &mut PPU) -> MMU {
     let mut dummy_mmu = MMU::new(Some(dummy_cartridge), dummy_ppu);
     dummy_mmu.PPU.video_ram = [1; 0x2000];
     dummy_mmu.external_ram = [2; 0x2000];
-    dummy_mmu.work_ram = [3; 0x2000];
+    dummy_mmu.work_ram = [3; 0x8000];
     dummy_mmu.io_registers = [4; 0x100];
     dummy_mmu.high_ram = [5; 0x80];
     dummy_mmu
@@ -1,4 +1,5 @@
 use crate::cpu::CPU::{InterruptType, CPU};
+use crate::io::display::display::{fit_frames, Viewport};
 use crate::tests::factories::{create_dummy_gamepad, create_dummy_mmu, create_dummy_ppu};
 use piston_window::Key;
 
@@ -77,3 +78,35 @@ fn interrupt_handler_sets_right_pc_address_and_SP() {
     assert_eq!(cpu.Registers.PC, 0x58);
     assert_eq!(cpu.read_from_stack(), 42);
 }
+
+#[test]
+fn frames_are_letterboxed_in_the_window() {
+    //a wide window gets black bars on the sides
+    assert_eq!(
+        fit_frames([800.0, 432.0], &[[160, 144]], false),
+        vec![Viewport { x: 160.0, y: 0.0, scale: 3.0 }]
+    );
+    let viewport = fit_frames([500.0, 500.0], &[[160, 144]], false)[0];
+    assert_eq!((viewport.x, viewport.y, viewport.scale), (0.0, 25.0, 3.125));
+    //integer scaling leaves the rest of the window empty, unless the window is too small
+    assert_eq!(
+        fit_frames([500.0, 500.0], &[[160, 144]], true),
+        vec![Viewport { x: 10.0, y: 34.0, scale: 3.0 }]
+    );
+    assert_eq!(fit_frames([80.0, 72.0], &[[160, 144]], true)[0].scale, 0.5);
+}
+
+#[test]
+fn debug_views_are_drawn_next_to_the_frame() {
+    //both frames get the same height and the row is centered in the window
+    let viewports = fit_frames([1000.0, 600.0], &[[160, 144], [264, 192]], false);
+    let row_height: f64 = 1000.0 / (160.0 / 144.0 + 264.0 / 192.0);
+    assert_eq!(viewports[0].x, 0.0);
+    assert_eq!(viewports[0].y, ((600.0 - row_height) / 2.0).floor());
+    assert!((viewports[0].scale * 144.0 - row_height).abs() < 1e-9);
+    assert!((viewports[1].scale * 192.0 - row_height).abs() < 1e-9);
+    assert_eq!(viewports[1].x, (160.0 * viewports[0].scale).floor());
+    //with integer scaling each frame is scaled by a whole number inside its column
+    let viewports = fit_frames([1000.0, 600.0], &[[160, 144], [264, 192]], true);
+    assert_eq!((viewports[0].scale, viewports[1].scale), (2.0, 2.0));
+}
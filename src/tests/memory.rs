@@ -1,5 +1,12 @@
 use crate::cpu::CPU::CPU;
-use crate::tests::factories::{create_dummy_mmu, create_dummy_ppu};
+use crate::memory::cartridge::cartridge::{
+    compute_global_checksum, find_patch_file, load_rom, rom_crc32, rom_sha1, CartridgeInfo,
+};
+use crate::memory::cheats::cheats::{decode_game_genie, decode_gameshark, CheatCode, CheatError};
+use crate::memory::dat::dat::{DatEntry, RomDatabase};
+use crate::memory::patch::patch::{apply_patch, PatchError};
+use crate::ppu::ppu::PpuMode;
+use crate::tests::factories::{create_dummy_mmu, create_dummy_ppu, create_dummy_rom};
 
 #[test]
 fn decoder_can_parse_correctly() {
@@ -78,3 +85,389 @@ fn push_and_pop_works() {
     cpu.pop_rr("DE");
     assert_eq!(cpu.Registers.get_item("DE"), 0xFFEE)
 }
+
+#[test]
+fn cartridge_header_is_parsed_correctly() {
+    let mut rom = create_dummy_rom();
+
+    let cartridge_info = CartridgeInfo::from_rom(&rom).unwrap();
+    assert_eq!(cartridge_info.game_title(), "POGBOY");
+    assert_eq!(cartridge_info.mapper_name(), "MBC1+RAM+BATTERY");
+    assert_eq!(cartridge_info.rom_size_bytes(), Some(0x8000));
+    assert_eq!(cartridge_info.ram_size_bytes(), Some(0x2000));
+    assert_eq!(cartridge_info.licensee(), "Nintendo");
+    assert_eq!(cartridge_info.supports_cgb(), false);
+    assert_eq!(cartridge_info.supports_sgb(), false); //old licensee code isn't 0x33
+    assert_eq!(cartridge_info.is_header_checksum_valid(), false);
+
+    rom[0x14D] = cartridge_info.compute_header_checksum();
+    let global_checksum = compute_global_checksum(&rom);
+    rom[0x14E] = (global_checksum >> 8) as u8;
+    rom[0x14F] = (global_checksum & 0xFF) as u8;
+
+    let cartridge_info = CartridgeInfo::from_rom(&rom).unwrap();
+    assert_eq!(cartridge_info.is_header_checksum_valid(), true);
+    assert_eq!(cartridge_info.global_checksum(), compute_global_checksum(&rom));
+
+    assert!(CartridgeInfo::from_rom(&rom[..0x100]).is_none());
+}
+
+//UPS/BPS variable length number
+fn encode_patch_number(mut value: usize) -> Vec<u8> {
+    let mut encoded = vec![];
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            encoded.push(0x80 | byte);
+            return encoded;
+        }
+        encoded.push(byte);
+        value -= 1;
+    }
+}
+
+fn add_patch_footer(mut patch: Vec<u8>, source: &[u8], target: &[u8]) -> Vec<u8> {
+    patch.extend_from_slice(&crc32fast::hash(source).to_le_bytes());
+    patch.extend_from_slice(&crc32fast::hash(target).to_le_bytes());
+    patch.extend_from_slice(&crc32fast::hash(&patch).to_le_bytes());
+    patch
+}
+
+#[test]
+fn ips_patches_are_applied() {
+    let rom = vec![0; 0x10];
+    let mut patch = b"PATCH".to_vec();
+    patch.extend_from_slice(&[0x00, 0x00, 0x02, 0x00, 0x02, 0xC0, 0xFE]); //2 bytes at 0x2
+    patch.extend_from_slice(&[0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x04, 0xAA]); //RLE 4 bytes at 0x10
+    patch.extend_from_slice(b"EOF");
+
+    let patched_rom = apply_patch(&rom, &patch).unwrap();
+    assert_eq!(patched_rom.len(), 0x14);
+    assert_eq!(&patched_rom[0x2..0x4], &[0xC0, 0xFE]);
+    assert_eq!(&patched_rom[0x10..0x14], &[0xAA; 4]);
+
+    patch.truncate(patch.len() - 4);
+    assert_eq!(apply_patch(&rom, &patch), Err(PatchError::UnexpectedEnd));
+}
+
+#[test]
+fn ups_patches_are_applied_and_verified() {
+    let rom: Vec<u8> = (0..0x20).collect();
+    let mut target = rom.clone();
+    target[0x4] = 0xFF;
+    target[0x5] = 0xEE;
+    target.push(0x42);
+
+    let mut patch = b"UPS1".to_vec();
+    patch.extend(encode_patch_number(rom.len()));
+    patch.extend(encode_patch_number(target.len()));
+    patch.extend(encode_patch_number(0x4));
+    patch.extend_from_slice(&[0x04 ^ 0xFF, 0x05 ^ 0xEE, 0x00]);
+    patch.extend(encode_patch_number(0x20 - 0x7));
+    patch.extend_from_slice(&[0x42, 0x00]);
+    let patch = add_patch_footer(patch, &rom, &target);
+
+    assert_eq!(apply_patch(&rom, &patch).unwrap(), target);
+
+    let mut other_rom = rom.clone();
+    other_rom[0] = 0xFF;
+    assert!(matches!(
+        apply_patch(&other_rom, &patch),
+        Err(PatchError::SourceChecksumMismatch { .. })
+    ));
+
+    let mut corrupted_patch = patch.clone();
+    corrupted_patch[6] ^= 0xFF;
+    assert!(matches!(
+        apply_patch(&rom, &corrupted_patch),
+        Err(PatchError::PatchChecksumMismatch { .. })
+    ));
+}
+
+#[test]
+fn bps_patches_are_applied_and_verified() {
+    let rom: Vec<u8> = (0..0x10).collect();
+    //first 4 bytes from the rom, 2 new bytes, 4 bytes copied from rom 0x8 and 4 copied from the output start
+    let mut target: Vec<u8> = rom[0..4].to_vec();
+    target.extend_from_slice(&[0xC0, 0xFE]);
+    target.extend_from_slice(&rom[0x8..0xC]);
+    target.extend_from_slice(&rom[0..4]);
+
+    let mut patch = b"BPS1".to_vec();
+    patch.extend(encode_patch_number(rom.len()));
+    patch.extend(encode_patch_number(target.len()));
+    patch.extend(encode_patch_number(0));
+    patch.extend(encode_patch_number(3 << 2));
+    patch.extend(encode_patch_number((1 << 2) | 1));
+    patch.extend_from_slice(&[0xC0, 0xFE]);
+    patch.extend(encode_patch_number((3 << 2) | 2));
+    patch.extend(encode_patch_number(0x8 << 1));
+    patch.extend(encode_patch_number((3 << 2) | 3));
+    patch.extend(encode_patch_number(0));
+    let patch = add_patch_footer(patch, &rom, &target);
+
+    assert_eq!(apply_patch(&rom, &patch).unwrap(), target);
+    assert!(matches!(
+        apply_patch(&rom[..0x8], &patch),
+        Err(PatchError::SourceSizeMismatch { .. })
+    ));
+    assert_eq!(apply_patch(&rom, b"NOT A PATCH"), Err(PatchError::UnknownFormat));
+}
+
+#[test]
+fn cheat_codes_are_decoded() {
+    assert_eq!(
+        decode_game_genie("C3A-12F-3B2"),
+        Ok(CheatCode::GameGenie {
+            address: 0x0A12,
+            value: 0xC3,
+            compare: Some(0x36),
+        })
+    );
+    assert_eq!(
+        decode_game_genie("c3a12f"),
+        Ok(CheatCode::GameGenie {
+            address: 0x0A12,
+            value: 0xC3,
+            compare: None,
+        })
+    );
+    assert_eq!(decode_game_genie("C3A-127"), Err(CheatError::InvalidAddress(0x8A12)));
+    assert_eq!(decode_game_genie("C3A-12"), Err(CheatError::InvalidLength));
+    assert_eq!(decode_game_genie("X3A-12F"), Err(CheatError::InvalidCharacter('X')));
+
+    assert_eq!(
+        decode_gameshark("010238CD"),
+        Ok(CheatCode::GameShark {
+            address: 0xCD38,
+            value: 0x02,
+            bank: None,
+        })
+    );
+    assert_eq!(
+        decode_gameshark("9102FFD0"),
+        Ok(CheatCode::GameShark {
+            address: 0xD0FF,
+            value: 0x02,
+            bank: Some(1),
+        })
+    );
+    assert_eq!(
+        decode_gameshark("820500A0"),
+        Ok(CheatCode::GameShark {
+            address: 0xA000,
+            value: 0x05,
+            bank: Some(2),
+        })
+    );
+    assert_eq!(decode_gameshark("910200C0"), Err(CheatError::InvalidAddress(0xC000)));
+}
+
+#[test]
+fn cheats_are_applied() {
+    let mut dummy_ppu = create_dummy_ppu();
+    let mut dummy_mmu = create_dummy_mmu(&mut dummy_ppu);
+
+    assert_eq!(dummy_mmu.read_byte(0x101), 0x3E);
+    let wrong_compare = dummy_mmu.cartridge.cheats.add("FF1-01F-1E3", "").unwrap();
+    assert_eq!(dummy_mmu.read_byte(0x101), 0x3E);
+    dummy_mmu.cartridge.cheats.remove(wrong_compare);
+
+    let genie = dummy_mmu.cartridge.cheats.add("FF1-01F-1E2", "").unwrap();
+    assert_eq!(dummy_mmu.read_byte(0x101), 0xFF);
+    assert_eq!(dummy_mmu.cartridge.cheats.toggle(genie), false);
+    assert_eq!(dummy_mmu.read_byte(0x101), 0x3E);
+
+    let gameshark = dummy_mmu.cartridge.cheats.add("019900C0", "").unwrap();
+    dummy_mmu.apply_cheats();
+    assert_eq!(dummy_mmu.read_byte(0xC000), 0x99);
+
+    dummy_mmu.cartridge.cheats.set_enabled(gameshark, false);
+    dummy_mmu.write_byte(0xC000, 0x00);
+    dummy_mmu.apply_cheats();
+    assert_eq!(dummy_mmu.read_byte(0xC000), 0x00);
+}
+
+#[test]
+fn roms_are_identified_from_dat() {
+    let rom = create_dummy_rom();
+    let dat = format!(
+        r#"<?xml version="1.0"?>
+        <datafile>
+            <header><name>Nintendo - Game Boy</name></header>
+            <game name="Pog Boy (Japan, USA) (Rev A)">
+                <rom name="Pog Boy (Japan, USA) (Rev A).gb" size="{}" crc="{:08X}" sha1="{}"/>
+            </game>
+            <game name="Pog Boy (Europe) (Beta)">
+                <rom name="Pog Boy (Europe) (Beta).gb" size="{}" crc="{:08x}" sha1="0000000000000000000000000000000000000000"/>
+            </game>
+        </datafile>"#,
+        rom.len(),
+        rom_crc32(&rom),
+        rom_sha1(&rom),
+        rom.len(),
+        rom_crc32(&rom),
+    );
+    let rom_database = RomDatabase::parse(&dat).unwrap();
+    assert_eq!(rom_database.len(), 2);
+    assert_eq!(
+        rom_database.identify(&rom),
+        Some(DatEntry {
+            name: "Pog Boy (Japan, USA) (Rev A)".to_owned(),
+            region: Some("Japan, USA".to_owned()),
+            revision: Some("A".to_owned()),
+        })
+    );
+
+    let mut unknown_rom = rom.clone();
+    unknown_rom[0x150] = 0xFF;
+    assert_eq!(rom_database.identify(&unknown_rom), None);
+
+    let entry = DatEntry::from_name("Pog Boy (Europe) (En,Fr,De) (v1.1)");
+    assert_eq!(entry.region.as_deref(), Some("Europe"));
+    assert_eq!(entry.revision.as_deref(), Some("v1.1"));
+    assert!(RomDatabase::parse("<datafile>").is_err());
+}
+
+//an ips patch writing a byte
+fn ips_patch(address: u32, value: u8) -> Vec<u8> {
+    let mut patch = b"PATCH".to_vec();
+    patch.extend_from_slice(&address.to_be_bytes()[1..]);
+    patch.extend_from_slice(&[0x00, 0x01, value]);
+    patch.extend_from_slice(b"EOF");
+    patch
+}
+
+#[test]
+fn only_the_first_patch_next_to_a_rom_is_applied() {
+    let directory = std::env::temp_dir().join("pog_boy_patch_lookup_test");
+    let _ = std::fs::remove_dir_all(&directory);
+    std::fs::create_dir_all(&directory).unwrap();
+    let rom_path = directory.join("pog.gb");
+    std::fs::write(&rom_path, create_dummy_rom()).unwrap();
+    assert_eq!(find_patch_file(&rom_path), None);
+
+    //alternatives for the same rom, a broken bps would stop the loading if it was applied
+    std::fs::write(directory.join("pog.gb.ips"), ips_patch(0x150, 0x02)).unwrap();
+    std::fs::write(directory.join("pog.bps"), b"BPS1").unwrap();
+    assert_eq!(find_patch_file(&rom_path), Some(directory.join("pog.gb.ips")));
+    std::fs::write(directory.join("pog.ips"), ips_patch(0x151, 0x01)).unwrap();
+    assert_eq!(find_patch_file(&rom_path), Some(directory.join("pog.ips")));
+    let (rom, _) = load_rom(&rom_path, &[], None);
+    assert_eq!(&rom[0x150..0x152], &[0x00, 0x01]);
+
+    //patches given explicitly are still chained
+    let patch_files = [directory.join("pog.ips"), directory.join("pog.gb.ips")];
+    let (rom, _) = load_rom(&rom_path, &patch_files, None);
+    assert_eq!(&rom[0x150..0x152], &[0x02, 0x01]);
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn patched_roms_are_identified_from_the_original_rom() {
+    let directory = std::env::temp_dir().join("pog_boy_patched_dat_test");
+    let _ = std::fs::remove_dir_all(&directory);
+    std::fs::create_dir_all(&directory).unwrap();
+    let rom = create_dummy_rom();
+    let rom_path = directory.join("pog.gb");
+    std::fs::write(&rom_path, &rom).unwrap();
+    //the translation changes a byte, so the patched rom isn't in the dat anymore
+    std::fs::write(directory.join("pog.ips"), ips_patch(0x150, 0xFF)).unwrap();
+    let dat = format!(
+        r#"<datafile>
+            <game name="Pog Boy (Europe) (Rev 1)">
+                <rom name="Pog Boy (Europe) (Rev 1).gb" size="{}" crc="{:08X}"/>
+            </game>
+        </datafile>"#,
+        rom.len(),
+        rom_crc32(&rom),
+    );
+    let rom_database = RomDatabase::parse(&dat).unwrap();
+
+    let (patched_rom, dat_entry) = load_rom(&rom_path, &[], Some(&rom_database));
+    assert_eq!(patched_rom[0x150], 0xFF);
+    assert_eq!(rom_database.identify(&patched_rom), None);
+    assert_eq!(
+        dat_entry.map(|dat_entry| dat_entry.name),
+        Some("Pog Boy (Europe) (Rev 1)".to_owned())
+    );
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn cgb_work_ram_banks_can_be_switched() {
+    let mut dummy_ppu = create_dummy_ppu();
+    let mut dummy_mmu = create_dummy_mmu(&mut dummy_ppu);
+
+    //dmg ignores svbk
+    dummy_mmu.write_byte(0xFF70, 0x02);
+    dummy_mmu.write_byte(0xD000, 0xAB);
+    assert_eq!(dummy_mmu.work_ram_bank, 1);
+    assert_eq!(dummy_mmu.work_ram[0x1000], 0xAB);
+
+    dummy_mmu.cgb_mode = true;
+    dummy_mmu.write_byte(0xFF70, 0x02);
+    assert_eq!(dummy_mmu.read_byte(0xFF70), 0xFA);
+    dummy_mmu.write_byte(0xD000, 0xCD);
+    dummy_mmu.write_byte(0xC000, 0xEF);
+    dummy_mmu.write_byte(0xFF70, 0x03);
+    assert_eq!(dummy_mmu.read_byte(0xD000), 0x03);
+    assert_eq!(dummy_mmu.read_byte(0xC000), 0xEF);
+    dummy_mmu.write_byte(0xFF70, 0x02);
+    assert_eq!(dummy_mmu.read_byte(0xD000), 0xCD);
+
+    //bank 0 can't be selected
+    dummy_mmu.write_byte(0xFF70, 0x00);
+    assert_eq!(dummy_mmu.read_byte(0xFF70), 0xF9);
+    assert_eq!(dummy_mmu.read_byte(0xD000), 0xAB);
+}
+
+#[test]
+fn cgb_vram_dma_copies_blocks_to_vram() {
+    let mut dummy_ppu = create_dummy_ppu();
+    let mut dummy_mmu = create_dummy_mmu(&mut dummy_ppu);
+    dummy_mmu.cgb_mode = true;
+    for offset in 0..0x40 {
+        dummy_mmu.write_byte(0xC000 + offset, offset as u8);
+    }
+
+    //general purpose dma copies 2 blocks right away and stalls the cpu
+    dummy_mmu.write_byte(0xFF51, 0xC0);
+    dummy_mmu.write_byte(0xFF52, 0x00);
+    dummy_mmu.write_byte(0xFF53, 0x80);
+    dummy_mmu.write_byte(0xFF54, 0x00);
+    dummy_mmu.write_byte(0xFF55, 0x01);
+    assert_eq!(dummy_mmu.read_byte(0x8000), 0x00);
+    assert_eq!(dummy_mmu.read_byte(0x801F), 0x1F);
+    assert_eq!(dummy_mmu.read_byte(0x8020), 0x01);
+    assert_eq!(dummy_mmu.read_byte(0xFF55), 0xFF);
+    assert_eq!(dummy_mmu.hdma.stall_cycles, 64);
+    dummy_mmu.hdma.stall_cycles = 0;
+
+    //hblank dma copies a block at the start of each hblank
+    dummy_mmu.write_byte(0xFF40, 0x91);
+    dummy_mmu.write_byte(0xFF51, 0xC0);
+    dummy_mmu.write_byte(0xFF52, 0x20);
+    dummy_mmu.write_byte(0xFF53, 0x01);
+    dummy_mmu.write_byte(0xFF54, 0x00);
+    dummy_mmu.write_byte(0xFF55, 0x82);
+    assert_eq!(dummy_mmu.read_byte(0xFF55), 0x02);
+    while dummy_mmu.hdma.stall_cycles < 64 {
+        dummy_mmu.PPU.step(1);
+        dummy_mmu.step_hdma();
+    }
+    assert_eq!(dummy_mmu.PPU.get_current_mode(), PpuMode::HBlank);
+    assert_eq!(dummy_mmu.PPU.current_line, 1);
+    assert_eq!(dummy_mmu.read_byte(0x8100), 0x20);
+    assert_eq!(dummy_mmu.read_byte(0x811F), 0x3F);
+    assert_eq!(dummy_mmu.read_byte(0x8120), 0x01);
+    assert_eq!(dummy_mmu.read_byte(0xFF55), 0x00);
+
+    //writing bit 7 clear cancels it, the status keeps the blocks left
+    dummy_mmu.write_byte(0xFF55, 0x00);
+    assert_eq!(dummy_mmu.read_byte(0xFF55), 0x80);
+    dummy_mmu.PPU.step(456);
+    dummy_mmu.step_hdma();
+    assert_eq!(dummy_mmu.read_byte(0xFF55), 0x80);
+}
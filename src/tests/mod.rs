@@ -1,5 +1,8 @@
+mod config;
 mod cpu;
+mod debugger;
 mod factories;
 mod io;
 mod memory;
 mod ppu;
+mod tools;
@@ -1,5 +1,16 @@
-use crate::ppu::ppu::{Tile, TilePixelValue, COLORS};
-use crate::tests::factories::{create_dummy_mmu, create_dummy_ppu, create_dummy_tile};
+use crate::memory::cartridge::cartridge::CartridgeInfo;
+use crate::ppu::compatibility::compatibility::{compatibility_palette, title_index};
+use crate::ppu::filters::filters::{ColorCorrection, FrameBlending, FrameFilter};
+use crate::ppu::hd_pack::hd_pack::{tile_file_name, tile_hash, HdPack};
+use crate::ppu::palette::palette::{
+    palette_presets, parse_user_palettes, PaletteLayer, PaletteSelector, POG_RED,
+};
+use crate::ppu::scalers::scalers::{Overlay, Scaler, Upscaler};
+use crate::ppu::ppu::{dump_line_registers, PpuMode, Tile, TilePixelValue, PPU};
+use crate::ppu::sgb::sgb::{Sgb, SGB_SCREEN_HEIGHT, SGB_SCREEN_WIDTH};
+use image::{Rgba, RgbaImage};
+use crate::memory::mmu::mmu::MMU;
+use crate::tests::factories::{create_dummy_mmu, create_dummy_ppu, create_dummy_rom, create_dummy_tile};
 
 #[test]
 fn tiles_are_generated_correctly() {
@@ -42,7 +53,7 @@ fn tiles_are_generated_correctly() {
 fn color_from_bg_palette_is_loaded_correctly() {
     let mut dummy_ppu = create_dummy_ppu();
     let mut dummy_mmu = create_dummy_mmu(&mut dummy_ppu);
-    let ppu_colors = COLORS;
+    let ppu_colors = POG_RED;
 
     dummy_mmu.write_byte(0xFF47, 0xFF);
     assert_eq!(
@@ -74,3 +85,949 @@ fn color_from_bg_palette_is_loaded_correctly() {
         ppu_colors[0]
     );
 }
+
+//step the ppu until it enters the given mode, returns the elapsed dots
+fn step_until_mode(ppu: &mut PPU, ppu_mode: PpuMode) -> u32 {
+    let mut dots = 0;
+    while ppu.get_current_mode() != ppu_mode {
+        ppu.step(1);
+        dots += 1;
+    }
+    dots
+}
+
+//dots spent in mode 3 by the next visible line
+fn next_pixel_transfer_length(ppu: &mut PPU) -> u32 {
+    step_until_mode(ppu, PpuMode::OAM);
+    step_until_mode(ppu, PpuMode::VRAM);
+    step_until_mode(ppu, PpuMode::HBlank)
+}
+
+#[test]
+fn pixel_transfer_length_depends_on_scroll_and_sprites() {
+    let mut ppu = create_dummy_ppu();
+    ppu.write_byte(0xFF40, 0x91);
+    assert_eq!(next_pixel_transfer_length(&mut ppu), 172);
+    assert_eq!(step_until_mode(&mut ppu, PpuMode::OAM), 456 - 80 - 172);
+
+    //fine scroll discards scx & 7 pixels
+    ppu.write_byte(0xFF43, 0x0B);
+    assert_eq!(next_pixel_transfer_length(&mut ppu), 175);
+    ppu.write_byte(0xFF43, 0x00);
+
+    //each sprite stalls the fetcher for 6 to 11 dots
+    ppu.write_byte(0xFF40, 0x93);
+    let next_line = ppu.current_line as u8 + 1;
+    ppu.write_byte(0xFE00, 16 + next_line);
+    ppu.write_byte(0xFE01, 8 + 40);
+    let length = next_pixel_transfer_length(&mut ppu);
+    assert!((172 + 6..=172 + 11).contains(&length), "{}", length);
+
+    //only the first 10 sprites of a line are fetched
+    for sprite in 0..40 {
+        ppu.write_byte(0xFE00 + sprite * 4, 16);
+        ppu.write_byte(0xFE01 + sprite * 4, 8 + (sprite as u8 % 20) * 8);
+    }
+    //next line is line 0
+    step_until_mode(&mut ppu, PpuMode::VBlank);
+    let length = next_pixel_transfer_length(&mut ppu);
+    assert_eq!(ppu.fifo.line_sprites.len(), 0);
+    assert!((172 + 10 * 6..=172 + 10 * 11).contains(&length), "{}", length);
+}
+
+#[test]
+fn mid_scanline_palette_writes_are_visible() {
+    let mut ppu = create_dummy_ppu();
+    ppu.write_byte(0xFF40, 0x91);
+    skip_blank_frame(&mut ppu);
+    ppu.write_byte(0xFF47, 0x00);
+    step_until_mode(&mut ppu, PpuMode::OAM);
+    step_until_mode(&mut ppu, PpuMode::VRAM);
+
+    //first pixel is sent after 12 dots, change the palette half way through the line
+    ppu.step(12 + 80);
+    ppu.write_byte(0xFF47, 0x03);
+    step_until_mode(&mut ppu, PpuMode::HBlank);
+
+    let line = ppu.current_line;
+    assert_eq!(ppu.image_buffer.get_pixel(0, line).0, POG_RED[0]);
+    assert_eq!(ppu.image_buffer.get_pixel(79, line).0, POG_RED[0]);
+    assert_eq!(ppu.image_buffer.get_pixel(80, line).0, POG_RED[3]);
+    assert_eq!(ppu.image_buffer.get_pixel(159, line).0, POG_RED[3]);
+}
+
+//the first frame after turning the lcd on is not drawn
+fn skip_blank_frame(ppu: &mut PPU) {
+    step_until_mode(ppu, PpuMode::VBlank);
+    step_until_line(ppu, 0);
+}
+
+//step the ppu until the oam scan of the given line
+fn step_until_line(ppu: &mut PPU, line: u32) {
+    while ppu.current_line != line || ppu.get_current_mode() != PpuMode::OAM {
+        ppu.step(1);
+    }
+}
+
+#[test]
+fn window_is_drawn_with_its_own_line_counter() {
+    let mut ppu = create_dummy_ppu();
+    //tile 1 is all color 3, the window tile map (0x9C00) only uses tile 1
+    for address in 0x8010..0x8020 {
+        ppu.write_byte(address, 0xFF);
+    }
+    for address in 0x9C00..0xA000 {
+        ppu.write_byte(address, 0x01);
+    }
+    ppu.write_byte(0xFF47, 0xE4);
+    ppu.write_byte(0xFF4A, 2);
+    ppu.write_byte(0xFF4B, 7 + 80);
+    ppu.write_byte(0xFF40, 0xF1);
+    skip_blank_frame(&mut ppu);
+
+    step_until_line(&mut ppu, 1);
+    step_until_line(&mut ppu, 2);
+    assert_eq!(ppu.image_buffer.get_pixel(100, 1).0, POG_RED[0]);
+    assert_eq!(ppu.window_line, 0);
+
+    step_until_mode(&mut ppu, PpuMode::VRAM);
+    //starting the window restarts the fetcher
+    assert_eq!(step_until_mode(&mut ppu, PpuMode::HBlank), 172 + 6);
+    assert_eq!(ppu.image_buffer.get_pixel(79, 2).0, POG_RED[0]);
+    assert_eq!(ppu.image_buffer.get_pixel(80, 2).0, POG_RED[3]);
+    assert_eq!(ppu.window_line, 1);
+
+    //lines without window don't advance the counter
+    ppu.write_byte(0xFF40, 0xD1);
+    step_until_line(&mut ppu, 4);
+    assert_eq!(ppu.image_buffer.get_pixel(100, 3).0, POG_RED[0]);
+    assert_eq!(ppu.window_line, 1);
+
+    //wx < 7 starts the window left of the screen
+    ppu.write_byte(0xFF40, 0xF1);
+    ppu.write_byte(0xFF4B, 0);
+    step_until_line(&mut ppu, 5);
+    assert_eq!(ppu.image_buffer.get_pixel(0, 4).0, POG_RED[3]);
+    assert_eq!(ppu.window_line, 2);
+
+    //wx > 166 is off screen
+    ppu.write_byte(0xFF4B, 167);
+    step_until_line(&mut ppu, 6);
+    assert_eq!(ppu.image_buffer.get_pixel(159, 5).0, POG_RED[0]);
+    assert_eq!(ppu.window_line, 2);
+
+    //the counter is reset with the next frame
+    step_until_line(&mut ppu, 0);
+    assert_eq!(ppu.window_line, 0);
+    assert!(!ppu.window_y_triggered);
+}
+
+//write an oam entry given its screen coordinates
+fn write_sprite(ppu: &mut PPU, index: usize, (x, y): (i32, i32), tile_number: u8, attributes: u8) {
+    let address = 0xFE00 + index * 4;
+    ppu.write_byte(address, (y + 16) as u8);
+    ppu.write_byte(address + 1, (x + 8) as u8);
+    ppu.write_byte(address + 2, tile_number);
+    ppu.write_byte(address + 3, attributes);
+}
+
+#[test]
+fn sprites_are_rendered_with_priorities_and_flips() {
+    let mut ppu = create_dummy_ppu();
+    //tile 2 is all color 2, tile 3 and 5 all color 3, tile 4 has a single color 1 pixel top left
+    for row in 0..8 {
+        ppu.write_byte(0x8020 + row * 2 + 1, 0xFF);
+        ppu.write_byte(0x8030 + row * 2, 0xFF);
+        ppu.write_byte(0x8030 + row * 2 + 1, 0xFF);
+        ppu.write_byte(0x8050 + row * 2, 0xFF);
+        ppu.write_byte(0x8050 + row * 2 + 1, 0xFF);
+    }
+    ppu.write_byte(0x8040, 0x80);
+    //background tile (x 96-103, line 8) with color 2
+    ppu.write_byte(0x9820 + 12, 0x02);
+    ppu.write_byte(0xFF47, 0xE4);
+    ppu.write_byte(0xFF48, 0xE4);
+    ppu.write_byte(0xFF40, 0x93);
+    skip_blank_frame(&mut ppu);
+
+    //lower x wins, oam index breaks ties
+    write_sprite(&mut ppu, 0, (14, 8), 3, 0x00);
+    write_sprite(&mut ppu, 1, (10, 8), 2, 0x00);
+    write_sprite(&mut ppu, 2, (40, 8), 3, 0x00);
+    write_sprite(&mut ppu, 3, (40, 8), 2, 0x00);
+    //x flip moves the pixel to the right
+    write_sprite(&mut ppu, 4, (60, 8), 4, 0x20);
+    //partially off screen on both edges
+    write_sprite(&mut ppu, 5, (-4, 8), 3, 0x00);
+    write_sprite(&mut ppu, 6, (156, 8), 3, 0x00);
+    //behind background colors 1-3 only
+    write_sprite(&mut ppu, 7, (96, 8), 3, 0x80);
+    write_sprite(&mut ppu, 8, (110, 8), 3, 0x80);
+
+    step_until_line(&mut ppu, 9);
+    let pixel = |ppu: &PPU, x: u32| ppu.image_buffer.get_pixel(x, 8).0;
+    assert_eq!(pixel(&ppu, 9), POG_RED[0]);
+    assert_eq!(pixel(&ppu, 10), POG_RED[2]);
+    assert_eq!(pixel(&ppu, 17), POG_RED[2]);
+    assert_eq!(pixel(&ppu, 18), POG_RED[3]);
+    assert_eq!(pixel(&ppu, 21), POG_RED[3]);
+    assert_eq!(pixel(&ppu, 40), POG_RED[3]);
+    assert_eq!(pixel(&ppu, 60), POG_RED[0]);
+    assert_eq!(pixel(&ppu, 67), POG_RED[1]);
+    assert_eq!(pixel(&ppu, 0), POG_RED[3]);
+    assert_eq!(pixel(&ppu, 3), POG_RED[3]);
+    assert_eq!(pixel(&ppu, 4), POG_RED[0]);
+    assert_eq!(pixel(&ppu, 155), POG_RED[0]);
+    assert_eq!(pixel(&ppu, 159), POG_RED[3]);
+    assert_eq!(pixel(&ppu, 96), POG_RED[2]);
+    assert_eq!(pixel(&ppu, 110), POG_RED[3]);
+
+    //only 10 sprites per line, the ones with the lowest oam index
+    for index in 0..11 {
+        write_sprite(&mut ppu, 10 + index, (index as i32 * 8, 20), 3, 0x00);
+    }
+    step_until_line(&mut ppu, 21);
+    assert_eq!(ppu.image_buffer.get_pixel(79, 20).0, POG_RED[3]);
+    assert_eq!(ppu.image_buffer.get_pixel(80, 20).0, POG_RED[0]);
+
+    //8x16 sprites pair tile 4 (top) and 5 (bottom), y flip swaps them
+    ppu.write_byte(0xFF40, 0x97);
+    write_sprite(&mut ppu, 30, (20, 52), 5, 0x00);
+    write_sprite(&mut ppu, 31, (40, 52), 4, 0x40);
+    step_until_line(&mut ppu, 53);
+    assert_eq!(ppu.image_buffer.get_pixel(20, 52).0, POG_RED[1]);
+    assert_eq!(ppu.image_buffer.get_pixel(40, 52).0, POG_RED[3]);
+    step_until_line(&mut ppu, 61);
+    assert_eq!(ppu.image_buffer.get_pixel(20, 60).0, POG_RED[3]);
+    assert_eq!(ppu.image_buffer.get_pixel(40, 60).0, POG_RED[0]);
+    step_until_line(&mut ppu, 68);
+    assert_eq!(ppu.image_buffer.get_pixel(40, 67).0, POG_RED[1]);
+}
+
+//dots spent in mode 3 by the given line of the next frame
+fn line_pixel_transfer_length(ppu: &mut PPU, line: u32) -> u32 {
+    step_until_line(ppu, 0);
+    step_until_line(ppu, line);
+    step_until_mode(ppu, PpuMode::VRAM);
+    step_until_mode(ppu, PpuMode::HBlank)
+}
+
+#[test]
+fn layers_can_be_hidden_without_changing_lcdc() {
+    let mut ppu = create_dummy_ppu();
+    //tile 1 is all color 3, tile 2 all color 2 and tile 3 all color 1
+    for row in 0..8 {
+        ppu.write_byte(0x8010 + row * 2, 0xFF);
+        ppu.write_byte(0x8010 + row * 2 + 1, 0xFF);
+        ppu.write_byte(0x8020 + row * 2 + 1, 0xFF);
+        ppu.write_byte(0x8030 + row * 2, 0xFF);
+    }
+    //background made of tile 2, window of tile 1 from x 80
+    for address in 0x9800..0x9C00 {
+        ppu.write_byte(address, 0x02);
+        ppu.write_byte(address + 0x400, 0x01);
+    }
+    ppu.write_byte(0xFF47, 0xE4);
+    ppu.write_byte(0xFF48, 0xE4);
+    ppu.write_byte(0xFF4A, 0);
+    ppu.write_byte(0xFF4B, 7 + 80);
+    ppu.write_byte(0xFF40, 0xF3);
+    //sprite 0 covers sprite 1
+    write_sprite(&mut ppu, 0, (10, 8), 3, 0x00);
+    write_sprite(&mut ppu, 1, (10, 8), 1, 0x00);
+    skip_blank_frame(&mut ppu);
+
+    let length = line_pixel_transfer_length(&mut ppu, 8);
+    let pixel = |ppu: &PPU, x: u32| ppu.image_buffer.get_pixel(x, 8).0;
+    assert_eq!(pixel(&ppu, 0), POG_RED[2]);
+    assert_eq!(pixel(&ppu, 10), POG_RED[1]);
+    assert_eq!(pixel(&ppu, 100), POG_RED[3]);
+
+    ppu.render_mask.background = false;
+    assert_eq!(line_pixel_transfer_length(&mut ppu, 8), length);
+    assert_eq!(pixel(&ppu, 0), POG_RED[0]);
+    assert_eq!(pixel(&ppu, 10), POG_RED[1]);
+    assert_eq!(pixel(&ppu, 100), POG_RED[3]);
+
+    ppu.render_mask.window = false;
+    assert_eq!(line_pixel_transfer_length(&mut ppu, 8), length);
+    assert_eq!(pixel(&ppu, 100), POG_RED[0]);
+
+    //a hidden sprite still takes its fetch time and lets the ones below it through
+    ppu.render_mask.toggle_sprite(0);
+    assert!(!ppu.render_mask.is_sprite_visible(0));
+    assert_eq!(line_pixel_transfer_length(&mut ppu, 8), length);
+    assert_eq!(pixel(&ppu, 10), POG_RED[3]);
+
+    ppu.render_mask.sprites = false;
+    assert_eq!(line_pixel_transfer_length(&mut ppu, 8), length);
+    assert_eq!(pixel(&ppu, 10), POG_RED[0]);
+    assert_eq!(ppu.read_byte(0xFF40), 0xF3);
+
+    //on cgb the hidden layers show the backdrop of the game, not the dmg palette
+    ppu.cgb_mode = true;
+    ppu.write_byte(0xFF68, 0x80);
+    ppu.write_byte(0xFF69, 0x1F);
+    ppu.write_byte(0xFF69, 0x00);
+    line_pixel_transfer_length(&mut ppu, 8);
+    assert_eq!(pixel(&ppu, 0), [255, 0, 0, 255]);
+    assert_eq!(pixel(&ppu, 100), [255, 0, 0, 255]);
+}
+
+#[test]
+fn registers_are_recorded_for_each_line() {
+    let mut ppu = create_dummy_ppu();
+    ppu.write_byte(0xFF40, 0x91);
+    ppu.write_byte(0xFF47, 0xE4);
+    skip_blank_frame(&mut ppu);
+
+    //raster effects change registers between lines
+    step_until_line(&mut ppu, 10);
+    ppu.write_byte(0xFF43, 0x05);
+    step_until_line(&mut ppu, 20);
+    ppu.write_byte(0xFF47, 0x1B);
+    step_until_line(&mut ppu, 21);
+
+    let registers = ppu.line_registers;
+    assert_eq!(registers[9].scroll_x, 0x00);
+    assert_eq!(registers[10].scroll_x, 0x05);
+    assert_eq!(registers[19].background_palette, 0xE4);
+    assert_eq!(registers[20].background_palette, 0x1B);
+    assert_eq!(registers[20].lcd_control, 0x91);
+    //recorded during the pixel transfer
+    assert_eq!(registers[20].lcd_status & 0x03, PpuMode::VRAM as u8);
+
+    let dump = dump_line_registers(&ppu);
+    assert_eq!(dump.lines().count(), 144);
+    assert!(dump
+        .lines()
+        .nth(10)
+        .unwrap()
+        .starts_with("010* SCX 05 SCY 00 WX 00 WY 00 LCDC 91 STAT"));
+    assert!(dump.lines().nth(11).unwrap().starts_with("011  SCX 05"));
+}
+
+#[test]
+fn hd_packs_replace_tiles_and_dump_the_missing_ones() {
+    let directory = std::env::temp_dir().join("pog_boy_hd_pack_test");
+    let (pack_directory, dump_directory) = (directory.join("pack"), directory.join("dump"));
+    let _ = std::fs::remove_dir_all(&directory);
+    std::fs::create_dir_all(&pack_directory).unwrap();
+
+    let mut ppu = create_dummy_ppu();
+    //tile 1 is all color 3, tile 2 has color 1 on its left half
+    for row in 0..8 {
+        ppu.write_byte(0x8010 + row * 2, 0xFF);
+        ppu.write_byte(0x8010 + row * 2 + 1, 0xFF);
+        ppu.write_byte(0x8020 + row * 2, 0xF0);
+    }
+    ppu.write_byte(0x9800, 0x01);
+    ppu.write_byte(0xFF47, 0xE4);
+    ppu.write_byte(0xFF48, 0xE4);
+    ppu.write_byte(0xFF40, 0x93);
+    //x flipped, the color 1 half is on the right
+    write_sprite(&mut ppu, 0, (80, 0), 2, 0x20);
+
+    //2x replacements: tile 1 is red with a transparent pixel,
+    //tile 2 is green with a blue column
+    let (red, green, blue) = ([255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255]);
+    let mut background_tile = RgbaImage::from_pixel(16, 16, Rgba(red));
+    background_tile.put_pixel(1, 1, Rgba([0, 0, 0, 0]));
+    let background_palette = ppu.tile_palette(PaletteLayer::Background, 0);
+    let background_hash = tile_hash(&ppu.tile_set[1], &background_palette);
+    background_tile
+        .save(pack_directory.join(tile_file_name(background_hash)))
+        .unwrap();
+    let sprite_tile = RgbaImage::from_fn(16, 16, |x, _| Rgba(if x == 7 { blue } else { green }));
+    let sprite_hash = tile_hash(&ppu.tile_set[2], &ppu.tile_palette(PaletteLayer::Obj0, 0));
+    sprite_tile
+        .save(pack_directory.join(tile_file_name(sprite_hash)))
+        .unwrap();
+
+    let mut hd_pack = HdPack::load(Some(&pack_directory), Some(&dump_directory)).unwrap();
+    assert_eq!((hd_pack.scale, hd_pack.len()), (2, 2));
+    hd_pack.attach(&mut ppu);
+    skip_blank_frame(&mut ppu);
+    step_until_line(&mut ppu, 8);
+
+    let frame = hd_pack.compose(&mut ppu);
+    assert_eq!(frame.dimensions(), (320, 288));
+    assert_eq!(frame.get_pixel(0, 0).0, red);
+    assert_eq!(frame.get_pixel(15, 15).0, red);
+    assert_eq!(frame.get_pixel(1, 1).0, POG_RED[3]);
+    assert_eq!(frame.get_pixel(16, 0).0, POG_RED[0]);
+    //screen x 84 is column 3 of the flipped sprite, its replacement is flipped too
+    assert_eq!(frame.get_pixel(168, 0).0, blue);
+    assert_eq!(frame.get_pixel(169, 1).0, green);
+    assert_eq!(frame.get_pixel(174, 0).0, green);
+    //transparent sprite pixels show the background
+    assert_eq!(frame.get_pixel(166, 0).0, POG_RED[0]);
+
+    //only the empty background tile has no replacement
+    let empty_hash = tile_hash(&ppu.tile_set[0], &background_palette);
+    let dumped: Vec<_> = std::fs::read_dir(&dump_directory).unwrap().collect();
+    assert_eq!(dumped.len(), 1);
+    let dumped_tile = image::open(dump_directory.join(tile_file_name(empty_hash))).unwrap();
+    assert_eq!(dumped_tile.to_rgba8(), RgbaImage::from_pixel(8, 8, Rgba(POG_RED[0])));
+
+    //every replacement must have the same size
+    RgbaImage::new(10, 10)
+        .save(pack_directory.join(tile_file_name(empty_hash)))
+        .unwrap();
+    assert!(HdPack::load(Some(&pack_directory), None).is_err());
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn hd_pack_replacements_survive_a_palette_change() {
+    let directory = std::env::temp_dir().join("pog_boy_hd_pack_palette_test");
+    let (pack_directory, dump_directory) = (directory.join("pack"), directory.join("dump"));
+    let _ = std::fs::remove_dir_all(&directory);
+    std::fs::create_dir_all(&pack_directory).unwrap();
+
+    let mut ppu = create_dummy_ppu();
+    //tile 1 is all color 3
+    for address in 0x8010..0x8020 {
+        ppu.write_byte(address, 0xFF);
+    }
+    ppu.write_byte(0x9800, 0x01);
+    ppu.write_byte(0xFF47, 0xE4);
+    ppu.write_byte(0xFF40, 0x91);
+    let red = [255, 0, 0, 255];
+    let hash = tile_hash(&ppu.tile_set[1], &ppu.tile_palette(PaletteLayer::Background, 0));
+    RgbaImage::from_pixel(16, 16, Rgba(red))
+        .save(pack_directory.join(tile_file_name(hash)))
+        .unwrap();
+    let mut hd_pack = HdPack::load(Some(&pack_directory), Some(&dump_directory)).unwrap();
+    hd_pack.attach(&mut ppu);
+
+    //dmg tiles are hashed with their shades, so another output palette keeps the replacement
+    //and doesn't dump the other tiles again
+    let dmg_green = palette_presets()
+        .into_iter()
+        .find(|palette| palette.name == "dmg green")
+        .unwrap();
+    for palette in [ppu.palette.clone(), dmg_green] {
+        ppu.palette = palette;
+        skip_blank_frame(&mut ppu);
+        step_until_line(&mut ppu, 8);
+        let frame = hd_pack.compose(&mut ppu);
+        assert_eq!(frame.get_pixel(0, 0).0, red);
+        assert_eq!(frame.get_pixel(16, 0).0, ppu.palette.background[0]);
+        assert_eq!(std::fs::read_dir(&dump_directory).unwrap().count(), 1);
+    }
+    //a different bgp changes the hash
+    ppu.write_byte(0xFF47, 0x1B);
+    assert_ne!(
+        tile_hash(&ppu.tile_set[1], &ppu.tile_palette(PaletteLayer::Background, 0)),
+        hash
+    );
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn sprite_limit_can_be_lifted_without_changing_timing() {
+    let mut ppu = create_dummy_ppu();
+    //tile 3 is all color 3
+    for address in 0x8030..0x8040 {
+        ppu.write_byte(address, 0xFF);
+    }
+    ppu.write_byte(0xFF48, 0xE4);
+    ppu.write_byte(0xFF40, 0x93);
+    for index in 0..12 {
+        write_sprite(&mut ppu, index, (index as i32 * 8, 20), 3, 0x00);
+    }
+    skip_blank_frame(&mut ppu);
+
+    let length = line_pixel_transfer_length(&mut ppu, 20);
+    assert_eq!(ppu.image_buffer.get_pixel(79, 20).0, POG_RED[3]);
+    assert_eq!(ppu.image_buffer.get_pixel(80, 20).0, POG_RED[0]);
+    assert_eq!(ppu.image_buffer.get_pixel(95, 20).0, POG_RED[0]);
+
+    //the 2 extra sprites are drawn but don't stall the fetcher
+    ppu.unlimited_sprites = true;
+    assert_eq!(line_pixel_transfer_length(&mut ppu, 20), length);
+    assert_eq!(ppu.image_buffer.get_pixel(80, 20).0, POG_RED[3]);
+    assert_eq!(ppu.image_buffer.get_pixel(95, 20).0, POG_RED[3]);
+    assert_eq!(ppu.image_buffer.get_pixel(96, 20).0, POG_RED[0]);
+}
+
+//number of stat interrupts requested in the given amount of dots
+fn count_stat_interrupts(ppu: &mut PPU, dots: u32) -> u32 {
+    (0..dots).filter(|_| ppu.step(1).2).count() as u32
+}
+
+#[test]
+fn stat_register_and_interrupts_are_modelled() {
+    let mut ppu = create_dummy_ppu();
+    ppu.write_byte(0xFF40, 0x91);
+    step_until_line(&mut ppu, 3);
+
+    //mode and ly=lyc flag are read only, bit 7 always reads 1
+    ppu.write_byte(0xFF45, 3);
+    ppu.write_byte(0xFF41, 0x07);
+    assert_eq!(ppu.read_byte(0xFF41), 0x80 | 0x04 | PpuMode::OAM as u8);
+    ppu.write_byte(0xFF45, 4);
+    assert_eq!(ppu.read_byte(0xFF41), 0x80 | PpuMode::OAM as u8);
+    //the stat write happened while ly == lyc, see the dmg quirk below
+    assert!(ppu.step(1).2);
+
+    //hblank and oam sources are or-ed, so oam right after hblank doesn't fire again
+    ppu.write_byte(0xFF41, 0x08);
+    assert_eq!(count_stat_interrupts(&mut ppu, 456 * 2), 2);
+    ppu.write_byte(0xFF41, 0x28);
+    step_until_line(&mut ppu, 10);
+    assert_eq!(count_stat_interrupts(&mut ppu, 456), 1);
+    ppu.write_byte(0xFF41, 0x20);
+    assert_eq!(count_stat_interrupts(&mut ppu, 456), 1);
+
+    //lyc interrupt fires once when ly reaches lyc
+    ppu.write_byte(0xFF45, 20);
+    ppu.write_byte(0xFF41, 0x40);
+    step_until_line(&mut ppu, 19);
+    assert_eq!(count_stat_interrupts(&mut ppu, 456), 1);
+    assert_eq!(ppu.read_byte(0xFF41) & 0x04, 0x04);
+    assert_eq!(count_stat_interrupts(&mut ppu, 456), 0);
+    assert_eq!(ppu.read_byte(0xFF41) & 0x04, 0x00);
+
+    //dmg quirk, writing stat during hblank requests an interrupt
+    ppu.write_byte(0xFF41, 0x00);
+    step_until_mode(&mut ppu, PpuMode::HBlank);
+    assert!(!ppu.step(1).2);
+    ppu.write_byte(0xFF41, 0x00);
+    assert!(ppu.step(1).2);
+    assert!(!ppu.step(1).2);
+
+    //the cgb doesn't have the quirk
+    ppu.cgb_mode = true;
+    step_until_line(&mut ppu, 30);
+    step_until_mode(&mut ppu, PpuMode::HBlank);
+    ppu.write_byte(0xFF41, 0x00);
+    assert!(!ppu.step(1).2);
+}
+
+#[test]
+fn lcd_can_be_turned_on_and_off() {
+    let mut ppu = create_dummy_ppu();
+    let mut mmu = create_dummy_mmu(&mut ppu);
+    mmu.write_byte(0xFF47, 0xFF);
+
+    //ly is held at 0 while the lcd is off
+    mmu.PPU.step(456 * 10);
+    assert_eq!(mmu.read_byte(0xFF44), 0);
+    assert_eq!(mmu.PPU.get_current_mode(), PpuMode::HBlank);
+
+    //the first frame is left blank
+    mmu.write_byte(0xFF40, 0x91);
+    assert_eq!(mmu.PPU.get_current_mode(), PpuMode::OAM);
+    mmu.PPU.step(456 * 10);
+    assert_eq!(mmu.read_byte(0xFF44), 10);
+    assert_eq!(mmu.PPU.image_buffer.get_pixel(0, 0).0, POG_RED[0]);
+    step_until_mode(mmu.PPU, PpuMode::VBlank);
+    step_until_line(mmu.PPU, 1);
+    assert_eq!(mmu.PPU.image_buffer.get_pixel(0, 0).0, POG_RED[3]);
+
+    mmu.write_byte(0xFF40, 0x11);
+    assert_eq!(mmu.read_byte(0xFF44), 0);
+    assert_eq!(mmu.PPU.image_buffer.get_pixel(0, 0).0, POG_RED[0]);
+}
+
+#[test]
+fn vram_and_oam_are_blocked_by_ppu_mode() {
+    let mut ppu = create_dummy_ppu();
+    let mut mmu = create_dummy_mmu(&mut ppu);
+    mmu.write_byte(0x8000, 0x12);
+    mmu.write_byte(0xFE00, 0x34);
+    mmu.write_byte(0xC000, 0x56);
+    mmu.write_byte(0xFF40, 0x91);
+
+    //oam scan
+    assert_eq!(mmu.read_byte(0x8000), 0x12);
+    assert_eq!(mmu.read_byte(0xFE00), 0xFF);
+    mmu.write_byte(0xFE00, 0x00);
+
+    //pixel transfer
+    step_until_mode(mmu.PPU, PpuMode::VRAM);
+    assert_eq!(mmu.read_byte(0x8000), 0xFF);
+    assert_eq!(mmu.read_byte(0xFE00), 0xFF);
+    mmu.write_byte(0x8000, 0x00);
+
+    //dma ignores blocking
+    mmu.write_byte(0xFF46, 0xC0);
+
+    step_until_mode(mmu.PPU, PpuMode::HBlank);
+    assert_eq!(mmu.read_byte(0x8000), 0x12);
+    assert_eq!(mmu.read_byte(0xFE00), 0x56);
+}
+
+#[test]
+fn palettes_can_be_selected_per_layer() {
+    let user_palettes = parse_user_palettes(
+        r##"[{
+            "name": "mine",
+            "background": ["#FFFFFF", "#AAAAAA", "#555555", "#000000"],
+            "obj_1": ["FF0000", "#00FF00", "#0000FF", "#FFFF00"]
+        }]"##,
+    )
+    .unwrap();
+    assert_eq!(user_palettes[0].obj_0, user_palettes[0].background);
+    assert_eq!(user_palettes[0].obj_1[1], [0, 255, 0, 255]);
+    assert!(parse_user_palettes(r##"[{"name": "bad", "background": ["#FFF", "", "", ""]}]"##).is_err());
+
+    let mut palette_selector = PaletteSelector::new(user_palettes);
+    assert_eq!(palette_selector.current().background, POG_RED);
+    assert!(palette_selector.select("DMG green"));
+    assert!(!palette_selector.select("missing"));
+    assert_eq!(palette_selector.next().name, "pocket grey");
+    assert!(palette_selector.select("mine"));
+    assert_eq!(palette_selector.next().name, "pog red");
+    palette_selector.select("mine");
+
+    let mut ppu = create_dummy_ppu();
+    ppu.palette = palette_selector.current().clone();
+    ppu.write_byte(0xFF47, 0xE4);
+    ppu.write_byte(0xFF49, 0x1B);
+    assert_eq!(
+        ppu.get_color_from_palette(TilePixelValue::One, PaletteLayer::Background),
+        [0xAA, 0xAA, 0xAA, 255]
+    );
+    assert_eq!(
+        ppu.get_color_from_palette(TilePixelValue::One, PaletteLayer::Obj1),
+        [0, 0, 255, 255]
+    );
+}
+
+#[test]
+fn shade_0_is_the_lightest_color_of_every_preset() {
+    let luminance = |color: &[u8; 4]| {
+        299 * color[0] as u32 + 587 * color[1] as u32 + 114 * color[2] as u32
+    };
+    //the cgb right+b palette is a negative, so only the presets are checked
+    for palette in palette_presets() {
+        for palette_layer in [PaletteLayer::Background, PaletteLayer::Obj0, PaletteLayer::Obj1] {
+            let colors = palette.colors(palette_layer);
+            assert!(
+                colors[1..].iter().all(|color| luminance(color) <= luminance(&colors[0])),
+                "{} {:?}",
+                palette.name,
+                palette_layer
+            );
+        }
+    }
+}
+
+#[test]
+fn dmg_games_are_colorized_like_the_cgb_boot_rom() {
+    let mut cartridge_info = CartridgeInfo::from_rom(&create_dummy_rom()).unwrap();
+    assert_eq!(cartridge_info.title_checksum(), 0xD0);
+    assert_eq!(title_index(&cartridge_info), None);
+
+    //0x46 is shared by two titles, told apart by the fourth letter
+    cartridge_info.set_title("ABCE;");
+    assert_eq!(title_index(&cartridge_info), Some(66));
+    cartridge_info.set_title("ABCR.");
+    assert_eq!(title_index(&cartridge_info), Some(80));
+    cartridge_info.set_title("ABCX(");
+    assert_eq!(title_index(&cartridge_info), None);
+
+    //only nintendo titles are looked up
+    cartridge_info.set_title("ABCE;");
+    cartridge_info.set_old_licensee_code(0x33);
+    cartridge_info.set_new_licensee_code(*b"08");
+    assert_eq!(title_index(&cartridge_info), None);
+    cartridge_info.set_new_licensee_code(*b"01");
+    assert_eq!(title_index(&cartridge_info), Some(66));
+
+    //titles missing from the table get the right+a palette
+    cartridge_info.set_new_licensee_code(*b"08");
+    let mut palette_selector = PaletteSelector::new(vec![]);
+    palette_selector.insert(compatibility_palette(&cartridge_info));
+    assert_eq!(palette_selector.current().name, "right+a");
+    assert_eq!(palette_selector.current().background[2], [0x00, 0x63, 0xC5, 255]);
+    assert_eq!(palette_selector.current().obj_0[2], [0x94, 0x3A, 0x3A, 255]);
+
+    //red background and obj 1, green obj 0
+    cartridge_info.set_title("POKEMON RED");
+    cartridge_info.set_old_licensee_code(0x01);
+    assert_eq!(cartridge_info.title_checksum(), 0x14);
+    palette_selector.insert(compatibility_palette(&cartridge_info));
+    let pokemon_red = palette_selector.current().clone();
+    assert_eq!(pokemon_red.name, "cgb title palette 13");
+    assert_eq!(pokemon_red.background[1], [0xFF, 0x84, 0x84, 255]);
+    assert_eq!(pokemon_red.obj_1, pokemon_red.background);
+    assert_eq!(pokemon_red.obj_0[1], [0x7B, 0xFF, 0x31, 255]);
+    assert_eq!(pokemon_red.obj_0[3], [0, 0, 0, 255]);
+
+    //some combinations start on the last color of the previous palette
+    cartridge_info.set_title("ABCE;");
+    assert_eq!(compatibility_palette(&cartridge_info).obj_0[0], [0, 0, 0, 255]);
+    assert_eq!(compatibility_palette(&cartridge_info).obj_0[1], [0xFF, 0xFF, 0xFF, 255]);
+
+    //button combinations override the automatic palette
+    assert!(palette_selector.select("LEFT+B"));
+    assert_eq!(palette_selector.current().background[1], [0xA5, 0xA5, 0xA5, 255]);
+}
+
+#[test]
+fn cgb_mode_uses_vram_banks_attributes_and_color_palettes() {
+    let mut ppu = create_dummy_ppu();
+    ppu.cgb_mode = true;
+    //tile 1 is all color 1 in bank 0, has color 3 in the leftmost column in bank 1
+    //tiles 2 and 3 are all color 2 and 3
+    for row in 0..8 {
+        ppu.write_byte(0x8010 + row * 2, 0xFF);
+        ppu.write_byte(0x8020 + row * 2 + 1, 0xFF);
+        ppu.write_byte(0x8030 + row * 2, 0xFF);
+        ppu.write_byte(0x8030 + row * 2 + 1, 0xFF);
+    }
+    ppu.write_byte(0xFF4F, 1);
+    for row in 0..8 {
+        ppu.write_byte(0x8010 + row * 2, 0x80);
+        ppu.write_byte(0x8010 + row * 2 + 1, 0x80);
+    }
+    assert_eq!(ppu.read_byte(0xFF4F), 0xFF);
+    assert_eq!(ppu.read_byte(0x8010), 0x80);
+
+    //attributes live in bank 1: palette 2 from bank 1, the same x flipped, palette 0 with priority
+    ppu.write_byte(0x9801, 0x0A);
+    ppu.write_byte(0x9802, 0x2A);
+    ppu.write_byte(0x9806, 0x80);
+    ppu.write_byte(0xFF4F, 0);
+    assert_eq!(ppu.read_byte(0xFF4F), 0xFE);
+    assert_eq!(ppu.read_byte(0x8010), 0xFF);
+    for tile in [0, 1, 2, 6] {
+        ppu.write_byte(0x9800 + tile, 0x01);
+    }
+
+    //bg palette 0 color 1 is red, bg palette 2 color 3 is blue, everything else stays white
+    ppu.write_byte(0xFF68, 0x80 | 2);
+    ppu.write_byte(0xFF69, 0x1F);
+    ppu.write_byte(0xFF69, 0x00);
+    ppu.write_byte(0xFF68, 0x80 | 22);
+    ppu.write_byte(0xFF69, 0x00);
+    ppu.write_byte(0xFF69, 0x7C);
+    assert_eq!(ppu.read_byte(0xFF68), 0xC0 | 24);
+    ppu.write_byte(0xFF68, 22);
+    assert_eq!(ppu.read_byte(0xFF69), 0x00);
+    assert_eq!(ppu.read_byte(0xFF69), 0x00);
+    //obj palette 0 color 3 is red, obj palette 1 color 2 is green
+    ppu.write_byte(0xFF6A, 6);
+    ppu.write_byte(0xFF6B, 0x1F);
+    ppu.write_byte(0xFF6A, 0x80 | 7);
+    ppu.write_byte(0xFF6B, 0x00);
+    ppu.write_byte(0xFF6A, 12);
+    ppu.write_byte(0xFF6B, 0xE0);
+    ppu.write_byte(0xFF6A, 13);
+    ppu.write_byte(0xFF6B, 0x03);
+
+    //the sprite with the lowest oam index wins, even when fetched later
+    write_sprite(&mut ppu, 0, (34, 0), 2, 0x01);
+    write_sprite(&mut ppu, 1, (30, 0), 3, 0x00);
+    write_sprite(&mut ppu, 2, (48, 0), 2, 0x01);
+    ppu.write_byte(0xFF40, 0x93);
+    skip_blank_frame(&mut ppu);
+    step_until_line(&mut ppu, 1);
+
+    let (white, red, green, blue) = (
+        [255, 255, 255, 255],
+        [255, 0, 0, 255],
+        [0, 255, 0, 255],
+        [0, 0, 255, 255],
+    );
+    let pixel = |ppu: &PPU, x: u32, y: u32| ppu.image_buffer.get_pixel(x, y).0;
+    assert_eq!(pixel(&ppu, 0, 0), red);
+    assert_eq!(pixel(&ppu, 8, 0), blue);
+    assert_eq!(pixel(&ppu, 9, 0), white);
+    assert_eq!(pixel(&ppu, 16, 0), white);
+    assert_eq!(pixel(&ppu, 23, 0), blue);
+    assert_eq!(pixel(&ppu, 32, 0), red);
+    assert_eq!(pixel(&ppu, 34, 0), green);
+    assert_eq!(pixel(&ppu, 41, 0), green);
+    //the map attribute priority keeps the background over the sprite
+    assert_eq!(pixel(&ppu, 48, 0), red);
+
+    //opri bit 0 goes back to the dmg x priority
+    ppu.write_byte(0xFF6C, 0x01);
+    step_until_line(&mut ppu, 2);
+    assert_eq!(pixel(&ppu, 34, 1), red);
+    assert_eq!(pixel(&ppu, 40, 1), green);
+
+    //lcdc bit 0 clear puts every sprite over the background, which is still drawn
+    ppu.write_byte(0xFF40, 0x92);
+    step_until_line(&mut ppu, 3);
+    assert_eq!(pixel(&ppu, 48, 2), green);
+    assert_eq!(pixel(&ppu, 0, 2), red);
+}
+
+#[test]
+fn frames_can_be_color_corrected_and_blended() {
+    //white stays white, saturated colors are muted
+    assert_eq!(ColorCorrection::None.correct([255, 0, 0, 255]), [255, 0, 0, 255]);
+    assert_eq!(ColorCorrection::Cgb.correct([255, 255, 255, 255]), [255, 255, 255, 255]);
+    assert_eq!(ColorCorrection::Cgb.correct([255, 0, 0, 255]), [207, 0, 48, 255]);
+    let agb_red = ColorCorrection::Agb.correct([255, 0, 0, 255]);
+    assert!(agb_red[0] < 255 && agb_red[1] > 0 && agb_red[2] > 0, "{:?}", agb_red);
+    assert_eq!(ColorCorrection::from_name("AGB"), Some(ColorCorrection::Agb));
+    assert_eq!(FrameBlending::from_name("blur"), None);
+
+    let black = RgbaImage::from_pixel(2, 2, Rgba([0, 0, 0, 255]));
+    let white = RgbaImage::from_pixel(2, 2, Rgba([255, 255, 255, 255]));
+
+    let mut frame_filter = FrameFilter::new(ColorCorrection::None, FrameBlending::Average);
+    assert_eq!(frame_filter.apply(&black), black);
+    assert_eq!(frame_filter.apply(&white).get_pixel(0, 0).0, [128, 128, 128, 255]);
+    assert_eq!(frame_filter.apply(&white), white);
+
+    //ghosting keeps part of every previous frame
+    let mut frame_filter = FrameFilter::new(ColorCorrection::None, FrameBlending::Ghosting);
+    frame_filter.apply(&black);
+    let first = frame_filter.apply(&white).get_pixel(0, 0).0[0];
+    let second = frame_filter.apply(&white).get_pixel(0, 0).0[0];
+    assert_eq!(first, 140);
+    assert!(first < second && second < 255, "{} {}", first, second);
+}
+
+//a reset pulse, 128 bits lsb first and a 0 stop bit, every bit is followed by both lines high
+fn send_sgb_packet(mmu: &mut MMU, packet: [u8; 16]) {
+    mmu.write_byte(0xFF00, 0x00);
+    mmu.write_byte(0xFF00, 0x30);
+    for byte in packet {
+        for bit in 0..8 {
+            mmu.write_byte(0xFF00, if (byte >> bit) & 1 == 1 { 0x10 } else { 0x20 });
+            mmu.write_byte(0xFF00, 0x30);
+        }
+    }
+    mmu.write_byte(0xFF00, 0x20);
+    mmu.write_byte(0xFF00, 0x30);
+}
+
+#[test]
+fn sgb_packets_set_palettes_attributes_and_the_border() {
+    let mut ppu = create_dummy_ppu();
+    let mut mmu = create_dummy_mmu(&mut ppu);
+    mmu.sgb = Some(Sgb::new(&POG_RED));
+    let (red, green, blue, white, black) = (
+        [255, 0, 0, 255],
+        [0, 255, 0, 255],
+        [0, 0, 255, 255],
+        [255, 255, 255, 255],
+        [0, 0, 0, 255],
+    );
+
+    //pal01: red backdrop, palette 0 is green, blue, black and palette 1 is white
+    send_sgb_packet(
+        &mut mmu,
+        [0x01, 0x1F, 0x00, 0xE0, 0x03, 0x00, 0x7C, 0x00, 0x00, 0xFF, 0x7F, 0xFF, 0x7F, 0xFF, 0x7F, 0],
+    );
+    //attr_blk: only the inside is set, so the border of the 1x1 block at 0,0 takes palette 1
+    send_sgb_packet(&mut mmu, [0x21, 0x01, 0x01, 0x01, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    mmu.PPU.shade_buffer[0] = 1;
+    mmu.PPU.shade_buffer[8] = 1;
+    mmu.PPU.shade_buffer[9] = 2;
+
+    let frame = mmu.sgb.as_mut().unwrap().compose_frame(&mmu.PPU);
+    assert_eq!(frame.dimensions(), (SGB_SCREEN_WIDTH, SGB_SCREEN_HEIGHT));
+    assert_eq!(frame.get_pixel(48, 40).0, white);
+    assert_eq!(frame.get_pixel(56, 40).0, green);
+    assert_eq!(frame.get_pixel(57, 40).0, blue);
+    assert_eq!(frame.get_pixel(58, 40).0, red);
+    //the empty border shows the backdrop
+    assert_eq!(frame.get_pixel(0, 0).0, red);
+
+    //mask_en black hides the game screen
+    send_sgb_packet(&mut mmu, [0xB9, 0x02, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    let frame = mmu.sgb.as_mut().unwrap().compose_frame(&mmu.PPU);
+    assert_eq!(frame.get_pixel(56, 40).0, black);
+
+    //chr_trn and pct_trn read vram on the next frame, every byte of it is 1 here:
+    //tiles have color 15 in their last column and the map uses tile 1 with border palette 4
+    send_sgb_packet(&mut mmu, [0x99, 0x00, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    mmu.sgb.as_mut().unwrap().compose_frame(&mmu.PPU);
+    send_sgb_packet(&mut mmu, [0xA1, 0x00, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    let frame = mmu.sgb.as_mut().unwrap().compose_frame(&mmu.PPU);
+    assert_eq!(frame.get_pixel(0, 0).0, red);
+    assert_eq!(frame.get_pixel(7, 0).0, [8, 66, 0, 255]);
+}
+
+#[test]
+fn sgb_multiplayer_request_cycles_the_joypad_id() {
+    let mut ppu = create_dummy_ppu();
+    let mut mmu = create_dummy_mmu(&mut ppu);
+    mmu.sgb = Some(Sgb::new(&POG_RED));
+    mmu.write_byte(0xFF00, 0x30);
+    assert_eq!(mmu.read_byte(0xFF00), 0xFF);
+
+    send_sgb_packet(&mut mmu, [0x89, 0x01, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(mmu.read_byte(0xFF00), 0xFF);
+    //p15 going back high selects the next joypad
+    for expected_id in [0xFE, 0xFF, 0xFE] {
+        mmu.write_byte(0xFF00, 0x10);
+        mmu.write_byte(0xFF00, 0x30);
+        assert_eq!(mmu.read_byte(0xFF00), expected_id);
+    }
+}
+
+#[test]
+fn frames_can_be_upscaled_with_an_overlay() {
+    let (white, black) = (Rgba([255, 255, 255, 255]), Rgba([0, 0, 0, 255]));
+    //a black staircase going down to the right on white
+    let mut frame = RgbaImage::from_pixel(4, 4, white);
+    for (x, y) in [(0, 0), (1, 1), (2, 2), (3, 3), (0, 1), (1, 2), (2, 3), (0, 2), (1, 3), (0, 3)] {
+        frame.put_pixel(x, y, black);
+    }
+
+    let nearest = Upscaler::new(Scaler::Nearest, 3, Overlay::None).apply(&frame);
+    assert_eq!(nearest.dimensions(), (12, 12));
+    assert_eq!(*nearest.get_pixel(5, 5), black);
+    assert_eq!(*nearest.get_pixel(6, 5), white);
+    assert_eq!(Upscaler::new(Scaler::None, 3, Overlay::None).apply(&frame), frame);
+
+    //scale2x cuts the corner of the white pixels above the steps
+    let scale2x = Upscaler::new(Scaler::Scale2x, 1, Overlay::None).apply(&frame);
+    assert_eq!(scale2x.dimensions(), (8, 8));
+    assert_eq!(*scale2x.get_pixel(2, 1), black);
+    assert_eq!(*scale2x.get_pixel(3, 0), white);
+    let scale3x = Upscaler::new(Scaler::Scale3x, 1, Overlay::None).apply(&frame);
+    assert_eq!(scale3x.dimensions(), (12, 12));
+
+    //hq2x interpolates the white pixel above the first step towards the black ones around it
+    let hq2x = Upscaler::new(Scaler::Hq2x, 4, Overlay::None).apply(&frame);
+    assert_eq!(hq2x.dimensions(), (8, 8));
+    assert_eq!(hq2x.get_pixel(2, 0).0[0], 191);
+    assert_eq!(hq2x.get_pixel(2, 1).0[0], 63);
+    assert_eq!(hq2x.get_pixel(3, 2).0[0], 127);
+    assert_eq!(*hq2x.get_pixel(6, 0), white);
+    //hq4x draws the steps as a smooth line through the middle of the diagonal pixels
+    assert_eq!(Scaler::from_name("hq4x"), Some(Scaler::Hq4x));
+    let hq4x = Upscaler::new(Scaler::Hq4x, 1, Overlay::None).apply(&frame);
+    assert_eq!(hq4x.dimensions(), (16, 16));
+    assert_eq!(hq4x.get_pixel(4, 0).0[0], 191);
+    assert_eq!(hq4x.get_pixel(4, 1).0[0], 63);
+    assert_eq!(hq4x.get_pixel(6, 4).0[0], 127);
+    assert_eq!(*hq4x.get_pixel(5, 4), black);
+    assert_eq!(*hq4x.get_pixel(7, 4), white);
+
+    //xbrz turns the staircase into a line going through the corner of that white pixel
+    for factor in 2..=6 {
+        let xbrz = Upscaler::new(Scaler::Xbrz, factor, Overlay::None).apply(&frame);
+        assert_eq!(xbrz.dimensions(), (4 * factor, 4 * factor));
+        assert!(xbrz.get_pixel(factor, factor - 1).0[0] <= 63, "{}", factor);
+        assert_eq!(*xbrz.get_pixel(factor * 3, 0), white, "{}", factor);
+    }
+    let xbrz = Upscaler::new(Scaler::Xbrz, 4, Overlay::None).apply(&frame);
+    assert_eq!(*xbrz.get_pixel(4, 3), black);
+    assert_eq!(xbrz.get_pixel(5, 3).0[0], 63);
+    assert_eq!(xbrz.get_pixel(6, 4).0[0], 127);
+
+    //the grid darkens the last row and column of every pixel, scanlines only the row
+    let grid = Upscaler::new(Scaler::Nearest, 2, Overlay::Grid).apply(&frame);
+    assert_eq!(grid.get_pixel(2, 0).0, [255, 255, 255, 255]);
+    assert_eq!(grid.get_pixel(3, 0).0, [191, 191, 191, 255]);
+    let scanlines = Upscaler::new(Scaler::Nearest, 2, Overlay::Scanlines).apply(&frame);
+    assert_eq!(scanlines.get_pixel(3, 0).0, [255, 255, 255, 255]);
+    assert_eq!(scanlines.get_pixel(3, 1).0, [153, 153, 153, 255]);
+    //an overlay alone still scales by 2 to have room for its lines
+    let unscaled_grid = Upscaler::new(Scaler::None, 1, Overlay::Grid).apply(&frame);
+    assert_eq!(unscaled_grid.dimensions(), (8, 8));
+    assert_eq!(unscaled_grid.get_pixel(3, 0).0, [191, 191, 191, 255]);
+}
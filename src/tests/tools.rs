@@ -0,0 +1,40 @@
+use crate::memory::cartridge::cartridge::{compute_global_checksum, CartridgeInfo};
+use crate::tests::factories::create_dummy_rom;
+use crate::tools::fix::fix::{fix_rom, FixOptions};
+
+#[test]
+fn header_fixer_rewrites_header() {
+    let mut rom = create_dummy_rom();
+    rom.truncate(0x6000);
+
+    let options = FixOptions {
+        title: Some("HOMEBREW".to_owned()),
+        cgb_flag: Some(0x80),
+        sgb: true,
+        cartridge_type: Some(0x1B),
+        ram_size: Some(0x03),
+        pad_value: Some(0xFF),
+        fix_logo: true,
+        fix_header_checksum: true,
+        fix_global_checksum: true,
+        ..FixOptions::default()
+    };
+    fix_rom(&mut rom, &options).unwrap();
+
+    assert_eq!(rom.len(), 0x8000); //padded to the next valid size
+    assert_eq!(rom[0x7FFF], 0xFF);
+
+    let cartridge_info = CartridgeInfo::from_rom(&rom).unwrap();
+    assert_eq!(cartridge_info.game_title(), "HOMEBREW");
+    assert_eq!(cartridge_info.supports_cgb(), true);
+    assert_eq!(cartridge_info.is_cgb_only(), false);
+    assert_eq!(cartridge_info.supports_sgb(), true);
+    assert_eq!(cartridge_info.mapper_name(), "MBC5+RAM+BATTERY");
+    assert_eq!(cartridge_info.rom_size_bytes(), Some(0x8000));
+    assert_eq!(cartridge_info.ram_size_bytes(), Some(0x8000));
+    assert_eq!(cartridge_info.is_nintendo_logo_valid(), true);
+    assert_eq!(cartridge_info.is_header_checksum_valid(), true);
+    assert_eq!(cartridge_info.global_checksum(), compute_global_checksum(&rom));
+    assert_eq!(rom[0x104], 0xCE); //logo is written as bytes, not words
+    assert_eq!(rom[0x105], 0xED);
+}
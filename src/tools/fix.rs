@@ -0,0 +1,205 @@
+pub mod fix {
+    use crate::memory::cartridge::cartridge::{rom_size_code, CartridgeInfo};
+    use crate::memory::licensee::licensee::CARTRIDGE_TYPES;
+    use std::fs;
+    use std::process;
+
+    const USAGE: &str = "usage: pog_boy fix [options] <rom>
+    -t, --title <title>          game title, up to 15 characters
+    -c, --cgb-compatible         set the cgb flag to 0x80 (dmg and cgb)
+    -C, --cgb-only               set the cgb flag to 0xC0 (cgb only)
+    -s, --sgb-compatible         set the sgb flag (also sets the old licensee to 0x33)
+    -m, --mbc-type <value>       cartridge type, as a number or a name like MBC1+RAM+BATTERY
+    -r, --ram-size <value>       ram size code
+    -l, --old-licensee <value>   old licensee code
+    -k, --new-licensee <code>    two characters new licensee code
+    -p, --pad-value <value>      pad the rom to the next valid size with the given byte
+    -f, --fix-spec <lhg>         fix the nintendo logo (l), header checksum (h), global checksum (g)
+    -v, --validate               same as -f lhg
+    -o, --output <file>          write to the given file instead of overwriting the rom";
+
+    #[derive(Default, Debug)]
+    pub struct FixOptions {
+        pub title: Option<String>,
+        pub cgb_flag: Option<u8>,
+        pub sgb: bool,
+        pub cartridge_type: Option<u8>,
+        pub ram_size: Option<u8>,
+        pub old_licensee_code: Option<u8>,
+        pub new_licensee_code: Option<[u8; 2]>,
+        pub pad_value: Option<u8>,
+        pub fix_logo: bool,
+        pub fix_header_checksum: bool,
+        pub fix_global_checksum: bool,
+    }
+
+    //accepts both decimal and 0x prefixed hex values
+    fn parse_number(value: &str) -> Result<u8, String> {
+        let parsed = match value.strip_prefix("0x").or(value.strip_prefix("$")) {
+            Some(hex_value) => u8::from_str_radix(hex_value, 16),
+            None => value.parse::<u8>(),
+        };
+        parsed.map_err(|_| format!("invalid value {}", value))
+    }
+
+    fn parse_cartridge_type(value: &str) -> Result<u8, String> {
+        if let Ok(cartridge_type) = parse_number(value) {
+            return Ok(cartridge_type);
+        }
+        CARTRIDGE_TYPES
+            .entries()
+            .find(|(_, name)| name.eq_ignore_ascii_case(&value.replace(' ', "+")))
+            .map(|(cartridge_type, _)| *cartridge_type)
+            .ok_or(format!("unknown mbc type {}", value))
+    }
+
+    fn parse_new_licensee_code(value: &str) -> Result<[u8; 2], String> {
+        match value.as_bytes() {
+            [first, second] => Ok([*first, *second]),
+            _ => Err(format!("new licensee code {} must be two characters", value)),
+        }
+    }
+
+    //apply the requested header changes, the rom is padded first so the rom size matches the final length
+    pub fn fix_rom(rom_buffer: &mut Vec<u8>, options: &FixOptions) -> Result<(), String> {
+        if let Some(pad_value) = options.pad_value {
+            let padded_length = (0x00..=0x08)
+                .map(|rom_size| (32 * 1024) << rom_size)
+                .find(|rom_length| *rom_length >= rom_buffer.len())
+                .ok_or("rom is too big to be padded")?;
+            rom_buffer.resize(padded_length, pad_value);
+        }
+
+        let mut cartridge_info =
+            CartridgeInfo::from_rom(rom_buffer).ok_or("rom is too small to contain a header")?;
+
+        if let Some(title) = &options.title {
+            if title.len() > 15 {
+                eprintln!("warning: title {} truncated to 15 characters", title);
+            }
+            cartridge_info.set_title(title);
+        }
+        if let Some(cgb_flag) = options.cgb_flag {
+            cartridge_info.set_cgb_flag(cgb_flag);
+        }
+        if options.sgb {
+            cartridge_info.set_sgb_flag(0x03);
+            cartridge_info.set_old_licensee_code(0x33);
+        }
+        if let Some(cartridge_type) = options.cartridge_type {
+            cartridge_info.set_cartridge_type(cartridge_type);
+        }
+        if let Some(ram_size) = options.ram_size {
+            cartridge_info.set_ram_size(ram_size);
+        }
+        if let Some(old_licensee_code) = options.old_licensee_code {
+            if options.sgb && old_licensee_code != 0x33 {
+                eprintln!("warning: sgb functions need the old licensee code to be 0x33");
+            }
+            cartridge_info.set_old_licensee_code(old_licensee_code);
+        }
+        if let Some(new_licensee_code) = options.new_licensee_code {
+            cartridge_info.set_new_licensee_code(new_licensee_code);
+        }
+        match rom_size_code(rom_buffer.len()) {
+            Some(rom_size) => cartridge_info.set_rom_size(rom_size),
+            None => eprintln!(
+                "warning: rom length 0x{:X} isn't a valid rom size, use -p to pad it",
+                rom_buffer.len()
+            ),
+        }
+        if options.fix_logo {
+            cartridge_info.fix_nintendo_logo();
+        }
+        if options.fix_header_checksum {
+            cartridge_info.fix_header_checksum();
+        }
+        cartridge_info.write_to_rom(rom_buffer);
+
+        if options.fix_global_checksum {
+            cartridge_info.fix_global_checksum(rom_buffer);
+            cartridge_info.write_to_rom(rom_buffer);
+        }
+        Ok(())
+    }
+
+    fn parse_arguments(args: &[String]) -> Result<(FixOptions, String, Option<String>), String> {
+        let mut options = FixOptions::default();
+        let mut rom_path = None;
+        let mut output_path = None;
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            let mut next_value = || {
+                args.next()
+                    .cloned()
+                    .ok_or(format!("missing value for {}", arg))
+            };
+            match arg.as_str() {
+                "-t" | "--title" => options.title = Some(next_value()?),
+                "-c" | "--cgb-compatible" => options.cgb_flag = Some(0x80),
+                "-C" | "--cgb-only" => options.cgb_flag = Some(0xC0),
+                "-s" | "--sgb-compatible" => options.sgb = true,
+                "-m" | "--mbc-type" => {
+                    options.cartridge_type = Some(parse_cartridge_type(&next_value()?)?)
+                }
+                "-r" | "--ram-size" => options.ram_size = Some(parse_number(&next_value()?)?),
+                "-l" | "--old-licensee" => {
+                    options.old_licensee_code = Some(parse_number(&next_value()?)?)
+                }
+                "-k" | "--new-licensee" => {
+                    options.new_licensee_code = Some(parse_new_licensee_code(&next_value()?)?)
+                }
+                "-p" | "--pad-value" => options.pad_value = Some(parse_number(&next_value()?)?),
+                "-f" | "--fix-spec" => {
+                    for fix in next_value()?.chars() {
+                        match fix {
+                            'l' => options.fix_logo = true,
+                            'h' => options.fix_header_checksum = true,
+                            'g' => options.fix_global_checksum = true,
+                            _ => return Err(format!("unknown fix {}", fix)),
+                        }
+                    }
+                }
+                "-v" | "--validate" => {
+                    options.fix_logo = true;
+                    options.fix_header_checksum = true;
+                    options.fix_global_checksum = true;
+                }
+                "-o" | "--output" => output_path = Some(next_value()?),
+                value if value.starts_with('-') => return Err(format!("unknown option {}", value)),
+                value => rom_path = Some(value.to_owned()),
+            }
+        }
+
+        let rom_path = rom_path.ok_or("missing rom")?;
+        Ok((options, rom_path, output_path))
+    }
+
+    //pog_boy fix [options] <rom>
+    pub fn run(args: &[String]) {
+        let (options, rom_path, output_path) = match parse_arguments(args) {
+            Ok(arguments) => arguments,
+            Err(error) => {
+                eprintln!("{}\n{}", error, USAGE);
+                process::exit(2);
+            }
+        };
+
+        let mut rom_buffer = fs::read(&rom_path).unwrap_or_else(|error| {
+            eprintln!("{}: {}", rom_path, error);
+            process::exit(1);
+        });
+
+        if let Err(error) = fix_rom(&mut rom_buffer, &options) {
+            eprintln!("{}: {}", rom_path, error);
+            process::exit(1);
+        }
+
+        let output_path = output_path.unwrap_or(rom_path);
+        if let Err(error) = fs::write(&output_path, rom_buffer) {
+            eprintln!("{}: {}", output_path, error);
+            process::exit(1);
+        }
+    }
+}
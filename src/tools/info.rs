@@ -0,0 +1,210 @@
+pub mod info {
+    use crate::memory::cartridge::cartridge::{
+        compute_global_checksum, rom_crc32, rom_sha1, CartridgeInfo,
+    };
+    use serde::Serialize;
+    use std::fmt;
+    use std::fmt::Formatter;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::process;
+
+    const ROM_EXTENSIONS: [&str; 3] = ["gb", "gbc", "sgb"];
+
+    #[derive(Serialize, Debug)]
+    pub struct RomReport {
+        pub file: String,
+        pub title: String,
+        pub mapper: String,
+        pub cartridge_type: u8,
+        pub rom_size: Option<usize>,
+        pub ram_size: Option<usize>,
+        pub header_checksum: u8,
+        pub header_checksum_valid: bool,
+        pub global_checksum: u16,
+        pub global_checksum_valid: bool,
+        pub nintendo_logo_valid: bool,
+        pub cgb_support: bool,
+        pub cgb_only: bool,
+        pub sgb_support: bool,
+        pub licensee: String,
+        pub crc32: String,
+        pub sha1: String,
+    }
+
+    fn format_size(size: Option<usize>) -> String {
+        match size {
+            Some(0) => "none".to_owned(),
+            Some(size) if size >= 1024 * 1024 && size % (1024 * 1024) == 0 => {
+                format!("{} MiB", size / (1024 * 1024))
+            }
+            Some(size) => format!("{} KiB", size / 1024),
+            None => "unknown".to_owned(),
+        }
+    }
+
+    fn format_validity(valid: bool) -> &'static str {
+        if valid {
+            "valid"
+        } else {
+            "INVALID"
+        }
+    }
+
+    fn format_support(supported: bool) -> &'static str {
+        if supported {
+            "yes"
+        } else {
+            "no"
+        }
+    }
+
+    impl fmt::Display for RomReport {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            writeln!(f, "{}", self.file)?;
+            writeln!(f, "  title:           {}", self.title)?;
+            writeln!(
+                f,
+                "  mapper:          {} (0x{:02X})",
+                self.mapper, self.cartridge_type
+            )?;
+            writeln!(f, "  rom size:        {}", format_size(self.rom_size))?;
+            writeln!(f, "  ram size:        {}", format_size(self.ram_size))?;
+            writeln!(
+                f,
+                "  header checksum: 0x{:02X} ({})",
+                self.header_checksum,
+                format_validity(self.header_checksum_valid)
+            )?;
+            writeln!(
+                f,
+                "  global checksum: 0x{:04X} ({})",
+                self.global_checksum,
+                format_validity(self.global_checksum_valid)
+            )?;
+            writeln!(
+                f,
+                "  nintendo logo:   {}",
+                format_validity(self.nintendo_logo_valid)
+            )?;
+            writeln!(
+                f,
+                "  cgb:             {}",
+                if self.cgb_only {
+                    "only"
+                } else {
+                    format_support(self.cgb_support)
+                }
+            )?;
+            writeln!(f, "  sgb:             {}", format_support(self.sgb_support))?;
+            writeln!(f, "  licensee:        {}", self.licensee)?;
+            writeln!(f, "  crc32:           {}", self.crc32)?;
+            write!(f, "  sha1:            {}", self.sha1)
+        }
+    }
+
+    //build a report from a rom dump, None if the dump is too small to contain a header
+    pub fn rom_report(file: &Path, rom_buffer: &[u8]) -> Option<RomReport> {
+        let cartridge_info = CartridgeInfo::from_rom(rom_buffer)?;
+        Some(RomReport {
+            file: file.display().to_string(),
+            title: cartridge_info.game_title().to_owned(),
+            mapper: cartridge_info.mapper_name().to_owned(),
+            cartridge_type: cartridge_info.cartridge_type(),
+            rom_size: cartridge_info.rom_size_bytes(),
+            ram_size: cartridge_info.ram_size_bytes(),
+            header_checksum: cartridge_info.header_checksum(),
+            header_checksum_valid: cartridge_info.is_header_checksum_valid(),
+            global_checksum: cartridge_info.global_checksum(),
+            global_checksum_valid: cartridge_info.global_checksum()
+                == compute_global_checksum(rom_buffer),
+            nintendo_logo_valid: cartridge_info.is_nintendo_logo_valid(),
+            cgb_support: cartridge_info.supports_cgb(),
+            cgb_only: cartridge_info.is_cgb_only(),
+            sgb_support: cartridge_info.supports_sgb(),
+            licensee: cartridge_info.licensee().to_owned(),
+            crc32: format!("{:08x}", rom_crc32(rom_buffer)),
+            sha1: rom_sha1(rom_buffer),
+        })
+    }
+
+    //a directory is scanned (not recursively) for files with a rom extension
+    fn collect_rom_files(path: &Path) -> Vec<PathBuf> {
+        if !path.is_dir() {
+            return vec![path.to_path_buf()];
+        }
+
+        let mut rom_files: Vec<PathBuf> = match fs::read_dir(path) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|file| {
+                    file.extension()
+                        .and_then(|extension| extension.to_str())
+                        .map(|extension| {
+                            ROM_EXTENSIONS.contains(&&*extension.to_ascii_lowercase())
+                        })
+                        .unwrap_or(false)
+                })
+                .collect(),
+            Err(error) => {
+                eprintln!("{}: {}", path.display(), error);
+                vec![]
+            }
+        };
+        rom_files.sort();
+        rom_files
+    }
+
+    //pog_boy info [--json] <rom or directory>...
+    pub fn run(args: &[String]) {
+        let json_output = args.iter().any(|arg| arg == "--json");
+        let paths: Vec<&String> = args.iter().filter(|arg| !arg.starts_with("--")).collect();
+        if paths.is_empty() {
+            eprintln!("usage: pog_boy info [--json] <rom or directory>...");
+            process::exit(2);
+        }
+
+        let batch_mode = paths.len() > 1 || paths.iter().any(|path| Path::new(path).is_dir());
+        let mut reports: Vec<RomReport> = vec![];
+        let mut has_failures = false;
+
+        for path in paths {
+            for file in collect_rom_files(Path::new(path)) {
+                match fs::read(&file) {
+                    Ok(rom_buffer) => match rom_report(&file, &rom_buffer) {
+                        Some(report) => reports.push(report),
+                        None => {
+                            eprintln!("{}: too small to contain a cartridge header", file.display());
+                            has_failures = true;
+                        }
+                    },
+                    Err(error) => {
+                        eprintln!("{}: {}", file.display(), error);
+                        has_failures = true;
+                    }
+                }
+            }
+        }
+
+        if json_output {
+            let json = if batch_mode {
+                serde_json::to_string_pretty(&reports)
+            } else {
+                match reports.first() {
+                    Some(report) => serde_json::to_string_pretty(report),
+                    None => Ok("null".to_owned()),
+                }
+            };
+            println!("{}", json.expect("Can't serialize rom report"));
+        } else {
+            for report in reports.iter() {
+                println!("{}\n", report);
+            }
+        }
+
+        if has_failures {
+            process::exit(1);
+        }
+    }
+}